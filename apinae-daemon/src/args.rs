@@ -1,14 +1,15 @@
 use std::error::Error;
 
-use clap::Parser;
+use apinae_lib::openapi;
+use clap::{Parser, Subcommand};
 
 /// Command line application for starting daemon and reading test configurations.
 #[derive(Parser, Debug, Clone)]
-#[command(version, about, long_about = None, author="Kjetil Fjellheim")]
+#[command(version, about, long_about = None, author="Kjetil Fjellheim", subcommand_negates_reqs = true)]
 pub struct Args {
-    /// Input file.
-    #[arg(long)]
-    pub file: String,
+    /// Input file. Required unless a command is specified.
+    #[arg(long, required = true)]
+    pub file: Option<String>,
 
     /// This starts the daemon with the specified setup from the file.
     #[arg(long)]
@@ -39,6 +40,40 @@ pub struct Args {
     /// List all predefined sets.
     #[arg(long)]
     pub list_predefined_sets: bool,
+
+    /// Command to run instead of starting the daemon.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands that do not use a configuration file.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Generate a local CA, a server certificate and a client certificate signed by the CA.
+    Cert {
+        /// Directory the certificates are written to.
+        #[arg(long, default_value = ".")]
+        dir: String,
+
+        /// Subject alternative name of the server certificate. Multiple names can be specified.
+        /// Defaults to localhost and 127.0.0.1.
+        #[arg(long)]
+        san: Vec<String>,
+    },
+    /// Import an OpenAPI 3.x document in JSON or YAML as a setup with a mocked endpoint per operation.
+    Import {
+        /// The OpenAPI document.
+        #[arg(long)]
+        openapi: String,
+
+        /// Configuration file the setup is added to. It is created if it does not exist.
+        #[arg(long)]
+        output: String,
+
+        /// Http port of the generated server.
+        #[arg(long, default_value_t = openapi::DEFAULT_HTTP_PORT)]
+        port: u16,
+    },
 }
 
 /// Parse a single key-value pair
@@ -60,7 +95,7 @@ mod test {
     #[test]
     fn test_daemon_args() {
         let args = Args::parse_from(["apinae-daemon", "--file", "test.json", "--id", "1"]);
-        assert_eq!(args.file, "test.json");
+        assert_eq!(args.file, Some("test.json".to_string()));
         assert_eq!(args.id, Some("1".to_string()));
         assert!(!args.list);
     }
@@ -68,8 +103,36 @@ mod test {
     #[test]
     fn test_daemon_args_list() {
         let args = Args::parse_from(["apinae-daemon", "--file", "test.json", "--list"]);
-        assert_eq!(args.file, "test.json");
+        assert_eq!(args.file, Some("test.json".to_string()));
         assert_eq!(args.id, None);
         assert!(args.list);
     }
+
+    #[test]
+    fn test_daemon_args_cert() {
+        let args = Args::parse_from(["apinae-daemon", "cert", "--dir", "certs", "--san", "localhost", "--san", "10.0.0.1"]);
+        assert_eq!(args.file, None);
+        match args.command {
+            Some(Command::Cert { dir, san }) => {
+                assert_eq!(dir, "certs");
+                assert_eq!(san, vec!["localhost".to_string(), "10.0.0.1".to_string()]);
+            }
+            _ => panic!("Missing command"),
+        }
+        assert!(Args::try_parse_from(["apinae-daemon", "--id", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_daemon_args_import() {
+        let args = Args::parse_from(["apinae-daemon", "import", "--openapi", "petstore.yaml", "--output", "test.json"]);
+        assert_eq!(args.file, None);
+        match args.command {
+            Some(Command::Import { openapi, output, port }) => {
+                assert_eq!(openapi, "petstore.yaml");
+                assert_eq!(output, "test.json");
+                assert_eq!(port, 8080);
+            }
+            _ => panic!("Missing command"),
+        }
+    }
 }
@@ -1,13 +1,17 @@
 mod args;
 mod server;
 
+use std::path::Path;
+
 use clap::Parser;
 
 use apinae_lib::{
+    cert,
     config::{AppConfiguration, SetupConfiguration},
     error::ApplicationError,
+    openapi,
 };
-use args::Args;
+use args::{Args, Command};
 use server::setup::ServerSetup;
 
 /**
@@ -19,6 +23,9 @@ use server::setup::ServerSetup;
 async fn main() -> Result<(), ApplicationError> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     let args = Args::parse();
+    if let Some(command) = &args.command {
+        return run_command(command);
+    }
     let config = read_input_file(&args)?;
     init(args, config).await?;
     Ok(())
@@ -37,10 +44,47 @@ async fn main() -> Result<(), ApplicationError> {
  * An error if the input file could not be read.
  */
 fn read_input_file(args: &Args) -> Result<AppConfiguration, ApplicationError> {
-    let config = AppConfiguration::load(&args.file)?;
+    let file = args.file.as_deref().ok_or(ApplicationError::CouldNotFind("Missing file".to_string()))?;
+    let config = AppConfiguration::load(file)?;
     Ok(config)
 }
 
+/**
+ * Run a command that does not use a configuration file.
+ *
+ * # Arguments
+ * `command`: The command to run.
+ *
+ * # Returns
+ * Ok if the command was run successfully.
+ *
+ * # Errors
+ * An error if the certificates could not be generated.
+ * An error if the OpenAPI document could not be imported.
+ * An error if the configuration file could not be read or written.
+ */
+fn run_command(command: &Command) -> Result<(), ApplicationError> {
+    match command {
+        Command::Cert { dir, san } => {
+            let certificates = cert::generate_certificates(Path::new(dir), san)?;
+            println!("CA certificate: {}", certificates.ca_certificate);
+            println!("CA private key: {}", certificates.ca_private_key);
+            println!("Server certificate: {}", certificates.server_certificate);
+            println!("Server private key: {}", certificates.server_private_key);
+            println!("Client certificate: {}", certificates.client_certificate);
+            println!("Client private key: {}", certificates.client_private_key);
+        }
+        Command::Import { openapi, output, port } => {
+            let setup = openapi::import_file(openapi, *port)?;
+            let mut config = if Path::new(output).exists() { AppConfiguration::load(output)? } else { AppConfiguration::new(setup.name.clone(), setup.description.clone(), Vec::new()) };
+            println!("Imported setup {} with {} endpoints: {}", setup.name, setup.servers.iter().map(|server| server.endpoints.len()).sum::<usize>(), setup.id);
+            config.setups.push(setup);
+            config.save(output)?;
+        }
+    }
+    Ok(())
+}
+
 /**
  * Initialize the application with the specified arguments and configuration.
  *
@@ -167,8 +211,9 @@ async fn start_daemon(args: Args, config: &AppConfiguration) -> Result<(), Appli
     log::info!("Starting daemon with id: {setup_id} and args {args:?}");
     let setup = get_setup(setup_id.as_str(), config)?;
     let params = validate_parameters(setup, &args)?;
+    let setup = generate_certificates(setup, args.file.as_deref())?;
     let mut server_setup = ServerSetup::new();
-    server_setup.setup(setup, params).await?;
+    server_setup.setup(&setup, params).await?;
     server_setup.start_servers().await.map_err(|err| ApplicationError::ServerStartUpError(format!("Server startup failed: {err}")))?;
     if args.verify {
         return Ok(());
@@ -179,6 +224,33 @@ async fn start_daemon(args: Args, config: &AppConfiguration) -> Result<(), Appli
     }
 }
 
+/**
+ * Generate the certificates for the https servers with auto generation. The certificates are written next to the configuration file.
+ * The CA directories of the forward proxies are resolved relative to the configuration file.
+ *
+ * # Arguments
+ * `setup`: The setup to generate the certificates for.
+ * `file`: The configuration file.
+ *
+ * # Returns
+ * The setup using the generated certificates.
+ *
+ * # Errors
+ * An error if the certificates could not be generated.
+ */
+fn generate_certificates(setup: &SetupConfiguration, file: Option<&str>) -> Result<SetupConfiguration, ApplicationError> {
+    let base_directory = file.and_then(|file| Path::new(file).parent()).unwrap_or(Path::new("."));
+    let mut setup = setup.clone();
+    for https_config in setup.servers.iter_mut().filter_map(|server| server.https_config.as_mut()) {
+        cert::apply_auto_generate(https_config, base_directory)?;
+    }
+    for intercept in setup.servers.iter_mut().filter_map(|server| server.forward_proxy.as_mut().and_then(|forward_proxy| forward_proxy.intercept.as_mut())) {
+        let directory = intercept.directory.as_ref().map_or_else(|| base_directory.to_path_buf(), |directory| base_directory.join(directory));
+        intercept.directory = Some(directory.to_string_lossy().to_string());
+    }
+    Ok(setup)
+}
+
 /**
  * Validate the parameters for the test.
  * All test parameters must be specified in the arguments.
@@ -0,0 +1,253 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use apinae_lib::{
+    config::{LoadBalancingConfiguration, LoadBalancingStrategy, RouteConfiguration, UpstreamConfiguration},
+    error::ApplicationError,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/**
+ * Default time in milliseconds a failed upstream is skipped.
+ */
+const DEFAULT_COOLDOWN_MS: u64 = 10000;
+
+/**
+ * The state shared between requests.
+ */
+struct BalancerState {
+    // The next upstream for round robin.
+    next: usize,
+    // When each upstream is healthy again after a failure.
+    unhealthy_until: Vec<Option<Instant>>,
+    // The random generator.
+    rng: StdRng,
+}
+
+/**
+ * Balances the requests of a route between the upstreams and tracks their health.
+ */
+pub struct UpstreamBalancer {
+    // The upstreams.
+    upstreams: Vec<UpstreamConfiguration>,
+    // The load balancing configuration.
+    configuration: LoadBalancingConfiguration,
+    // The state.
+    state: Mutex<BalancerState>,
+}
+
+impl UpstreamBalancer {
+    /**
+     * Create a balancer for the route.
+     *
+     * # Arguments
+     * `route_configuration`: The route configuration.
+     *
+     * # Returns
+     * The balancer, or None if the route has no upstreams.
+     */
+    pub fn new(route_configuration: &RouteConfiguration) -> Option<Self> {
+        let upstreams = route_configuration.upstreams.clone().filter(|upstreams| !upstreams.is_empty())?;
+        let state = BalancerState { next: 0, unhealthy_until: vec![None; upstreams.len()], rng: StdRng::from_os_rng() };
+        Some(UpstreamBalancer { upstreams, configuration: route_configuration.load_balancing.clone().unwrap_or_default(), state: Mutex::new(state) })
+    }
+
+    /**
+     * Get the url of the upstream.
+     *
+     * # Arguments
+     * `index`: The index of the upstream.
+     *
+     * # Returns
+     * The url.
+     */
+    pub fn url(&self, index: usize) -> &str {
+        &self.upstreams[index].url
+    }
+
+    /**
+     * Get the upstreams to try for a request. The first is chosen by the strategy and the following are the
+     * other healthy upstreams in order, limited by the number of retries. If no upstream is healthy all upstreams are used.
+     *
+     * # Returns
+     * The indexes of the upstreams.
+     *
+     * # Errors
+     * An error if the state is poisoned.
+     */
+    pub fn attempts(&self) -> Result<Vec<usize>, ApplicationError> {
+        self.attempts_at(Instant::now())
+    }
+
+    /**
+     * Mark the upstream as failed. It is skipped until the cool-down has passed.
+     *
+     * # Arguments
+     * `index`: The index of the upstream.
+     *
+     * # Errors
+     * An error if the state is poisoned.
+     */
+    pub fn mark_failure(&self, index: usize) -> Result<(), ApplicationError> {
+        log::info!("Upstream {} failed", self.url(index));
+        let cooldown = Duration::from_millis(self.configuration.cooldown_ms.unwrap_or(DEFAULT_COOLDOWN_MS));
+        self.lock()?.unhealthy_until[index] = Some(Instant::now() + cooldown);
+        Ok(())
+    }
+
+    /**
+     * Mark the upstream as healthy.
+     *
+     * # Arguments
+     * `index`: The index of the upstream.
+     *
+     * # Errors
+     * An error if the state is poisoned.
+     */
+    pub fn mark_success(&self, index: usize) -> Result<(), ApplicationError> {
+        self.lock()?.unhealthy_until[index] = None;
+        Ok(())
+    }
+
+    /**
+     * Get the upstreams to try for a request at the specified time.
+     *
+     * # Arguments
+     * `now`: The current time.
+     *
+     * # Returns
+     * The indexes of the upstreams.
+     *
+     * # Errors
+     * An error if the state is poisoned.
+     */
+    fn attempts_at(&self, now: Instant) -> Result<Vec<usize>, ApplicationError> {
+        let mut state = self.lock()?;
+        let mut healthy: Vec<usize> = (0..self.upstreams.len()).filter(|index| state.unhealthy_until[*index].is_none_or(|until| until <= now)).collect();
+        if healthy.is_empty() {
+            log::info!("No healthy upstreams. Trying all upstreams");
+            healthy = (0..self.upstreams.len()).collect();
+        }
+        let first = match self.configuration.strategy {
+            LoadBalancingStrategy::Failover => 0,
+            LoadBalancingStrategy::RoundRobin => {
+                let first = healthy.iter().position(|index| *index >= state.next).unwrap_or(0);
+                state.next = healthy[first] + 1;
+                first
+            }
+            LoadBalancingStrategy::Random => state.rng.random_range(0..healthy.len()),
+            LoadBalancingStrategy::Weighted => {
+                let weights: Vec<u64> = healthy.iter().map(|index| u64::from(self.upstreams[*index].weight.unwrap_or(1))).collect();
+                let total: u64 = weights.iter().sum();
+                if total == 0 {
+                    0
+                } else {
+                    let mut value = state.rng.random_range(0..total);
+                    weights
+                        .iter()
+                        .position(|weight| {
+                            if value < *weight {
+                                return true;
+                            }
+                            value -= weight;
+                            false
+                        })
+                        .unwrap_or(0)
+                }
+            }
+        };
+        healthy.rotate_left(first);
+        healthy.truncate(self.configuration.retries.unwrap_or(0) as usize + 1);
+        Ok(healthy)
+    }
+
+    /**
+     * Lock the state.
+     *
+     * # Returns
+     * The locked state.
+     *
+     * # Errors
+     * An error if the state is poisoned.
+     */
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, BalancerState>, ApplicationError> {
+        self.state.lock().map_err(|err| ApplicationError::RoutingError(format!("Failed to lock upstream state: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /**
+     * Create a balancer with three upstreams.
+     */
+    fn get_balancer(strategy: LoadBalancingStrategy, retries: Option<u32>) -> UpstreamBalancer {
+        let mut route = RouteConfiguration::new(String::new(), None, None, false, false, false, None, None, None, None, None, None);
+        route.upstreams = Some(vec![
+            UpstreamConfiguration::new("http://a".to_string(), None),
+            UpstreamConfiguration::new("http://b".to_string(), Some(0)),
+            UpstreamConfiguration::new("http://c".to_string(), None),
+        ]);
+        route.load_balancing = Some(LoadBalancingConfiguration::new(strategy, Some(1000), retries));
+        UpstreamBalancer::new(&route).unwrap()
+    }
+
+    /**
+     * Verify that no balancer is created without upstreams.
+     */
+    #[test]
+    fn test_no_upstreams() {
+        let route = RouteConfiguration::new("http://a".to_string(), None, None, false, false, false, None, None, None, None, None, None);
+        assert!(UpstreamBalancer::new(&route).is_none());
+    }
+
+    /**
+     * Verify that round robin skips failed upstreams until the cool-down has passed.
+     */
+    #[test]
+    fn test_round_robin() {
+        let balancer = get_balancer(LoadBalancingStrategy::RoundRobin, None);
+        let now = Instant::now();
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![0]);
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![1]);
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![2]);
+        balancer.mark_failure(0).unwrap();
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![1]);
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![2]);
+        assert_eq!(balancer.attempts_at(now + Duration::from_millis(2000)).unwrap(), vec![0]);
+    }
+
+    /**
+     * Verify that failover uses the first healthy upstream and retries the others.
+     */
+    #[test]
+    fn test_failover() {
+        let balancer = get_balancer(LoadBalancingStrategy::Failover, Some(5));
+        let now = Instant::now();
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![0, 1, 2]);
+        balancer.mark_failure(0).unwrap();
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![1, 2]);
+        balancer.mark_failure(1).unwrap();
+        balancer.mark_failure(2).unwrap();
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![0, 1, 2]);
+        balancer.mark_success(2).unwrap();
+        assert_eq!(balancer.attempts_at(now).unwrap(), vec![2]);
+    }
+
+    /**
+     * Verify that an upstream with weight zero is never chosen first.
+     */
+    #[test]
+    fn test_weighted() {
+        let balancer = get_balancer(LoadBalancingStrategy::Weighted, Some(1));
+        for _ in 0..100 {
+            let attempts = balancer.attempts().unwrap();
+            assert_eq!(attempts.len(), 2);
+            assert_ne!(attempts[0], 1);
+        }
+    }
+}
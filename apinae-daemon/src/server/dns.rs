@@ -0,0 +1,524 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use apinae_lib::{
+    config::{DnsFaultType, DnsRecordConfiguration, DnsRecordType, DnsServerConfiguration},
+    error::ApplicationError,
+};
+use hickory_proto::{
+    op::{Message, MessageType, ResponseCode},
+    rr::{
+        rdata::{A, AAAA, CNAME, SRV, TXT},
+        Name, RData, Record, RecordType,
+    },
+};
+use log::{error, info};
+use regex::Regex;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+    task::JoinHandle,
+};
+
+use super::{common::StartableServer, fault::check_probability};
+
+/**
+ * The largest udp message received.
+ */
+const MAX_UDP_MESSAGE_SIZE: usize = 65_535;
+
+/**
+ * The maximum number of CNAME records followed for a query.
+ */
+const MAX_CNAME_DEPTH: usize = 8;
+
+/**
+ * Time to wait for the forward server.
+ */
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/**
+ * The `AppDnsServer` struct is used to configure and start the dns server.
+ */
+pub struct AppDnsServer {
+    // Server configuration.
+    dns_server: DnsServerConfiguration,
+}
+
+/**
+ * A compiled dns fault.
+ */
+struct Fault {
+    // The regular expression the name must match. Every name matches if not set.
+    name: Option<Regex>,
+    // The response code returned.
+    response_code: ResponseCode,
+    // Probability that the fault is injected.
+    probability: Option<f64>,
+}
+
+/**
+ * The records and faults compiled. It is shared by the queries.
+ */
+struct Zone {
+    // The records by lowercase name without the trailing dot.
+    records: HashMap<String, Vec<Record>>,
+    // The faults.
+    faults: Vec<Fault>,
+    // Time to wait before answering.
+    delay_ms: Option<u64>,
+    // The server unknown names are forwarded to.
+    forward: Option<String>,
+}
+
+impl AppDnsServer {
+    /**
+     * Create a new `AppDnsServer`.
+     *
+     * # Arguments
+     * `dns_server`: The DNS server configuration.
+     *
+     * # Returns
+     * The created `AppDnsServer`.
+     */
+    pub fn new(dns_server: &DnsServerConfiguration) -> Self {
+        AppDnsServer { dns_server: dns_server.clone() }
+    }
+
+    /**
+     * Start the server on udp and tcp. Each query is answered in its own task.
+     *
+     * # Returns
+     * Ok if the server was started.
+     *
+     * # Errors
+     * An error if the port could not be bound or a record or fault is invalid.
+     */
+    pub async fn start_server(&self) -> Result<(), ApplicationError> {
+        let zone = Arc::new(compile_zone(&self.dns_server)?);
+        let address = SocketAddr::from(([127, 0, 0, 1], self.dns_server.port));
+        let udp_socket = Arc::new(UdpSocket::bind(address).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create dns udp socket: {err}")))?);
+        let tcp_listener = TcpListener::bind(address).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create dns tcp listener: {err}")))?;
+        info!("Dns listening on: {}", self.dns_server.port);
+
+        let udp_zone = zone.clone();
+        tokio::spawn(async move {
+            let mut buffer = vec![0; MAX_UDP_MESSAGE_SIZE];
+            loop {
+                let (read, peer) = match udp_socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(err) => {
+                        error!("Failed to receive dns query: {err}");
+                        continue;
+                    }
+                };
+                let request = buffer[..read].to_vec();
+                let udp_socket = udp_socket.clone();
+                let zone = udp_zone.clone();
+                tokio::spawn(async move {
+                    let result = match zone.resolve(&request).await {
+                        Ok(response) => udp_socket.send_to(&response, peer).await.map(|_| ()).map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to send dns response: {err}"))),
+                        Err(err) => Err(err),
+                    };
+                    let _ = result.map_err(|err| {
+                        error!("Error answering dns query from {peer}: {err}");
+                    });
+                });
+            }
+        });
+        tokio::spawn(async move {
+            loop {
+                let stream = match tcp_listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        error!("Failed to accept dns connection: {err}");
+                        continue;
+                    }
+                };
+                let zone = zone.clone();
+                tokio::spawn(async move {
+                    let _ = handle_tcp_stream(stream, &zone).await.map_err(|err| {
+                        error!("Error handling dns connection: {err}");
+                    });
+                });
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Zone {
+    /**
+     * Answer the query. A matching fault is returned before the records. Unknown names are forwarded if configured.
+     *
+     * # Arguments
+     * `request`: The query message.
+     *
+     * # Returns
+     * The response message.
+     *
+     * # Errors
+     * An error if the query could not be decoded or the response could not be encoded.
+     */
+    async fn resolve(&self, request: &[u8]) -> Result<Vec<u8>, ApplicationError> {
+        let request_message = Message::from_vec(request).map_err(|err| ApplicationError::ServerStartUpError(format!("Invalid dns query: {err}")))?;
+        if let Some(delay_ms) = self.delay_ms {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        }
+        let mut response = Message::error_msg(request_message.id(), request_message.op_code(), ResponseCode::NoError);
+        response.set_authoritative(true).set_recursion_desired(request_message.recursion_desired()).set_message_type(MessageType::Response);
+        let Some(query) = request_message.queries().first() else {
+            response.set_response_code(ResponseCode::FormErr);
+            return encode(&response);
+        };
+        response.add_query(query.clone());
+        let name = normalize(&query.name().to_ascii());
+        info!("Dns query {} {name}", query.query_type());
+
+        if let Some(fault) =
+            self.faults.iter().find(|fault| fault.name.as_ref().is_none_or(|regex| regex.is_match(&name)) && fault.probability.is_none_or(|probability| rand::random::<f64>() < probability))
+        {
+            info!("Injecting {} for {name}", fault.response_code);
+            response.set_response_code(fault.response_code);
+            return encode(&response);
+        }
+        let Some(answers) = self.answer(&name, query.query_type(), 0) else {
+            let Some(forward) = &self.forward else {
+                response.set_response_code(ResponseCode::NXDomain);
+                return encode(&response);
+            };
+            return match forward_query(forward, request).await {
+                Ok(forwarded) => Ok(forwarded),
+                Err(err) => {
+                    error!("Failed to forward dns query for {name}: {err}");
+                    response.set_response_code(ResponseCode::ServFail);
+                    encode(&response)
+                }
+            };
+        };
+        response.add_answers(answers);
+        encode(&response)
+    }
+
+    /**
+     * Find the records of the type. CNAME records for the name are followed.
+     *
+     * # Arguments
+     * `name`: The lowercase name without the trailing dot.
+     * `record_type`: The record type.
+     * `depth`: The number of CNAME records followed.
+     *
+     * # Returns
+     * The records, empty if the name has no records of the type, or None if the name is unknown.
+     */
+    fn answer(&self, name: &str, record_type: RecordType, depth: usize) -> Option<Vec<Record>> {
+        let records = self.records.get(name)?;
+        let mut answers: Vec<Record> = records.iter().filter(|record| record.record_type() == record_type).cloned().collect();
+        if answers.is_empty() && record_type != RecordType::CNAME && depth < MAX_CNAME_DEPTH {
+            for record in records {
+                if let RData::CNAME(target) = record.data() {
+                    answers.push(record.clone());
+                    answers.extend(self.answer(&normalize(&target.0.to_ascii()), record_type, depth + 1).unwrap_or_default());
+                }
+            }
+        }
+        Some(answers)
+    }
+}
+
+/**
+ * Answer the queries on a tcp connection until it is closed. Each message is prefixed with its length as two bytes.
+ *
+ * # Arguments
+ * `stream`: The tcp stream.
+ * `zone`: The compiled zone.
+ *
+ * # Returns
+ * Ok if the client closed the connection.
+ *
+ * # Errors
+ * An error if the stream could not be read or written or a query could not be answered.
+ */
+async fn handle_tcp_stream(mut stream: TcpStream, zone: &Zone) -> Result<(), ApplicationError> {
+    let map_err = |err: std::io::Error| ApplicationError::ServerStartUpError(format!("Failed to handle dns connection: {err}"));
+    loop {
+        let length = match stream.read_u16().await {
+            Ok(length) => length,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(map_err(err)),
+        };
+        let mut request = vec![0; usize::from(length)];
+        stream.read_exact(&mut request).await.map_err(map_err)?;
+        let response = zone.resolve(&request).await?;
+        let length = u16::try_from(response.len()).map_err(|err| ApplicationError::ServerStartUpError(format!("Dns response too large: {err}")))?;
+        stream.write_u16(length).await.map_err(map_err)?;
+        stream.write_all(&response).await.map_err(map_err)?;
+    }
+}
+
+/**
+ * Send the query to the forward server over udp.
+ *
+ * # Arguments
+ * `forward`: The address of the forward server.
+ * `request`: The query message.
+ *
+ * # Returns
+ * The response message from the forward server.
+ *
+ * # Errors
+ * An error if the forward server could not be reached or did not answer in time.
+ */
+async fn forward_query(forward: &str, request: &[u8]) -> Result<Vec<u8>, ApplicationError> {
+    let map_err = |err: std::io::Error| ApplicationError::ServerStartUpError(format!("Failed to forward to {forward}: {err}"));
+    let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).await.map_err(map_err)?;
+    socket.send_to(request, forward).await.map_err(map_err)?;
+    let mut buffer = vec![0; MAX_UDP_MESSAGE_SIZE];
+    let read = tokio::time::timeout(FORWARD_TIMEOUT, socket.recv(&mut buffer)).await.map_err(|_| ApplicationError::ServerStartUpError(format!("Timeout forwarding to {forward}")))?.map_err(map_err)?;
+    buffer.truncate(read);
+    Ok(buffer)
+}
+
+/**
+ * Compile the records and faults.
+ *
+ * # Arguments
+ * `dns_server`: The DNS server configuration.
+ *
+ * # Returns
+ * The compiled zone.
+ *
+ * # Errors
+ * An error if a name, value or regular expression is invalid or an SRV record has no port.
+ * An error if a fault probability is outside 0.0 to 1.0.
+ */
+fn compile_zone(dns_server: &DnsServerConfiguration) -> Result<Zone, ApplicationError> {
+    let mut records: HashMap<String, Vec<Record>> = HashMap::new();
+    for record in &dns_server.records {
+        records.entry(normalize(&record.name)).or_default().push(compile_record(record)?);
+    }
+    let faults = dns_server
+        .faults
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .map(|fault| {
+            check_probability(fault.probability, "Dns fault probability")?;
+            Ok(Fault {
+                name: fault.name.as_ref().map(|name| Regex::new(name).map_err(|err| ApplicationError::ConfigurationError(format!("Error in regular expression {name}: {err}")))).transpose()?,
+                response_code: match fault.fault_type {
+                    DnsFaultType::NxDomain => ResponseCode::NXDomain,
+                    DnsFaultType::ServFail => ResponseCode::ServFail,
+                },
+                probability: fault.probability,
+            })
+        })
+        .collect::<Result<Vec<Fault>, ApplicationError>>()?;
+    Ok(Zone { records, faults, delay_ms: dns_server.delay_ms, forward: dns_server.forward.clone() })
+}
+
+/**
+ * Compile the record.
+ *
+ * # Arguments
+ * `record`: The record configuration.
+ *
+ * # Returns
+ * The record.
+ *
+ * # Errors
+ * An error if the name or value is invalid or an SRV record has no port.
+ */
+fn compile_record(record: &DnsRecordConfiguration) -> Result<Record, ApplicationError> {
+    let invalid = |err: String| ApplicationError::ConfigurationError(format!("Invalid {:?} record {}: {err}", record.record_type, record.name));
+    let rdata = match record.record_type {
+        DnsRecordType::A => RData::A(A(record.value.parse::<Ipv4Addr>().map_err(|err| invalid(err.to_string()))?)),
+        DnsRecordType::AAAA => RData::AAAA(AAAA(record.value.parse::<Ipv6Addr>().map_err(|err| invalid(err.to_string()))?)),
+        DnsRecordType::CNAME => RData::CNAME(CNAME(get_name(&record.value).map_err(invalid)?)),
+        DnsRecordType::TXT => RData::TXT(TXT::new(vec![record.value.clone()])),
+        DnsRecordType::SRV => RData::SRV(SRV::new(
+            record.priority.unwrap_or_default(),
+            record.weight.unwrap_or_default(),
+            record.port.ok_or_else(|| invalid("port is required".to_string()))?,
+            get_name(&record.value).map_err(invalid)?,
+        )),
+    };
+    Ok(Record::from_rdata(get_name(&record.name).map_err(invalid)?, record.ttl, rdata))
+}
+
+/**
+ * Get the fully qualified name.
+ *
+ * # Arguments
+ * `name`: The name with or without the trailing dot.
+ *
+ * # Returns
+ * The name.
+ *
+ * # Errors
+ * A description of the error if the name is invalid.
+ */
+fn get_name(name: &str) -> Result<Name, String> {
+    Name::from_ascii(format!("{}.", normalize(name))).map_err(|err| err.to_string())
+}
+
+/**
+ * Normalize the name to lowercase without the trailing dot.
+ *
+ * # Arguments
+ * `name`: The name.
+ *
+ * # Returns
+ * The normalized name.
+ */
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
+
+/**
+ * Encode the message.
+ *
+ * # Arguments
+ * `message`: The message.
+ *
+ * # Returns
+ * The encoded message.
+ *
+ * # Errors
+ * An error if the message could not be encoded.
+ */
+fn encode(message: &Message) -> Result<Vec<u8>, ApplicationError> {
+    message.to_vec().map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to encode dns response: {err}")))
+}
+
+impl StartableServer for AppDnsServer {
+    fn start_server(&mut self) -> Result<Vec<JoinHandle<()>>, ApplicationError> {
+        let dns_server = self.dns_server.clone();
+        let handle = tokio::spawn(async move {
+            let _ = AppDnsServer { dns_server }.start_server().await.map_err(|err| {
+                error!("Failed to start dns server: {err}");
+            });
+        });
+        Ok(vec![handle])
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use apinae_lib::config::DnsFaultConfiguration;
+    use hickory_proto::op::Query;
+
+    use super::*;
+
+    /**
+     * Create the query message.
+     */
+    fn get_query(name: &str, record_type: RecordType) -> Vec<u8> {
+        let mut message = Message::new();
+        message.set_id(7).add_query(Query::query(Name::from_ascii(name).unwrap(), record_type));
+        message.to_vec().unwrap()
+    }
+
+    /**
+     * Resolve the name with the zone.
+     */
+    async fn resolve(zone: &Zone, name: &str, record_type: RecordType) -> Message {
+        Message::from_vec(&zone.resolve(&get_query(name, record_type)).await.unwrap()).unwrap()
+    }
+
+    /**
+     * Create the dns server configuration used by the tests.
+     */
+    fn get_configuration(port: u16) -> DnsServerConfiguration {
+        let mut srv = DnsRecordConfiguration::new("_http._tcp.service.test".to_string(), DnsRecordType::SRV, "api.service.test".to_string());
+        srv.port = Some(8080);
+        DnsServerConfiguration::new(
+            port,
+            vec![
+                DnsRecordConfiguration::new("api.service.test".to_string(), DnsRecordType::A, "127.0.0.1".to_string()),
+                DnsRecordConfiguration::new("api.service.test".to_string(), DnsRecordType::AAAA, "::1".to_string()),
+                DnsRecordConfiguration::new("www.service.test.".to_string(), DnsRecordType::CNAME, "API.service.test".to_string()),
+                DnsRecordConfiguration::new("service.test".to_string(), DnsRecordType::TXT, "v=test".to_string()),
+                srv,
+            ],
+        )
+        .unwrap()
+    }
+
+    /**
+     * Verify answers for the record types, CNAME chains, names without records of the type and unknown names.
+     */
+    #[tokio::test]
+    async fn test_resolve() {
+        let zone = compile_zone(&get_configuration(0)).unwrap();
+        let response = resolve(&zone, "API.service.test.", RecordType::A).await;
+        assert_eq!(response.id(), 7);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers()[0].data(), &RData::A(A(Ipv4Addr::LOCALHOST)));
+        let response = resolve(&zone, "www.service.test.", RecordType::AAAA).await;
+        assert_eq!(response.answers().len(), 2);
+        assert_eq!(response.answers()[1].data(), &RData::AAAA(AAAA(Ipv6Addr::LOCALHOST)));
+        let response = resolve(&zone, "_http._tcp.service.test.", RecordType::SRV).await;
+        assert!(matches!(response.answers()[0].data(), RData::SRV(srv) if srv.port() == 8080));
+        let response = resolve(&zone, "service.test.", RecordType::TXT).await;
+        assert_eq!(response.answers().len(), 1);
+        let response = resolve(&zone, "service.test.", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+        assert_eq!(resolve(&zone, "other.test.", RecordType::A).await.response_code(), ResponseCode::NXDomain);
+    }
+
+    /**
+     * Verify that faults are returned for matching names.
+     */
+    #[tokio::test]
+    async fn test_faults() {
+        let mut configuration = get_configuration(0);
+        configuration.faults = Some(vec![
+            DnsFaultConfiguration::new(Some("^www\\.".to_string()), DnsFaultType::ServFail, None),
+            DnsFaultConfiguration::new(Some("^api\\.".to_string()), DnsFaultType::NxDomain, Some(0.0)),
+        ]);
+        let zone = compile_zone(&configuration).unwrap();
+        assert_eq!(resolve(&zone, "www.service.test.", RecordType::A).await.response_code(), ResponseCode::ServFail);
+        assert_eq!(resolve(&zone, "api.service.test.", RecordType::A).await.response_code(), ResponseCode::NoError);
+        configuration.faults = Some(vec![DnsFaultConfiguration::new(None, DnsFaultType::ServFail, Some(-1.0))]);
+        assert!(compile_zone(&configuration).is_err());
+    }
+
+    /**
+     * Verify that invalid records are configuration errors.
+     */
+    #[test]
+    fn test_invalid_records() {
+        let invalid = DnsServerConfiguration::new(0, vec![DnsRecordConfiguration::new("api.test".to_string(), DnsRecordType::A, "::1".to_string())]).unwrap();
+        assert!(compile_zone(&invalid).is_err());
+        let invalid = DnsServerConfiguration::new(0, vec![DnsRecordConfiguration::new("_a._tcp.test".to_string(), DnsRecordType::SRV, "api.test".to_string())]).unwrap();
+        assert!(compile_zone(&invalid).is_err());
+    }
+
+    /**
+     * Verify queries over udp and tcp and forwarding of unknown names.
+     */
+    #[tokio::test]
+    async fn test_server() {
+        AppDnsServer::new(&get_configuration(18_046)).start_server().await.unwrap();
+        let mut forwarding = DnsServerConfiguration::new(18_047, Vec::new()).unwrap();
+        forwarding.forward = Some("127.0.0.1:18046".to_string());
+        let zone = compile_zone(&forwarding).unwrap();
+        let response = resolve(&zone, "api.service.test.", RecordType::A).await;
+        assert_eq!(response.answers()[0].data(), &RData::A(A(Ipv4Addr::LOCALHOST)));
+
+        let mut stream = TcpStream::connect("127.0.0.1:18046").await.unwrap();
+        let query = get_query("api.service.test.", RecordType::AAAA);
+        stream.write_u16(u16::try_from(query.len()).unwrap()).await.unwrap();
+        stream.write_all(&query).await.unwrap();
+        let mut response = vec![0; usize::from(stream.read_u16().await.unwrap())];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(Message::from_vec(&response).unwrap().answers()[0].data(), &RData::AAAA(AAAA(Ipv6Addr::LOCALHOST)));
+    }
+}
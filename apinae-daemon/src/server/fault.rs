@@ -0,0 +1,399 @@
+use std::{any::Any, io::ErrorKind, sync::Arc, time::Duration};
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{dev::Extensions, http::Version, rt::net::TcpStream, HttpRequest, HttpResponse};
+use apinae_lib::{
+    config::{FaultConfiguration, FaultType},
+    error::ApplicationError,
+};
+use log::{error, info};
+use socket2::{SockRef, Socket};
+
+/**
+ * Default number of bytes sent for the `RandomData` fault.
+ */
+const DEFAULT_RANDOM_DATA_LENGTH: usize = 1024;
+/**
+ * Time to wait before failing the body in the `CloseAfterHeaders` fault. This gives the server time to flush the headers.
+ */
+const CLOSE_AFTER_HEADERS_DELAY_MS: u64 = 50;
+/**
+ * The response written by the `MalformedResponse` fault.
+ */
+const MALFORMED_RESPONSE: &[u8] = b"HTTP/1.1 abc Malformed\r\nContent-Length: -1\r\nTransfer-Encoding: chunked\r\n\r\nnot-a-chunk\r\n";
+/**
+ * The HTTP/2 GOAWAY frame written by the `GoAway` fault. The last stream id is 0 so no stream is processed, and the error code is NO_ERROR.
+ */
+const GOAWAY_FRAME: &[u8] = &[0, 0, 8, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+/**
+ * A duplicate of the socket for a connection. It is stored in the connection data so that
+ * the faults can manipulate the socket directly.
+ */
+#[derive(Clone)]
+pub struct ConnectionSocket(Arc<Socket>);
+
+/**
+ * Called when a connection is accepted. Stores a duplicate of the socket in the connection data.
+ *
+ * # Arguments
+ * `connection`: The accepted connection. Either a tcp stream, a tls stream or a unix stream.
+ * `data`: The connection data.
+ */
+pub fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    let socket = if let Some(stream) = connection.downcast_ref::<TcpStream>() {
+        SockRef::from(stream).try_clone()
+    } else if let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() {
+        SockRef::from(stream.get_ref().0).try_clone()
+    } else if let Some(stream) = get_openssl_stream(connection) {
+        SockRef::from(stream).try_clone()
+    } else if let Some(stream) = duplicate_unix_socket(connection) {
+        stream
+    } else {
+        return;
+    };
+    match socket {
+        Ok(socket) => {
+            data.insert(ConnectionSocket(Arc::new(socket)));
+        }
+        Err(err) => error!("Failed to duplicate connection socket: {err}"),
+    }
+}
+
+/**
+ * Get the tcp stream of an OpenSSL connection.
+ *
+ * # Arguments
+ * `connection`: The accepted connection.
+ *
+ * # Returns
+ * The tcp stream if the connection is an OpenSSL stream.
+ */
+#[cfg(feature = "openssl")]
+fn get_openssl_stream(connection: &dyn Any) -> Option<&TcpStream> {
+    connection.downcast_ref::<actix_tls::accept::openssl::TlsStream<TcpStream>>().map(|stream| stream.get_ref())
+}
+
+/**
+ * Get the tcp stream of an OpenSSL connection. OpenSSL is not enabled.
+ *
+ * # Arguments
+ * `connection`: The accepted connection.
+ *
+ * # Returns
+ * None.
+ */
+#[cfg(not(feature = "openssl"))]
+fn get_openssl_stream(_connection: &dyn Any) -> Option<&TcpStream> {
+    None
+}
+
+/**
+ * Duplicate the socket of a connection on a unix socket.
+ *
+ * # Arguments
+ * `connection`: The accepted connection.
+ *
+ * # Returns
+ * The duplicated socket if the connection is a unix stream, also when the protocol was detected from its preface.
+ */
+#[cfg(unix)]
+fn duplicate_unix_socket(connection: &dyn Any) -> Option<std::io::Result<Socket>> {
+    connection
+        .downcast_ref::<actix_web::rt::net::UnixStream>()
+        .or_else(|| connection.downcast_ref::<super::prefixed::PrefixedStream<actix_web::rt::net::UnixStream>>().map(|stream| stream.get_ref()))
+        .map(|stream| SockRef::from(stream).try_clone())
+}
+
+/**
+ * Duplicate the socket of a connection on a unix socket. Unix sockets are not supported.
+ *
+ * # Arguments
+ * `connection`: The accepted connection.
+ *
+ * # Returns
+ * None.
+ */
+#[cfg(not(unix))]
+fn duplicate_unix_socket(_connection: &dyn Any) -> Option<std::io::Result<Socket>> {
+    None
+}
+
+/**
+ * Apply the fault to the response. If no fault is configured or the fault is not triggered
+ * the response is returned unchanged.
+ *
+ * # Arguments
+ * `fault`: The fault configuration.
+ * `req`: The request.
+ * `response`: The response to return if the fault is not injected.
+ *
+ * # Returns
+ * The response.
+ */
+pub async fn apply_fault(fault: Option<&FaultConfiguration>, req: &HttpRequest, response: HttpResponse) -> HttpResponse {
+    match trigger(fault) {
+        Some(fault) => inject_fault(fault, req, response).await,
+        None => response,
+    }
+}
+
+/**
+ * Decide if the fault is injected for the request.
+ *
+ * # Arguments
+ * `fault`: The fault configuration.
+ *
+ * # Returns
+ * The fault if it is configured and triggered by the probability.
+ */
+pub fn trigger(fault: Option<&FaultConfiguration>) -> Option<&FaultConfiguration> {
+    fault.filter(|fault| should_inject(fault))
+}
+
+/**
+ * Check if the fault keeps the status and headers of the response. The other faults break the connection,
+ * so the response is not used.
+ *
+ * # Arguments
+ * `fault`: The fault configuration.
+ *
+ * # Returns
+ * True if the response is used.
+ */
+pub fn uses_response(fault: &FaultConfiguration) -> bool {
+    matches!(fault.fault_type, FaultType::CloseAfterHeaders | FaultType::RstStream)
+}
+
+/**
+ * Inject the triggered fault.
+ *
+ * # Arguments
+ * `fault`: The fault configuration.
+ * `req`: The request.
+ * `response`: The response whose status and headers are kept by the faults that use it.
+ *
+ * # Returns
+ * The response.
+ */
+pub async fn inject_fault(fault: &FaultConfiguration, req: &HttpRequest, response: HttpResponse) -> HttpResponse {
+    info!("Injecting fault {:?}", fault.fault_type);
+    let socket = req.conn_data::<ConnectionSocket>();
+    match fault.fault_type {
+        // Failing the body after the headers resets the stream on HTTP/2 and closes the connection on HTTP/1.
+        FaultType::CloseAfterHeaders | FaultType::RstStream => {
+            let mut response_builder = HttpResponse::build(response.status());
+            for (key, value) in response.headers() {
+                response_builder.append_header((key.clone(), value.clone()));
+            }
+            return response_builder.streaming(broken_body(Duration::from_millis(CLOSE_AFTER_HEADERS_DELAY_MS)));
+        }
+        FaultType::ConnectionReset => {
+            if let Some(socket) = socket {
+                let _ = socket.0.set_linger(Some(Duration::ZERO)).map_err(|err| error!("Failed to set linger: {err}"));
+            }
+        }
+        FaultType::EmptyReply => {
+            if let Some(socket) = socket {
+                shutdown(socket);
+            }
+        }
+        FaultType::MalformedResponse => {
+            if let Some(socket) = socket {
+                if is_plain_http1(req) {
+                    write_raw(socket, MALFORMED_RESPONSE).await;
+                }
+                shutdown(socket);
+            }
+        }
+        FaultType::GoAway => {
+            if let Some(socket) = socket {
+                // The frame can only be written directly without TLS.
+                if req.version() == Version::HTTP_2 && !req.app_config().secure() {
+                    write_raw(socket, GOAWAY_FRAME).await;
+                }
+                shutdown(socket);
+            }
+        }
+        FaultType::RandomData => {
+            if let Some(socket) = socket {
+                if is_plain_http1(req) {
+                    let data: Vec<u8> = (0..fault.random_data_length.unwrap_or(DEFAULT_RANDOM_DATA_LENGTH)).map(|_| rand::random::<u8>()).collect();
+                    write_raw(socket, &data).await;
+                }
+                shutdown(socket);
+            }
+        }
+    }
+    HttpResponse::Ok().streaming(broken_body(Duration::ZERO))
+}
+
+/**
+ * Check if the request is plain HTTP/1. Data written directly to the socket would break the TLS records or the HTTP/2 frames otherwise.
+ *
+ * # Arguments
+ * `req`: The request.
+ *
+ * # Returns
+ * True if the request is HTTP/1 without TLS.
+ */
+fn is_plain_http1(req: &HttpRequest) -> bool {
+    req.version() < Version::HTTP_2 && !req.app_config().secure()
+}
+
+/**
+ * Check that a probability is between 0.0 and 1.0.
+ *
+ * # Arguments
+ * `probability`: The optional probability.
+ * `name`: Name of the value used in the error message.
+ *
+ * # Errors
+ * An error if the probability is outside 0.0 to 1.0.
+ */
+pub fn check_probability(probability: Option<f64>, name: &str) -> Result<(), ApplicationError> {
+    match probability {
+        Some(probability) if !(0.0..=1.0).contains(&probability) => Err(ApplicationError::ConfigurationError(format!("{name} {probability} must be between 0.0 and 1.0"))),
+        _ => Ok(()),
+    }
+}
+
+/**
+ * Check if the fault should be injected based on the probability.
+ *
+ * # Arguments
+ * `fault`: The fault configuration.
+ *
+ * # Returns
+ * True if the fault should be injected.
+ */
+fn should_inject(fault: &FaultConfiguration) -> bool {
+    fault.probability.is_none_or(|probability| rand::random::<f64>() < probability)
+}
+
+/**
+ * Create a body that fails after the delay. When the body fails the server closes the connection.
+ *
+ * # Arguments
+ * `delay`: Time to wait before failing.
+ *
+ * # Returns
+ * The body stream.
+ */
+fn broken_body(delay: Duration) -> impl futures_util::Stream<Item = Result<actix_web::web::Bytes, std::io::Error>> {
+    futures_util::stream::once(async move {
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        Err(std::io::Error::new(ErrorKind::ConnectionAborted, "Fault injected"))
+    })
+}
+
+/**
+ * Write data directly to the socket, bypassing the http server.
+ *
+ * # Arguments
+ * `socket`: The connection socket.
+ * `data`: The data to write.
+ */
+async fn write_raw(socket: &ConnectionSocket, data: &[u8]) {
+    let mut written = 0;
+    while written < data.len() {
+        match socket.0.send(&data[written..]) {
+            Ok(count) => written += count,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => tokio::time::sleep(Duration::from_millis(1)).await,
+            Err(err) => {
+                error!("Failed to write fault data: {err}");
+                return;
+            }
+        }
+    }
+}
+
+/**
+ * Shut down both directions of the socket.
+ *
+ * # Arguments
+ * `socket`: The connection socket.
+ */
+fn shutdown(socket: &ConnectionSocket) {
+    let _ = socket.0.shutdown(std::net::Shutdown::Both).map_err(|err| error!("Failed to shut down connection: {err}"));
+}
+
+#[cfg(test)]
+mod test {
+
+    use actix_web::{http::StatusCode, test::TestRequest};
+
+    use super::*;
+
+    /**
+     * Verify the probability check.
+     */
+    #[test]
+    fn test_should_inject() {
+        assert!(should_inject(&FaultConfiguration::new(FaultType::EmptyReply, None, None)));
+        assert!(should_inject(&FaultConfiguration::new(FaultType::EmptyReply, Some(1.0), None)));
+        assert!(!should_inject(&FaultConfiguration::new(FaultType::EmptyReply, Some(0.0), None)));
+    }
+
+    /**
+     * Verify that probabilities outside 0.0 to 1.0 are rejected.
+     */
+    #[test]
+    fn test_check_probability() {
+        assert!(check_probability(None, "Fault probability").is_ok());
+        assert!(check_probability(Some(0.0), "Fault probability").is_ok());
+        assert!(check_probability(Some(1.0), "Fault probability").is_ok());
+        assert!(check_probability(Some(1.5), "Fault probability").is_err());
+        assert!(check_probability(Some(-1.0), "Fault probability").is_err());
+        assert!(check_probability(Some(f64::NAN), "Fault probability").is_err());
+    }
+
+    /**
+     * Verify that the response is unchanged when no fault is injected.
+     */
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_apply_no_fault() {
+        let req = TestRequest::default().to_http_request();
+        let response = apply_fault(None, &req, HttpResponse::Created().finish()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let fault = FaultConfiguration::new(FaultType::ConnectionReset, Some(0.0), None);
+        let response = apply_fault(Some(&fault), &req, HttpResponse::Created().finish()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    /**
+     * Verify that only plain HTTP/1 requests get data written directly to the socket.
+     */
+    #[test]
+    fn test_is_plain_http1() {
+        assert!(is_plain_http1(&TestRequest::default().to_http_request()));
+        assert!(is_plain_http1(&TestRequest::default().version(Version::HTTP_10).to_http_request()));
+        assert!(!is_plain_http1(&TestRequest::default().version(Version::HTTP_2).to_http_request()));
+    }
+
+    /**
+     * Verify which faults use the response.
+     */
+    #[test]
+    fn test_uses_response() {
+        assert!(uses_response(&FaultConfiguration::new(FaultType::CloseAfterHeaders, None, None)));
+        assert!(uses_response(&FaultConfiguration::new(FaultType::RstStream, None, None)));
+        assert!(!uses_response(&FaultConfiguration::new(FaultType::ConnectionReset, None, None)));
+        assert!(!uses_response(&FaultConfiguration::new(FaultType::EmptyReply, None, None)));
+        assert!(!uses_response(&FaultConfiguration::new(FaultType::MalformedResponse, None, None)));
+    }
+
+    /**
+     * Verify that the status and headers are kept when closing after headers.
+     */
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_apply_close_after_headers() {
+        let req = TestRequest::default().to_http_request();
+        let fault = FaultConfiguration::new(FaultType::CloseAfterHeaders, None, None);
+        let response = apply_fault(Some(&fault), &req, HttpResponse::Accepted().append_header(("X-Test", "1")).finish()).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(response.headers().get("X-Test").unwrap(), "1");
+    }
+}
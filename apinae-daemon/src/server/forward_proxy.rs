@@ -0,0 +1,400 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use apinae_lib::{cert::CertificateAuthority, config::ForwardProxyConfiguration, error::ApplicationError};
+use log::{error, info};
+use regex::Regex;
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    ServerConfig,
+};
+use tokio::{
+    io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::TlsAcceptor;
+
+use super::{prefixed::PrefixedStream, tcp::READ_BUFFER_SIZE};
+
+/**
+ * The largest request head read from a client.
+ */
+const MAX_HEAD_SIZE: usize = 16_384;
+/**
+ * The response sent when a tunnel is established.
+ */
+const CONNECTION_ESTABLISHED: &[u8] = b"HTTP/1.1 200 Connection Established\r\n\r\n";
+
+/**
+ * The targets of the intercepted tunnels by the local address of the connection to the http server.
+ * The http server finds the target of a request by the peer address of the connection.
+ */
+#[derive(Debug, Default)]
+pub struct TunnelTargets(Mutex<HashMap<SocketAddr, String>>);
+
+impl TunnelTargets {
+    /**
+     * Get the target of a connection to the http server.
+     *
+     * # Arguments
+     * `address`: The peer address of the connection.
+     *
+     * # Returns
+     * The scheme and authority of the target, like <https://example.com:443>, if the connection is an intercepted tunnel.
+     *
+     * # Errors
+     * An error if the targets could not be locked.
+     */
+    pub fn get(&self, address: &SocketAddr) -> Result<Option<String>, ApplicationError> {
+        Ok(self.lock()?.get(address).cloned())
+    }
+
+    /**
+     * Add the target of a connection to the http server.
+     *
+     * # Arguments
+     * `address`: The local address of the connection.
+     * `target`: The scheme and authority of the target.
+     *
+     * # Errors
+     * An error if the targets could not be locked.
+     */
+    fn insert(&self, address: SocketAddr, target: String) -> Result<(), ApplicationError> {
+        self.lock()?.insert(address, target);
+        Ok(())
+    }
+
+    /**
+     * Remove the target of a closed connection.
+     *
+     * # Arguments
+     * `address`: The local address of the connection.
+     *
+     * # Errors
+     * An error if the targets could not be locked.
+     */
+    fn remove(&self, address: &SocketAddr) -> Result<(), ApplicationError> {
+        self.lock()?.remove(address);
+        Ok(())
+    }
+
+    /**
+     * Lock the targets.
+     *
+     * # Returns
+     * The locked targets.
+     *
+     * # Errors
+     * An error if the lock is poisoned.
+     */
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<SocketAddr, String>>, ApplicationError> {
+        self.0.lock().map_err(|err| ApplicationError::RoutingError(format!("Failed to lock tunnel targets: {err}")))
+    }
+}
+
+/**
+ * Terminates the TLS of the intercepted tunnels with certificates signed by the CA.
+ */
+struct Interceptor {
+    // The CA signing the certificates.
+    authority: CertificateAuthority,
+    // The intercepted hosts. All hosts are intercepted if empty.
+    hosts: Vec<Regex>,
+    // The TLS configurations by host. A certificate is only signed once per host.
+    configs: Mutex<HashMap<String, Arc<ServerConfig>>>,
+}
+
+impl Interceptor {
+    /**
+     * Check if the tunnels to the host are intercepted.
+     *
+     * # Arguments
+     * `host`: The host without the port.
+     *
+     * # Returns
+     * True if the host is intercepted.
+     */
+    fn intercepts(&self, host: &str) -> bool {
+        self.hosts.is_empty() || self.hosts.iter().any(|regex| regex.is_match(host))
+    }
+
+    /**
+     * Get the TLS configuration for the host. The certificate is signed the first time the host is intercepted.
+     *
+     * # Arguments
+     * `host`: The host without the port.
+     *
+     * # Returns
+     * The TLS configuration.
+     *
+     * # Errors
+     * An error if the certificate could not be signed.
+     */
+    fn server_config(&self, host: &str) -> Result<Arc<ServerConfig>, ApplicationError> {
+        let mut configs = self.configs.lock().map_err(|err| ApplicationError::RoutingError(format!("Failed to lock certificates: {err}")))?;
+        if let Some(config) = configs.get(host) {
+            return Ok(config.clone());
+        }
+        info!("Signing certificate for {host}");
+        let (certificate, key) = self.authority.sign(host)?;
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![CertificateDer::from(certificate)], PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key)))
+            .map_err(|err| ApplicationError::ConfigurationError(format!("Invalid certificate for {host}: {err}")))?;
+        // The http server behind the tunnel only serves HTTP/1.
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let config = Arc::new(config);
+        configs.insert(host.to_string(), config.clone());
+        Ok(config)
+    }
+}
+
+/**
+ * A forward proxy. CONNECT tunnels are intercepted or passed through. All other requests are sent to the http server,
+ * which handles the absolute-form requests with the endpoints.
+ */
+pub struct ForwardProxy {
+    // The address of the http server handling the requests.
+    server_address: SocketAddr,
+    // The targets of the intercepted tunnels shared with the http server.
+    targets: Arc<TunnelTargets>,
+    // TLS interception. The tunnels are passed through if not set.
+    interceptor: Option<Interceptor>,
+}
+
+impl ForwardProxy {
+    /**
+     * Create the forward proxy. The CA is loaded if the tunnels are intercepted.
+     *
+     * # Arguments
+     * `configuration`: The forward proxy configuration.
+     * `server_address`: The address of the http server handling the requests.
+     * `targets`: The targets of the intercepted tunnels shared with the http server.
+     *
+     * # Returns
+     * The forward proxy.
+     *
+     * # Errors
+     * An error if the CA could not be loaded or a host expression is invalid.
+     */
+    pub fn new(configuration: &ForwardProxyConfiguration, server_address: SocketAddr, targets: Arc<TunnelTargets>) -> Result<Self, ApplicationError> {
+        let interceptor = match &configuration.intercept {
+            Some(intercept) => {
+                let hosts = intercept
+                    .hosts
+                    .iter()
+                    .flatten()
+                    .map(|host| Regex::new(host).map_err(|err| ApplicationError::ConfigurationError(format!("Error in regular expression {host}: {err}"))))
+                    .collect::<Result<Vec<Regex>, ApplicationError>>()?;
+                let authority = CertificateAuthority::load(Path::new(intercept.directory.as_deref().unwrap_or(".")))?;
+                Some(Interceptor { authority, hosts, configs: Mutex::new(HashMap::new()) })
+            }
+            None => None,
+        };
+        Ok(ForwardProxy { server_address, targets, interceptor })
+    }
+
+    /**
+     * Accept the connections. Each connection is handled in its own task.
+     *
+     * # Arguments
+     * `listener`: The listener of the proxy port.
+     */
+    pub async fn serve(self, listener: TcpListener) {
+        let proxy = Arc::new(self);
+        loop {
+            let (client, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    error!("Failed to accept proxy connection: {err}");
+                    continue;
+                }
+            };
+            let proxy = proxy.clone();
+            tokio::spawn(async move {
+                if let Err(err) = proxy.handle_connection(client).await {
+                    error!("Error in proxy connection from {peer}: {err}");
+                }
+            });
+        }
+    }
+
+    /**
+     * Handle a connection. A CONNECT request opens a tunnel, otherwise the connection is forwarded to the http server.
+     *
+     * # Arguments
+     * `client`: The client connection.
+     *
+     * # Returns
+     * Ok when the connection is closed.
+     *
+     * # Errors
+     * An error if the connection failed.
+     */
+    async fn handle_connection(&self, mut client: TcpStream) -> Result<(), ApplicationError> {
+        let head = read_head(&mut client).await?;
+        if let Some(authority) = get_connect_authority(&head) {
+            let early_data = head[get_head_length(&head)..].to_vec();
+            return self.tunnel(client, &authority, early_data).await;
+        }
+        let mut server = TcpStream::connect(self.server_address).await.map_err(|err| ApplicationError::RoutingError(format!("Failed to connect to http server: {err}")))?;
+        server.write_all(&head).await.map_err(|err| ApplicationError::RoutingError(format!("Failed to write request: {err}")))?;
+        copy_bidirectional(&mut client, &mut server).await.map_err(|err| ApplicationError::RoutingError(format!("Connection failed: {err}")))?;
+        Ok(())
+    }
+
+    /**
+     * Open the tunnel. An intercepted tunnel is decrypted and sent to the http server with the target registered for the
+     * connection. Other tunnels are connected to the target. The bytes the client sent after the CONNECT request are
+     * sent through the tunnel first.
+     *
+     * # Arguments
+     * `client`: The client connection.
+     * `authority`: The host and port of the CONNECT request.
+     * `early_data`: The bytes read after the CONNECT request.
+     *
+     * # Returns
+     * Ok when the tunnel is closed.
+     *
+     * # Errors
+     * An error if the target could not be connected or the tunnel failed.
+     */
+    async fn tunnel(&self, mut client: TcpStream, authority: &str, early_data: Vec<u8>) -> Result<(), ApplicationError> {
+        let map_err = |err: std::io::Error| ApplicationError::RoutingError(format!("Tunnel to {authority} failed: {err}"));
+        let host = get_host(authority);
+        match self.interceptor.as_ref().filter(|interceptor| interceptor.intercepts(host)) {
+            Some(interceptor) => {
+                info!("Intercepting tunnel to {authority}");
+                let acceptor = TlsAcceptor::from(interceptor.server_config(host)?);
+                client.write_all(CONNECTION_ESTABLISHED).await.map_err(map_err)?;
+                let mut client = acceptor.accept(PrefixedStream::new(early_data, client)).await.map_err(map_err)?;
+                let mut server = TcpStream::connect(self.server_address).await.map_err(map_err)?;
+                let address = server.local_addr().map_err(map_err)?;
+                self.targets.insert(address, format!("https://{authority}"))?;
+                let result = copy_bidirectional(&mut client, &mut server).await;
+                self.targets.remove(&address)?;
+                result.map_err(map_err)?;
+            }
+            None => {
+                info!("Passing tunnel through to {authority}");
+                let mut upstream = TcpStream::connect(authority).await.map_err(map_err)?;
+                client.write_all(CONNECTION_ESTABLISHED).await.map_err(map_err)?;
+                upstream.write_all(&early_data).await.map_err(map_err)?;
+                copy_bidirectional(&mut client, &mut upstream).await.map_err(map_err)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Read the request head. The bytes read after the head are included.
+ *
+ * # Arguments
+ * `client`: The client connection.
+ *
+ * # Returns
+ * The bytes read.
+ *
+ * # Errors
+ * An error if the connection failed, was closed or the head is too large.
+ */
+async fn read_head(client: &mut TcpStream) -> Result<Vec<u8>, ApplicationError> {
+    let mut head = Vec::new();
+    let mut buffer = [0; READ_BUFFER_SIZE];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(ApplicationError::RoutingError("Request head too large".to_string()));
+        }
+        let read = client.read(&mut buffer).await.map_err(|err| ApplicationError::RoutingError(format!("Failed to read request: {err}")))?;
+        if read == 0 {
+            return Err(ApplicationError::RoutingError("Connection closed before the request head".to_string()));
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    Ok(head)
+}
+
+/**
+ * Get the length of the request head including the empty line.
+ *
+ * # Arguments
+ * `head`: The bytes read by `read_head`.
+ *
+ * # Returns
+ * The length of the head.
+ */
+fn get_head_length(head: &[u8]) -> usize {
+    head.windows(4).position(|window| window == b"\r\n\r\n").map_or(head.len(), |position| position + 4)
+}
+
+/**
+ * Get the authority of a CONNECT request.
+ *
+ * # Arguments
+ * `head`: The request head.
+ *
+ * # Returns
+ * The host and port if the request is a CONNECT request.
+ */
+fn get_connect_authority(head: &[u8]) -> Option<String> {
+    let request_line = head.split(|byte| *byte == b'\n').next()?;
+    let mut parts = std::str::from_utf8(request_line).ok()?.split_whitespace();
+    if parts.next()? != "CONNECT" {
+        return None;
+    }
+    parts.next().map(ToString::to_string)
+}
+
+/**
+ * Get the host of the authority without the port. The brackets of an IPv6 address are removed.
+ * The authority of a CONNECT request always has a port.
+ *
+ * # Arguments
+ * `authority`: The host and port.
+ *
+ * # Returns
+ * The host.
+ */
+fn get_host(authority: &str) -> &str {
+    let host = authority.rsplit_once(':').map_or(authority, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /**
+     * Verify that the authority is found in CONNECT requests only.
+     */
+    #[test]
+    fn test_get_connect_authority() {
+        assert_eq!(get_connect_authority(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n"), Some("example.com:443".to_string()));
+        assert_eq!(get_connect_authority(b"GET http://example.com/ HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(get_host("example.com:443"), "example.com");
+        assert_eq!(get_host("[::1]:443"), "::1");
+    }
+
+    /**
+     * Verify that the bytes after the CONNECT request are read before the stream.
+     */
+    #[tokio::test]
+    async fn test_prefixed_stream() {
+        let head = b"CONNECT example.com:443 HTTP/1.1\r\n\r\nHELLO".to_vec();
+        let early_data = head[get_head_length(&head)..].to_vec();
+        assert_eq!(early_data, b"HELLO");
+        let (client, mut server) = tokio::io::duplex(64);
+        server.write_all(b" WORLD").await.unwrap();
+        drop(server);
+        let mut stream = PrefixedStream::new(early_data, client);
+        let mut read = String::new();
+        stream.read_to_string(&mut read).await.unwrap();
+        assert_eq!(read, "HELLO WORLD");
+    }
+}
@@ -0,0 +1,264 @@
+use apinae_lib::{
+    config::{TcpFramingConfiguration, TcpFramingType},
+    error::ApplicationError,
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::tcp::READ_BUFFER_SIZE;
+
+/**
+ * The default header length for length prefixed messages.
+ */
+const DEFAULT_HEADER_LENGTH: usize = 2;
+
+/**
+ * The default maximum message length.
+ */
+const DEFAULT_MAX_LENGTH: usize = 1024 * 1024;
+
+/**
+ * The compiled framing.
+ */
+#[derive(Debug, Clone, PartialEq)]
+enum Framing {
+    // Each read is a message.
+    Read,
+    // Messages end with a newline.
+    Newline,
+    // Messages have a fixed length.
+    FixedLength(usize),
+    // Messages start with a length header of the number of bytes.
+    LengthPrefixed(usize, bool),
+    // Messages end with the byte.
+    Delimiter(u8),
+}
+
+/**
+ * Splits the bytes read from a connection into messages. Bytes after the message are kept for the next message.
+ */
+#[derive(Debug, Clone)]
+pub struct Framer {
+    // The framing.
+    framing: Framing,
+    // Bytes not yet returned as a message.
+    input: Vec<u8>,
+    // The maximum message length.
+    max_length: usize,
+}
+
+impl Framer {
+    /**
+     * Create the framer.
+     *
+     * # Arguments
+     * `configuration`: The framing configuration. Each read is a message if not set.
+     *
+     * # Returns
+     * The framer.
+     *
+     * # Errors
+     * An error if the fields used by the framing type are missing or invalid.
+     */
+    pub fn new(configuration: Option<&TcpFramingConfiguration>) -> Result<Self, ApplicationError> {
+        let framing = match configuration {
+            None => Framing::Read,
+            Some(configuration) => match configuration.framing_type {
+                TcpFramingType::Newline => Framing::Newline,
+                TcpFramingType::FixedLength => match configuration.length {
+                    Some(length) if length > 0 => Framing::FixedLength(length),
+                    _ => return Err(ApplicationError::ConfigurationError("Fixed length framing requires a length above 0".to_string())),
+                },
+                TcpFramingType::LengthPrefixed => {
+                    let header_length = configuration.header_length.unwrap_or(DEFAULT_HEADER_LENGTH);
+                    if !(1..=8).contains(&header_length) {
+                        return Err(ApplicationError::ConfigurationError(format!("Header length {header_length} must be between 1 and 8")));
+                    }
+                    Framing::LengthPrefixed(header_length, configuration.little_endian)
+                }
+                TcpFramingType::Delimiter => Framing::Delimiter(configuration.delimiter.ok_or_else(|| ApplicationError::ConfigurationError("Delimiter framing requires a delimiter".to_string()))?),
+            },
+        };
+        let max_length = configuration.and_then(|configuration| configuration.max_length).unwrap_or(DEFAULT_MAX_LENGTH);
+        Ok(Framer { framing, input: Vec::new(), max_length })
+    }
+
+    /**
+     * Read the next message.
+     *
+     * # Arguments
+     * `stream`: The stream.
+     *
+     * # Returns
+     * The message without framing bytes, or None if the client closed the connection. An incomplete message is discarded.
+     *
+     * # Errors
+     * An error if the stream could not be read or the message is longer than the maximum length.
+     */
+    pub async fn read_message<S: AsyncRead + Unpin>(&mut self, stream: &mut S) -> Result<Option<Vec<u8>>, ApplicationError> {
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(Some(message));
+            }
+            if matches!(self.framing, Framing::Newline | Framing::Delimiter(_)) && self.input.len() > self.max_length {
+                return Err(ApplicationError::RoutingError(format!("Message exceeds the maximum length of {} bytes", self.max_length)));
+            }
+            let read = stream.read(&mut buffer).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to read: {err}")))?;
+            if read == 0 {
+                if !self.input.is_empty() {
+                    log::debug!("Discarding incomplete message: {:?}", String::from_utf8_lossy(&self.input));
+                }
+                return Ok(None);
+            }
+            self.input.extend_from_slice(&buffer[..read]);
+        }
+    }
+
+    /**
+     * Take the next complete message from the input.
+     *
+     * # Returns
+     * The message, or None if the input does not hold a complete message.
+     *
+     * # Errors
+     * An error if the length header is above the maximum length.
+     */
+    fn take_message(&mut self) -> Result<Option<Vec<u8>>, ApplicationError> {
+        Ok(match self.framing {
+            Framing::Read => (!self.input.is_empty()).then(|| std::mem::take(&mut self.input)),
+            Framing::Newline => self.take_until(b'\n').map(|mut message| {
+                if message.last() == Some(&b'\r') {
+                    message.pop();
+                }
+                message
+            }),
+            Framing::Delimiter(delimiter) => self.take_until(delimiter),
+            Framing::FixedLength(length) => (self.input.len() >= length).then(|| self.input.drain(..length).collect()),
+            Framing::LengthPrefixed(header_length, little_endian) => {
+                let Some(header) = self.input.get(..header_length) else {
+                    return Ok(None);
+                };
+                let length = if little_endian {
+                    header.iter().rev().fold(0_u64, |length, byte| (length << 8) | u64::from(*byte))
+                } else {
+                    header.iter().fold(0_u64, |length, byte| (length << 8) | u64::from(*byte))
+                };
+                // The length is checked before the message is read so that a large header does not fill the memory.
+                let length = usize::try_from(length).ok().filter(|length| *length <= self.max_length).ok_or_else(|| {
+                    ApplicationError::RoutingError(format!("Message length {length} exceeds the maximum length of {} bytes", self.max_length))
+                })?;
+                let end = length + header_length;
+                if self.input.len() < end {
+                    return Ok(None);
+                }
+                let message = self.input[header_length..end].to_vec();
+                self.input.drain(..end);
+                Some(message)
+            }
+        })
+    }
+
+    /**
+     * Take the input until the delimiter. The delimiter is removed.
+     *
+     * # Arguments
+     * `delimiter`: The delimiter.
+     *
+     * # Returns
+     * The message, or None if the input does not contain the delimiter.
+     */
+    fn take_until(&mut self, delimiter: u8) -> Option<Vec<u8>> {
+        let position = self.input.iter().position(|byte| *byte == delimiter)?;
+        let mut message: Vec<u8> = self.input.drain(..=position).collect();
+        message.pop();
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    /**
+     * Read all messages from the bytes written in the chunks.
+     */
+    async fn read_messages(configuration: Option<&TcpFramingConfiguration>, chunks: &[&[u8]]) -> Vec<Vec<u8>> {
+        let mut framer = Framer::new(configuration).unwrap();
+        let (mut server, mut client) = tokio::io::duplex(1024);
+        for chunk in chunks {
+            client.write_all(chunk).await.unwrap();
+        }
+        drop(client);
+        let mut messages = Vec::new();
+        while let Some(message) = framer.read_message(&mut server).await.unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    /**
+     * Verify newline and delimiter framing.
+     */
+    #[tokio::test]
+    async fn test_delimited() {
+        let messages = read_messages(Some(&TcpFramingConfiguration::new(TcpFramingType::Newline)), &[b"HELO a\r\nMA", b"IL\nQUIT"]).await;
+        assert_eq!(messages, vec![b"HELO a".to_vec(), b"MAIL".to_vec()]);
+        let mut configuration = TcpFramingConfiguration::new(TcpFramingType::Delimiter);
+        configuration.delimiter = Some(0x03);
+        let messages = read_messages(Some(&configuration), &[b"\x02A\x03\x02B\x03"]).await;
+        assert_eq!(messages, vec![b"\x02A".to_vec(), b"\x02B".to_vec()]);
+    }
+
+    /**
+     * Verify fixed length and length prefixed framing.
+     */
+    #[tokio::test]
+    async fn test_length() {
+        let mut configuration = TcpFramingConfiguration::new(TcpFramingType::FixedLength);
+        configuration.length = Some(3);
+        let messages = read_messages(Some(&configuration), &[b"ABCD", b"EFG"]).await;
+        assert_eq!(messages, vec![b"ABC".to_vec(), b"DEF".to_vec()]);
+        let configuration = TcpFramingConfiguration::new(TcpFramingType::LengthPrefixed);
+        let messages = read_messages(Some(&configuration), &[b"\x00\x02AB\x00", b"\x01C"]).await;
+        assert_eq!(messages, vec![b"AB".to_vec(), b"C".to_vec()]);
+        let mut configuration = TcpFramingConfiguration::new(TcpFramingType::LengthPrefixed);
+        configuration.header_length = Some(4);
+        configuration.little_endian = true;
+        let messages = read_messages(Some(&configuration), &[b"\x01\x00\x00\x00Z"]).await;
+        assert_eq!(messages, vec![b"Z".to_vec()]);
+    }
+
+    /**
+     * Verify that invalid framing is a configuration error.
+     */
+    #[test]
+    fn test_framer_invalid() {
+        assert!(Framer::new(Some(&TcpFramingConfiguration::new(TcpFramingType::FixedLength))).is_err());
+        assert!(Framer::new(Some(&TcpFramingConfiguration::new(TcpFramingType::Delimiter))).is_err());
+        let mut configuration = TcpFramingConfiguration::new(TcpFramingType::LengthPrefixed);
+        configuration.header_length = Some(9);
+        assert!(Framer::new(Some(&configuration)).is_err());
+    }
+
+    /**
+     * Verify that messages longer than the maximum length are errors.
+     */
+    #[tokio::test]
+    async fn test_max_length() {
+        let mut configuration = TcpFramingConfiguration::new(TcpFramingType::LengthPrefixed);
+        configuration.header_length = Some(4);
+        let mut framer = Framer::new(Some(&configuration)).unwrap();
+        assert!(framer.read_message(&mut &b"\xff\xff\xff\xff"[..]).await.is_err());
+        configuration.max_length = Some(2);
+        let mut framer = Framer::new(Some(&configuration)).unwrap();
+        assert_eq!(framer.read_message(&mut &b"\x00\x00\x00\x02AB"[..]).await.unwrap(), Some(b"AB".to_vec()));
+        assert!(framer.read_message(&mut &b"\x00\x00\x00\x03ABC"[..]).await.is_err());
+        let mut configuration = TcpFramingConfiguration::new(TcpFramingType::Newline);
+        configuration.max_length = Some(4);
+        let mut framer = Framer::new(Some(&configuration)).unwrap();
+        assert!(framer.read_message(&mut &b"ABCDEFGH"[..]).await.is_err());
+    }
+}
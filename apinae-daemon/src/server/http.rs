@@ -1,23 +1,53 @@
-use std::{fs::File, io::BufReader, str::FromStr, sync::Arc, time::Duration};
+use std::{any::Any, fs::File, io::BufReader, str::FromStr, sync::Arc, time::Duration};
 
-use actix_web::{http::StatusCode, middleware::Logger, web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_http::{error::DispatchError, HttpService};
+use actix_service::{fn_service, map_config, ServiceFactoryExt};
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{
+    dev::{AppConfig, Extensions, Server},
+    http::{uri::Authority, StatusCode, Version},
+    middleware::Logger,
+    rt::net::TcpStream,
+    web, App, HttpRequest, HttpResponse, HttpServer,
+};
 use apinae_lib::{
-    config::{EndpointConfiguration, EndpointType, HttpsConfiguration, MockResponseConfiguration, RouteConfiguration, ServerConfiguration, TlsVersion},
+    config::{EndpointConfiguration, EndpointType, HeaderRulesConfiguration, HttpProtocol, HttpsConfiguration, MockResponseConfiguration, RouteConfiguration, ServerConfiguration, TlsVersion},
     error::ApplicationError,
 };
 use log::{error, info};
 use regex::Regex;
 use reqwest::Method;
 use rustls::{
-    pki_types::PrivateKeyDer,
+    crypto::CryptoProvider,
     server::{danger::ClientCertVerifier, WebPkiClientVerifier},
+    sign::CertifiedKey,
     version::{TLS12, TLS13},
     RootCertStore, ServerConfig, SupportedProtocolVersion,
 };
-use rustls_pemfile::{certs, pkcs8_private_keys};
+use rustls_pemfile::certs;
 use tokio::task::JoinHandle;
 
-use super::common::StartableServer;
+use super::{
+    balancer::UpstreamBalancer,
+    common::StartableServer,
+    fault,
+    forward_proxy::{ForwardProxy, TunnelTargets},
+    identity::{self, ClientIdentity},
+    pkcs8::to_pkcs8_pem,
+    profile::{EndpointProfiles, ErrorRateProfile},
+    ratelimit::{RateLimitDecision, RateLimiter},
+    rewrite::{apply_header_rules, compile_path_rewrites, rewrite_path, PathRewrite},
+    sni::SniCertificateResolver,
+};
+#[cfg(feature = "openssl")]
+use super::legacy_tls;
+#[cfg(unix)]
+use super::{
+    prefixed::PrefixedStream,
+    unix::{bind_unix_socket, detect_protocol},
+};
+#[cfg(unix)]
+use actix_web::rt::net::UnixStream;
 
 /**
  * The character used to separate query parameters in a URL.
@@ -27,6 +57,14 @@ const QUERYPARAMSEPARATOR: char = '&';
  * The character used to separate key and value in a query parameter.
  */
 const KEYVALUESEPARATOR: char = '=';
+/**
+ * The prefix of a route or upstream url on a unix socket, like unix:///var/run/service.sock.
+ */
+const UNIX_URL_PREFIX: &str = "unix://";
+/**
+ * The url the path is added to for requests on a unix socket.
+ */
+const UNIX_BASE_URL: &str = "http://localhost";
 
 /**
  * The `AppServer` struct is used to configure and start the server.
@@ -34,8 +72,8 @@ const KEYVALUESEPARATOR: char = '=';
 pub struct AppServer {
     // Server configuration
     server_configuration: ServerConfiguration,
-    // Parameters
-    params: Vec<(String, String)>,
+    // State shared by the http and https servers.
+    app_state: web::Data<AppState>,
 }
 
 impl AppServer {
@@ -48,13 +86,17 @@ impl AppServer {
      *
      * # Returns
      * The created `AppServer`.
+     *
+     * # Errors
+     * An error if the client for a route could not be created or a latency configuration is invalid.
      */
-    pub fn new(server_configuration: ServerConfiguration, params: Vec<(String, String)>) -> Self {
-        AppServer { server_configuration, params }
+    pub fn new(server_configuration: ServerConfiguration, params: Vec<(String, String)>) -> Result<Self, ApplicationError> {
+        let app_state = web::Data::new(AppState::new(server_configuration.clone(), params)?);
+        Ok(AppServer { server_configuration, app_state })
     }
 
     /**
-     * Start the server with HTTP.
+     * Start the server with HTTP. HTTP/2 with prior knowledge (h2c) is detected from the connection preface if the protocol allows it.
      *
      * # Returns
      * Ok if the server was started.
@@ -64,11 +106,20 @@ impl AppServer {
      */
     pub fn start_server_http(&mut self) -> Result<(), ApplicationError> {
         if let Some(http_port) = self.server_configuration.http_port {
-            log::info!("Starting http server on port: {http_port}");
-            let appstate = web::Data::new(AppState::new(self.server_configuration.clone(), self.params.clone()));
-            let server = HttpServer::new(move || App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(web::to(request_handler)))
-                .bind(("127.0.0.1", http_port))
-                .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create http server: {err}")))?;
+            let http_protocol = self.server_configuration.http_protocol.clone();
+            log::info!("Starting http server on port: {http_port} with {http_protocol:?}");
+            let appstate = self.app_state.clone();
+            let require_http2 = http_protocol == HttpProtocol::H2c;
+            let server = HttpServer::new(move || {
+                let handler = if require_http2 { web::to(h2c_request_handler) } else { web::to(request_handler) };
+                App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(handler)
+            })
+            .on_connect(on_connect);
+            let server = match http_protocol {
+                HttpProtocol::Http1 => server.bind(("127.0.0.1", http_port)),
+                HttpProtocol::H2c | HttpProtocol::Http1AndH2c => server.bind_auto_h2c(("127.0.0.1", http_port)),
+            }
+            .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create http server: {err}")))?;
             let server = server.workers(2).run();
             tokio::spawn(async move {
                 match server.await {
@@ -83,7 +134,83 @@ impl AppServer {
     }
 
     /**
-     * Start the server with HTTPS. It uses rust tls to create a secure server.
+     * Start the server with HTTP on the unix socket.
+     *
+     * # Returns
+     * Ok if the server was started.
+     *
+     * # Errors
+     * An error if the unix socket could not be bound.
+     */
+    pub fn start_server_unix(&self) -> Result<(), ApplicationError> {
+        if let Some(unix_socket) = &self.server_configuration.unix_socket {
+            #[cfg(not(unix))]
+            return Err(ApplicationError::ConfigurationError(format!("Unix socket {} is only supported on unix", unix_socket.path)));
+            #[cfg(unix)]
+            {
+                let http_protocol = self.server_configuration.http_protocol.clone();
+                log::info!("Starting http server on unix socket: {} with {http_protocol:?}", unix_socket.path);
+                let appstate = self.app_state.clone();
+                let listener = bind_unix_socket(unix_socket)?;
+                let server = match http_protocol {
+                    HttpProtocol::Http1 => HttpServer::new(move || App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(web::to(request_handler)))
+                        .on_connect(on_connect)
+                        .listen_uds(listener)
+                        .map(|server| server.workers(2).run()),
+                    HttpProtocol::H2c | HttpProtocol::Http1AndH2c => unix_h2c_server(listener, http_protocol == HttpProtocol::H2c, appstate),
+                }
+                .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create http server on unix socket: {err}")))?;
+                tokio::spawn(async move {
+                    if let Err(err) = server.await {
+                        error!("Server error: {err}");
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Start the forward proxy. The requests are handled by a http server on a local port that is only used by the proxy.
+     *
+     * # Returns
+     * Ok if the proxy was started.
+     *
+     * # Errors
+     * An error if the ports could not be bound or the CA could not be loaded.
+     */
+    pub fn start_server_forward_proxy(&self) -> Result<(), ApplicationError> {
+        let Some(forward_proxy) = &self.server_configuration.forward_proxy else {
+            return Ok(());
+        };
+        log::info!("Starting forward proxy on port: {}", forward_proxy.port);
+        let map_err = |err: std::io::Error| ApplicationError::ServerStartUpError(format!("Failed to create forward proxy: {err}"));
+        let server_listener = std::net::TcpListener::bind(("127.0.0.1", 0)).map_err(map_err)?;
+        let server_address = server_listener.local_addr().map_err(map_err)?;
+        let targets = Arc::new(TunnelTargets::default());
+        let proxy = ForwardProxy::new(forward_proxy, server_address, targets.clone())?;
+        let proxy_listener = std::net::TcpListener::bind(("127.0.0.1", forward_proxy.port)).map_err(map_err)?;
+        proxy_listener.set_nonblocking(true).map_err(map_err)?;
+        let proxy_listener = tokio::net::TcpListener::from_std(proxy_listener).map_err(map_err)?;
+        let appstate = self.app_state.clone();
+        let targets = web::Data::from(targets);
+        let server = HttpServer::new(move || App::new().wrap(Logger::default()).app_data(appstate.clone()).app_data(targets.clone()).default_service(web::to(request_handler)))
+            .on_connect(on_connect)
+            .listen(server_listener)
+            .map_err(map_err)?;
+        let server = server.workers(2).run();
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                error!("Server error: {err}");
+            }
+        });
+        tokio::spawn(proxy.serve(proxy_listener));
+        Ok(())
+    }
+
+    /**
+     * Start the server with HTTPS. It uses rust tls to create a secure server. If TLSv1.0 or TLSv1.1 is supported
+     * and the openssl feature is enabled OpenSSL is used instead. h2 is offered with ALPN unless it is switched off.
      *
      * # Returns
      * Ok if the server was started.
@@ -95,11 +222,32 @@ impl AppServer {
         let config = self.server_configuration.clone();
         if let Some(https_config) = config.https_config {
             log::info!("Starting https server on port: {}", https_config.https_port);
-            let ssl_builder = ssl_builder(&https_config)?;
-            let appstate = web::Data::new(AppState::new(self.server_configuration.clone(), self.params.clone()));
-            let server = HttpServer::new(move || App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(web::to(request_handler)))
-                .bind_rustls_0_23("127.0.0.1:".to_owned() + https_config.https_port.to_string().as_str(), ssl_builder)
-                .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create https server: {err}")))?;
+            let address = "127.0.0.1:".to_owned() + https_config.https_port.to_string().as_str();
+            let appstate = self.app_state.clone();
+            #[cfg(feature = "openssl")]
+            let legacy = legacy_tls::is_legacy(&https_config.supported_tls_versions);
+            if !https_config.http2 {
+                #[cfg(feature = "openssl")]
+                let server = if legacy { http1_openssl_server(address, legacy_tls::ssl_acceptor(&https_config)?, appstate)? } else { http1_tls_server(address, ssl_builder(&https_config)?, appstate)? };
+                #[cfg(not(feature = "openssl"))]
+                let server = http1_tls_server(address, ssl_builder(&https_config)?, appstate)?;
+                tokio::spawn(async move {
+                    if let Err(err) = server.await {
+                        error!("Server error: {err}");
+                    }
+                });
+                return Ok(());
+            }
+            let server = HttpServer::new(move || App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(web::to(request_handler))).on_connect(on_connect);
+            #[cfg(feature = "openssl")]
+            let server = if legacy {
+                server.bind_openssl(address, legacy_tls::ssl_acceptor(&https_config)?)
+            } else {
+                server.bind_rustls_0_23(address, ssl_builder(&https_config)?)
+            };
+            #[cfg(not(feature = "openssl"))]
+            let server = server.bind_rustls_0_23(address, ssl_builder(&https_config)?);
+            let server = server.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create https server: {err}")))?;
             let server = server.workers(2).run();
             tokio::spawn(async move {
                 match server.await {
@@ -112,8 +260,110 @@ impl AppServer {
     }
 }
 
+/**
+ * Create a https server that only negotiates HTTP/1.1. `HttpServer` always offers h2 with ALPN, so the HTTP/1 service is served directly.
+ *
+ * # Arguments
+ * `address`: The address to bind.
+ * `tls_config`: The rustls configuration.
+ * `appstate`: The application state.
+ *
+ * # Returns
+ * The running server.
+ *
+ * # Errors
+ * An error if the address could not be bound.
+ */
+fn http1_tls_server(address: String, mut tls_config: ServerConfig, appstate: web::Data<AppState>) -> Result<Server, ApplicationError> {
+    tls_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let server = Server::build()
+        .bind("https", address, move || {
+            let app = App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(web::to(request_handler));
+            HttpService::build()
+                .on_connect_ext(|stream: &TlsStream<TcpStream>, data: &mut Extensions| on_connect(stream, data))
+                .h1(map_config(app, |()| AppConfig::default()))
+                .rustls_0_23(tls_config.clone())
+        })
+        .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create https server: {err}")))?;
+    Ok(server.workers(2).run())
+}
+
+/**
+ * Create a https server with OpenSSL that only negotiates HTTP/1.1. `HttpServer` always offers h2 with ALPN, so the HTTP/1 service is served directly.
+ *
+ * # Arguments
+ * `address`: The address to bind.
+ * `builder`: The OpenSSL acceptor builder.
+ * `appstate`: The application state.
+ *
+ * # Returns
+ * The running server.
+ *
+ * # Errors
+ * An error if the address could not be bound.
+ */
+#[cfg(feature = "openssl")]
+fn http1_openssl_server(address: String, mut builder: openssl::ssl::SslAcceptorBuilder, appstate: web::Data<AppState>) -> Result<Server, ApplicationError> {
+    builder.set_alpn_select_callback(|_, client_protocols| openssl::ssl::select_next_proto(b"\x08http/1.1", client_protocols).ok_or(openssl::ssl::AlpnError::NOACK));
+    let acceptor = builder.build();
+    let server = Server::build()
+        .bind("https", address, move || {
+            let app = App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(web::to(request_handler));
+            HttpService::build()
+                .on_connect_ext(|stream: &actix_tls::accept::openssl::TlsStream<TcpStream>, data: &mut Extensions| on_connect(stream, data))
+                .h1(map_config(app, |()| AppConfig::default()))
+                .openssl(acceptor.clone())
+        })
+        .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create https server: {err}")))?;
+    Ok(server.workers(2).run())
+}
+
+/**
+ * Create the server on the unix socket that detects HTTP/2 with prior knowledge (h2c) from the connection preface.
+ * `HttpServer` only serves HTTP/1 on unix sockets.
+ *
+ * # Arguments
+ * `listener`: The bound unix socket.
+ * `require_http2`: If requests that are not HTTP/2 are rejected.
+ * `appstate`: The application state.
+ *
+ * # Returns
+ * The server.
+ *
+ * # Errors
+ * An error if the server could not be created.
+ */
+#[cfg(unix)]
+fn unix_h2c_server(listener: std::os::unix::net::UnixListener, require_http2: bool, appstate: web::Data<AppState>) -> std::io::Result<Server> {
+    let server = Server::build().listen_uds("http-unix", listener, move || {
+        let handler = if require_http2 { web::to(h2c_request_handler) } else { web::to(request_handler) };
+        let app = App::new().wrap(Logger::default()).app_data(appstate.clone()).default_service(handler);
+        fn_service(|stream| async { detect_protocol(stream).await.map(|(stream, protocol)| (stream, protocol, None)).map_err(DispatchError::Io) }).and_then(
+            HttpService::build()
+                .on_connect_ext(|stream: &PrefixedStream<UnixStream>, data: &mut Extensions| on_connect(stream, data))
+                .finish(map_config(app, |()| AppConfig::default())),
+        )
+    })?;
+    Ok(server.workers(2).run())
+}
+
+/**
+ * Called when a connection is accepted. Stores the data used by the faults and the client identity in the connection data.
+ *
+ * # Arguments
+ * `connection`: The accepted connection.
+ * `data`: The connection data.
+ */
+fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    fault::on_connect(connection, data);
+    identity::on_connect(connection, data);
+    #[cfg(feature = "openssl")]
+    legacy_tls::on_connect(connection, data);
+}
+
 /**
  * Handle the request. All request will be handled by this function.
+ * If the server has a rate limit the request is checked against it before matching the endpoints.
  *
  * # Arguments
  * `server_configuration`: The server configuration.
@@ -123,17 +373,89 @@ impl AppServer {
  * The response.
  */
 async fn request_handler(app_state: web::Data<AppState>, req: HttpRequest, payload: Option<web::Payload>) -> HttpResponse {
+    let server_decision = match &app_state.server_rate_limiter {
+        Some(rate_limiter) => match check_rate_limit(rate_limiter, &req, app_state.params.clone()).await {
+            Ok(decision) => Some(decision),
+            Err(response) => return response,
+        },
+        None => None,
+    };
+    let mut response = handle_request(&app_state, &req, payload).await;
+    if let Some(decision) = server_decision {
+        decision.add_headers(&mut response);
+    }
+    response
+}
+
+/**
+ * Handle the request on a server only serving h2c. HTTP/1.x requests are answered with 505 HTTP Version Not Supported.
+ *
+ * # Arguments
+ * `app_state`: The application state.
+ * `req`: The request.
+ * `payload`: The payload.
+ *
+ * # Returns
+ * The response.
+ */
+async fn h2c_request_handler(app_state: web::Data<AppState>, req: HttpRequest, payload: Option<web::Payload>) -> HttpResponse {
+    if req.version() != Version::HTTP_2 {
+        return HttpResponse::build(StatusCode::HTTP_VERSION_NOT_SUPPORTED).finish();
+    }
+    request_handler(app_state, req, payload).await
+}
+
+/**
+ * Find the endpoint matching the request and handle it. If the endpoint has a rate limit the request is checked against it.
+ * The fields of the client certificate are added to the parameters. Requests through the forward proxy not matching
+ * an endpoint are passed through to the target.
+ *
+ * # Arguments
+ * `app_state`: The application state.
+ * `req`: The request.
+ * `payload`: The payload.
+ *
+ * # Returns
+ * The response.
+ */
+async fn handle_request(app_state: &AppState, req: &HttpRequest, payload: Option<web::Payload>) -> HttpResponse {
     let payload_string: Option<String> = get_body_as_string(payload).await;
-    let path = get_path(&req);
-    for endpoint in &app_state.server_configuration.endpoints {         
-        match is_valid_endpoint(path.as_str(), req.method().as_str(), endpoint, &payload_string) {
-            Ok(true) => match handle_endpoint(endpoint, &req, &payload_string, app_state.params.clone()).await {
-                Ok(response) => return response,
-                Err(err) => {
-                    error!("Error handling request: {err}. Returning not implemented");
-                    return get_non_implemented_response();
+    let path = get_path(req);
+    let identity = req.conn_data::<ClientIdentity>();
+    let mut params = app_state.params.clone();
+    if let Some(identity) = identity {
+        params.extend(identity.params());
+    }
+    let target = match get_proxy_target(req) {
+        Ok(target) => target,
+        Err(err) => {
+            error!("Error finding proxy target: {err}. Returning service unavailable");
+            return HttpResponse::ServiceUnavailable().body(err.to_string());
+        }
+    };
+    let host = get_host(req, target.as_deref());
+    for (endpoint, endpoint_state) in app_state.server_configuration.endpoints.iter().zip(&app_state.endpoint_states) {
+        match is_valid_endpoint(path.as_str(), req.method().as_str(), host.as_deref(), endpoint, &payload_string, identity) {
+            Ok(true) => {
+                let decision = match &endpoint_state.rate_limiter {
+                    Some(rate_limiter) => match check_rate_limit(rate_limiter, req, params.clone()).await {
+                        Ok(decision) => Some(decision),
+                        Err(response) => return response,
+                    },
+                    None => None,
+                };
+                let mut response = match handle_endpoint(endpoint, endpoint_state, req, &payload_string, params.clone()).await {
+                    Ok(response) => response,
+                    Err(err) => {
+                        error!("Error handling request: {err}. Returning not implemented");
+                        get_non_implemented_response()
+                    }
+                };
+                if let Some(decision) = decision {
+                    decision.add_headers(&mut response);
                 }
-            },
+                return response;
+            }
             Ok(false) => {}
             Err(err) => {
                 error!("Error checking endpoint: {err}. Returning service unavailable");
@@ -141,10 +463,125 @@ async fn request_handler(app_state: web::Data<AppState>, req: HttpRequest, paylo
             }
         }
     }
+    if let (Some(target), Some(client)) = (target, &app_state.passthrough_client) {
+        return match pass_through(client, req, payload_string, &target).await {
+            Ok(response) => response,
+            Err(err) => {
+                error!("Error passing request through: {err}. Returning bad gateway");
+                HttpResponse::BadGateway().body(err.to_string())
+            }
+        };
+    }
     info!("No endpoints found: Returning not implemented");
     get_non_implemented_response()
 }
 
+/**
+ * Get the target of a request received by the forward proxy. Absolute-form requests have the target in the uri.
+ * Requests in an intercepted tunnel are found by the address of the connection.
+ *
+ * # Arguments
+ * `req`: The request.
+ *
+ * # Returns
+ * The scheme and authority of the target, like <http://example.com>, if the request was received by the forward proxy.
+ *
+ * # Errors
+ * An error if the tunnel targets could not be locked.
+ */
+fn get_proxy_target(req: &HttpRequest) -> Result<Option<String>, ApplicationError> {
+    let Some(targets) = req.app_data::<web::Data<TunnelTargets>>() else {
+        return Ok(None);
+    };
+    if let (Some(scheme), Some(authority)) = (req.uri().scheme_str(), req.uri().authority()) {
+        return Ok(Some(format!("{scheme}://{authority}")));
+    }
+    match req.peer_addr() {
+        Some(address) => targets.get(&address),
+        None => Ok(None),
+    }
+}
+
+/**
+ * Get the host of the request without the port. It is the host of the target for requests received by the forward proxy.
+ *
+ * # Arguments
+ * `req`: The request.
+ * `target`: The target of the forward proxy.
+ *
+ * # Returns
+ * The host.
+ */
+fn get_host(req: &HttpRequest, target: Option<&str>) -> Option<String> {
+    let authority = match target {
+        Some(target) => target.split_once("://").map_or(target, |(_, authority)| authority).to_string(),
+        None => req.connection_info().host().to_string(),
+    };
+    Authority::from_str(&authority).ok().map(|authority| authority.host().trim_start_matches('[').trim_end_matches(']').to_string())
+}
+
+/**
+ * Pass the request through to the target of the forward proxy.
+ *
+ * # Arguments
+ * `client`: The client without proxy.
+ * `req`: The request.
+ * `payload`: The payload.
+ * `target`: The scheme and authority of the target.
+ *
+ * # Returns
+ * The response from the target.
+ *
+ * # Errors
+ * An error if the request failed.
+ */
+async fn pass_through(client: &reqwest::Client, req: &HttpRequest, payload: Option<String>, target: &str) -> Result<HttpResponse, ApplicationError> {
+    let url = format!("{target}{}", req.path());
+    info!("Passing request through to {url}");
+    let request = get_request(client, req, get_request_headers(req)?, payload, url)?;
+    let response = client.execute(request).await.map_err(|err| ApplicationError::RoutingError(format!("Error executing client request: {err}")))?;
+    get_response(response, None, &Vec::new()).await
+}
+
+/**
+ * Check the request against the rate limit.
+ *
+ * # Arguments
+ * `rate_limiter`: The rate limiter.
+ * `req`: The request.
+ * `params`: The parameters.
+ *
+ * # Returns
+ * The decision if the request is allowed.
+ *
+ * # Errors
+ * The response to return if the limit is exceeded or the limit could not be checked.
+ */
+async fn check_rate_limit(rate_limiter: &RateLimiter, req: &HttpRequest, params: Vec<(String, String)>) -> Result<RateLimitDecision, HttpResponse> {
+    let decision = match rate_limiter.check(req) {
+        Ok(decision) => decision,
+        Err(err) => {
+            error!("Error checking rate limit: {err}. Returning service unavailable");
+            return Err(HttpResponse::ServiceUnavailable().body(err.to_string()));
+        }
+    };
+    if decision.allowed {
+        return Ok(decision);
+    }
+    info!("Rate limit exceeded: Returning {}", rate_limiter.status());
+    let configuration = rate_limiter.configuration();
+    let mock_response = MockResponseConfiguration::new(configuration.response.clone(), rate_limiter.status(), configuration.headers.clone(), 0);
+    let mut response = match generate_mock_response(&mock_response, params).await {
+        Ok(response) => response,
+        Err(err) => {
+            error!("Error generating rate limit response: {err}. Returning not implemented");
+            get_non_implemented_response()
+        }
+    };
+    decision.add_headers(&mut response);
+    Err(response)
+}
+
 /**
  * Get the not implemented response.
  * The response will have a status code of 501 Not Implemented and a body with the message "Not implemented".
@@ -207,14 +644,16 @@ async fn get_body_as_string(payload: Option<web::Payload>) -> Option<String> {
 }
 
 /**
- * Check if the request is a valid endpoint. This function will check the request path, method, and payload against the endpoint configuration.
+ * Check if the request is a valid endpoint. This function will check the request path, method, payload and client certificate against the endpoint configuration.
  * If all are true it will return true, otherwise it will return false.
  *
  * # Arguments
  * `request_path`: The request path.
  * `request_method`: The request method.
+ * `request_host`: The request host without the port.
  * `endpoint`: The endpoint configuration.
  * `payload_string`: The request payload as a string.
+ * `identity`: The identity of the client certificate.
  *
  * # Returns
  * True if the request is a valid endpoint.
@@ -222,11 +661,24 @@ async fn get_body_as_string(payload: Option<web::Payload>) -> Option<String> {
  * # Errors
  * An error if the endpoint is invalid.
  */
-fn is_valid_endpoint(request_path: &str, request_method: &str, endpoint: &EndpointConfiguration, payload_string: &Option<String>) -> Result<bool, ApplicationError> {
+fn is_valid_endpoint(
+    request_path: &str,
+    request_method: &str,
+    request_host: Option<&str>,
+    endpoint: &EndpointConfiguration,
+    payload_string: &Option<String>,
+    identity: Option<&ClientIdentity>,
+) -> Result<bool, ApplicationError> {
     let path_result = check_regexp(endpoint.path_expression.clone(), &Some(request_path.to_owned()))?;
+    let host_result = check_regexp(endpoint.host_expression.clone(), &request_host.map(ToOwned::to_owned))?;
     let payload_result = check_regexp(endpoint.body_expression.clone(), payload_string)?;
     let method_result = endpoint.method.clone().map_or_else(|| true, |f| f == request_method);
-    Ok(path_result && payload_result && method_result)
+    let identity_result = match (&endpoint.client_identity, identity) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(client_identity), Some(identity)) => identity.matches(client_identity)?,
+    };
+    Ok(path_result && host_result && payload_result && method_result && identity_result)
 }
 
 /**
@@ -263,10 +715,13 @@ fn check_regexp(regexp: Option<String>, data: &Option<String>) -> Result<bool, A
 /**
  * Handle the endpoint. This function will check the endpoint type and call the appropriate handler either
  * a mock response or a route request. If it fails to match any endpoint type, it will return a not 
- * implemented response.
+ * implemented response. If a fault is configured it is applied to the response. A route is not called for faults that
+ * break the connection.
+ * Random latency is added first, then the alternate response is returned if the error rate is hit.
  *
  * # Arguments
  * `endpoint`: The endpoint configuration.
+ * `endpoint_state`: The state for the endpoint.
  *
  * # Returns
  * The response.
@@ -274,14 +729,35 @@ fn check_regexp(regexp: Option<String>, data: &Option<String>) -> Result<bool, A
  * # Errors
  * An error if the status code is invalid.
  */
-async fn handle_endpoint(endpoint: &EndpointConfiguration, req: &HttpRequest, payload: &Option<String>, params: Vec<(String, String)>) -> Result<HttpResponse, ApplicationError> {
+async fn handle_endpoint(endpoint: &EndpointConfiguration, endpoint_state: &EndpointState, req: &HttpRequest, payload: &Option<String>, params: Vec<(String, String)>) -> Result<HttpResponse, ApplicationError> {
+    let profiles = &endpoint_state.profiles;
+    if let Some(latency) = &profiles.latency {
+        let delay = latency.next_delay()?;
+        log::debug!("Waiting {}ms random latency", delay.as_millis());
+        tokio::time::sleep(delay).await;
+    }
+    if let Some(error_rate) = &profiles.error_rate {
+        if error_rate.is_error()? {
+            return generate_error_response(error_rate, params).await;
+        }
+    }
     if let Some(endpoint_type) = &endpoint.endpoint_type {
         match endpoint_type {
             EndpointType::Mock { configuration } => {
-                return generate_mock_response(configuration, params).await;
+                let response = generate_mock_response(configuration, params).await?;
+                return Ok(fault::apply_fault(configuration.fault.as_ref(), req, response).await);
             }
             EndpointType::Route { configuration } => {
-                return route_request(configuration, req, payload.clone()).await;
+                let fault = fault::trigger(configuration.fault.as_ref());
+                // Faults that break the connection do not use the response, so the upstream is not called.
+                if let Some(fault) = fault.filter(|fault| !fault::uses_response(fault)) {
+                    return Ok(fault::inject_fault(fault, req, HttpResponse::Ok().finish()).await);
+                }
+                let response = route_request(configuration, endpoint_state, req, payload.clone(), &params).await?;
+                return Ok(match fault {
+                    Some(fault) => fault::inject_fault(fault, req, response).await,
+                    None => response,
+                });
             }
         }
     }
@@ -289,31 +765,41 @@ async fn handle_endpoint(endpoint: &EndpointConfiguration, req: &HttpRequest, pa
 }
 
 /**
- * Route the request to the configured route configuration.
+ * Route the request to the configured route configuration. The path is rewritten and the
+ * request and response headers are changed by the configured rules. If the route has upstreams
+ * the balancer chooses the upstream and the next upstream is tried if the connection fails.
  *
  * # Arguments
  * `route_configuration`: The route configuration.
+ * `endpoint_state`: The state with the clients, the upstream balancer and the path rewrites of the route.
  * `req`: The request.
+ * `payload`: The payload.
+ * `params`: The parameters.
  *
  * # Returns
  * The response.
  */
-async fn route_request(route_configuration: &RouteConfiguration, req: &HttpRequest, payload: Option<String>) -> Result<HttpResponse, ApplicationError> {
-    let mut url = route_configuration.url.clone();
-    url.push_str(req.path());
-
-    let request = get_request(req, payload.clone(), url)?;
+async fn route_request(route_configuration: &RouteConfiguration, endpoint_state: &EndpointState, req: &HttpRequest, payload: Option<String>, params: &Vec<(String, String)>) -> Result<HttpResponse, ApplicationError> {
+    let client = endpoint_state.client.as_ref().ok_or_else(|| ApplicationError::RoutingError("No client for route".to_string()))?;
+    let path = rewrite_path(req.path(), &endpoint_state.path_rewrites, params);
 
-    let client = get_client(route_configuration)?;
+    let headers = get_request_headers(req)?;
+    let headers = apply_header_rules(headers, route_configuration.request_headers.as_ref(), params);
 
     if let Some(delay_before) = route_configuration.delay_before {
         log::debug!("Waiting {delay_before}ms before request");
         tokio::time::sleep(Duration::from_millis(delay_before)).await;
     }
 
-    let response = client.execute(request).await.map_err(|err| ApplicationError::RoutingError(format!("Error executing client request: {err}")))?;
+    let response = match &endpoint_state.balancer {
+        Some(balancer) => execute_balanced(&endpoint_state.upstream_clients, balancer, req, &headers, &payload, &path).await?,
+        None => {
+            let request = get_request(client, req, headers, payload.clone(), get_url(&route_configuration.url, &path))?;
+            client.execute(request).await.map_err(|err| ApplicationError::RoutingError(format!("Error executing client request: {err}")))?
+        }
+    };
 
-    let response = get_response(response).await?;
+    let response = get_response(response, route_configuration.response_headers.as_ref(), params).await?;
 
     if let Some(delay_after) = route_configuration.delay_after {
         log::debug!("Waiting {delay_after}ms after request");
@@ -324,10 +810,61 @@ async fn route_request(route_configuration: &RouteConfiguration, req: &HttpReque
 }
 
 /**
- * Converts the request response to this applications response.
+ * Execute the request against the upstreams chosen by the balancer. An upstream that fails is marked as
+ * failed. The next upstream is only tried if the connection failed, since otherwise the request may have been processed.
+ *
+ * # Arguments
+ * `clients`: The clients for the upstreams.
+ * `balancer`: The upstream balancer.
+ * `req`: The original request.
+ * `headers`: The headers sent.
+ * `payload`: The payload.
+ * `path`: The rewritten path.
+ *
+ * # Returns
+ * The response from the upstream.
+ *
+ * # Errors
+ * An error if no upstream returned a response.
+ */
+async fn execute_balanced(
+    clients: &[reqwest::Client],
+    balancer: &UpstreamBalancer,
+    req: &HttpRequest,
+    headers: &[(String, String)],
+    payload: &Option<String>,
+    path: &str,
+) -> Result<reqwest::Response, ApplicationError> {
+    let mut last_error = ApplicationError::RoutingError("No upstreams".to_string());
+    for index in balancer.attempts()? {
+        let url = get_url(balancer.url(index), path);
+        log::debug!("Routing to upstream {url}");
+        let client = clients.get(index).ok_or_else(|| ApplicationError::RoutingError(format!("No client for upstream {}", balancer.url(index))))?;
+        let request = get_request(client, req, headers.to_vec(), payload.clone(), url)?;
+        match client.execute(request).await {
+            Ok(response) => {
+                balancer.mark_success(index)?;
+                return Ok(response);
+            }
+            Err(err) => {
+                balancer.mark_failure(index)?;
+                last_error = ApplicationError::RoutingError(format!("Error executing client request to {}: {err}", balancer.url(index)));
+                if !err.is_connect() {
+                    return Err(last_error);
+                }
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/**
+ * Converts the request response to this applications response. The response headers are changed by the configured rules.
  *
  * # Arguments
  * `response`: The response.
+ * `response_headers`: The response header rules.
+ * `params`: The parameters.
  *
  * # Returns
  * The response.
@@ -335,15 +872,20 @@ async fn route_request(route_configuration: &RouteConfiguration, req: &HttpReque
  * # Errors
  * An error if the status code is invalid.
  */
-async fn get_response(response: reqwest::Response) -> Result<HttpResponse, ApplicationError> {
+async fn get_response(response: reqwest::Response, response_headers: Option<&HeaderRulesConfiguration>, params: &Vec<(String, String)>) -> Result<HttpResponse, ApplicationError> {
     log::debug!("Creating response");
     let mut response_builder = HttpResponse::build(
         StatusCode::from_u16(response.status().as_u16()).map_err(|err| ApplicationError::RoutingError(format!("Invalid status code for response {}: {err}", response.status().as_str())))?,
     );
+    let mut headers = Vec::new();
     for (key, value) in response.headers() {
-        response_builder.append_header((key.as_str(), value.to_str().map_err(|err| ApplicationError::RoutingError(format!("Invalid header value for response {value:?}: {err}")))?));
+        let value = value.to_str().map_err(|err| ApplicationError::RoutingError(format!("Invalid header value for response {value:?}: {err}")))?;
+        headers.push((key.as_str().to_string(), value.to_string()));
+    }
+    for header in apply_header_rules(headers, response_headers, params) {
+        response_builder.append_header(header);
     }
-    let body = response.text().await.map_err(|err| ApplicationError::RoutingError(format!("Invalid body for response: {err}")))?;
+    let body = response.bytes().await.map_err(|err| ApplicationError::RoutingError(format!("Invalid body for response: {err}")))?;
 
     let response = response_builder.body(body);
 
@@ -351,8 +893,7 @@ async fn get_response(response: reqwest::Response) -> Result<HttpResponse, Appli
 }
 
 /**
- * Get the function name. This is required due to the fact that the client
- * allows proxy while sending from the request object does not.
+ * Get the client for a route. The client is created once per route so that connections are reused.
  *
  * # Arguments
  * `route_configuration`: The route configuration.
@@ -365,8 +906,77 @@ async fn get_response(response: reqwest::Response) -> Result<HttpResponse, Appli
  *
  */
 fn get_client(route_configuration: &RouteConfiguration) -> Result<reqwest::Client, ApplicationError> {
+    build_client(route_configuration, route_configuration.url.strip_prefix(UNIX_URL_PREFIX))
+}
+
+/**
+ * Get the clients for the upstreams of a route. Upstreams on a unix socket get their own client, the others share the route client.
+ *
+ * # Arguments
+ * `route_configuration`: The route configuration.
+ * `client`: The client for the route.
+ *
+ * # Returns
+ * The clients in the same order as the upstreams.
+ *
+ * # Errors
+ * An error if a client could not be created.
+ */
+fn get_upstream_clients(route_configuration: &RouteConfiguration, client: &reqwest::Client) -> Result<Vec<reqwest::Client>, ApplicationError> {
+    route_configuration
+        .upstreams
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .map(|upstream| match upstream.url.strip_prefix(UNIX_URL_PREFIX) {
+            Some(unix_socket) => build_client(route_configuration, Some(unix_socket)),
+            None => Ok(client.clone()),
+        })
+        .collect()
+}
+
+/**
+ * Get the url for the path. Requests on a unix socket use localhost as host.
+ *
+ * # Arguments
+ * `url`: The route or upstream url.
+ * `path`: The path.
+ *
+ * # Returns
+ * The url.
+ */
+fn get_url(url: &str, path: &str) -> String {
+    if url.starts_with(UNIX_URL_PREFIX) {
+        format!("{UNIX_BASE_URL}{path}")
+    } else {
+        format!("{url}{path}")
+    }
+}
+
+/**
+ * Create a client with the route settings.
+ *
+ * # Arguments
+ * `route_configuration`: The route configuration.
+ * `unix_socket`: The path of the unix socket all requests are sent on, if any. The proxy is not used for unix sockets.
+ *
+ * # Returns
+ * Client object to make the requests.
+ *
+ * # Errors
+ * An error if the client could not be created.
+ */
+fn build_client(route_configuration: &RouteConfiguration, unix_socket: Option<&str>) -> Result<reqwest::Client, ApplicationError> {
     log::debug!("Creating client");
     let mut client_builder = reqwest::Client::builder();
+    if let Some(unix_socket) = unix_socket {
+        #[cfg(unix)]
+        {
+            client_builder = client_builder.unix_socket(unix_socket);
+        }
+        #[cfg(not(unix))]
+        return Err(ApplicationError::ConfigurationError(format!("Unix socket {unix_socket} is only supported on unix")));
+    }
     if let Some(connect_timeout) = route_configuration.connect_timeout {
         client_builder = client_builder.connect_timeout(Duration::from_millis(connect_timeout));
     }
@@ -378,23 +988,37 @@ fn get_client(route_configuration: &RouteConfiguration) -> Result<reqwest::Clien
     }
     client_builder = client_builder.danger_accept_invalid_certs(route_configuration.accept_invalid_certs).danger_accept_invalid_hostnames(route_configuration.accept_invalid_hostnames);
 
+    if route_configuration.http2_prior_knowledge {
+        if route_configuration.http1_only {
+            return Err(ApplicationError::ConfigurationError("Http1 only and http2 prior knowledge can not both be used".to_string()));
+        }
+        client_builder = client_builder.http2_prior_knowledge();
+    }
+    if let Some(pool_idle_timeout) = route_configuration.pool_idle_timeout {
+        client_builder = client_builder.pool_idle_timeout(Duration::from_millis(pool_idle_timeout));
+    }
+    if let Some(pool_max_idle_per_host) = route_configuration.pool_max_idle_per_host {
+        client_builder = client_builder.pool_max_idle_per_host(pool_max_idle_per_host);
+    }
+
+    if let (Some(min_tls_version), Some(max_tls_version)) = (&route_configuration.min_tls_version, &route_configuration.max_tls_version) {
+        if get_reqwest_tls_version(min_tls_version) > get_reqwest_tls_version(max_tls_version) {
+            return Err(ApplicationError::ConfigurationError(format!("Minimum TLS version {min_tls_version:?} is higher than maximum TLS version {max_tls_version:?}")));
+        }
+    }
     if let Some(min_tls_version) = &route_configuration.min_tls_version {
-        match min_tls_version {
-            TlsVersion::TLSv1_0 => {
-                client_builder = client_builder.min_tls_version(reqwest::tls::Version::TLS_1_0);
-            }
-            TlsVersion::TLSv1_1 => {
-                client_builder = client_builder.min_tls_version(reqwest::tls::Version::TLS_1_1);
-            }
-            TlsVersion::TLSv1_2 => {
-                client_builder = client_builder.min_tls_version(reqwest::tls::Version::TLS_1_2);
-            }
-            TlsVersion::TLSv1_3 => {
-                client_builder = client_builder.min_tls_version(reqwest::tls::Version::TLS_1_3);
-            }
+        if *min_tls_version == TlsVersion::TLSv1_3 {
+            return Err(ApplicationError::ConfigurationError("Minimum TLS version 1.3 is not supported by the route client".to_string()));
         }
+        client_builder = client_builder.min_tls_version(get_reqwest_tls_version(min_tls_version));
+    }
+    // TLS 1.3 is the highest version, so it does not limit the client.
+    if let Some(max_tls_version) = route_configuration.max_tls_version.as_ref().filter(|version| **version != TlsVersion::TLSv1_3) {
+        client_builder = client_builder.max_tls_version(get_reqwest_tls_version(max_tls_version));
     }
 
+    client_builder = add_client_certificates(client_builder, route_configuration)?;
+
     let client = match &route_configuration.proxy_url {
         Some(proxy) => {
             log::debug!("Creating client with proxy: {proxy}");
@@ -406,11 +1030,110 @@ fn get_client(route_configuration: &RouteConfiguration) -> Result<reqwest::Clien
     Ok(client)
 }
 
+/**
+ * Get the reqwest TLS version.
+ *
+ * # Arguments
+ * `tls_version`: The TLS version.
+ *
+ * # Returns
+ * The reqwest TLS version.
+ */
+fn get_reqwest_tls_version(tls_version: &TlsVersion) -> reqwest::tls::Version {
+    match tls_version {
+        TlsVersion::TLSv1_0 => reqwest::tls::Version::TLS_1_0,
+        TlsVersion::TLSv1_1 => reqwest::tls::Version::TLS_1_1,
+        TlsVersion::TLSv1_2 => reqwest::tls::Version::TLS_1_2,
+        TlsVersion::TLSv1_3 => reqwest::tls::Version::TLS_1_3,
+    }
+}
+
+/**
+ * Add the client certificate and the trusted ca certificates to the client.
+ *
+ * # Arguments
+ * `client_builder`: The client builder.
+ * `route_configuration`: The route configuration.
+ *
+ * # Returns
+ * The client builder.
+ *
+ * # Errors
+ * An error if the files could not be read or are invalid.
+ * An error if only one of the client certificate and the client key is configured.
+ */
+fn add_client_certificates(mut client_builder: reqwest::ClientBuilder, route_configuration: &RouteConfiguration) -> Result<reqwest::ClientBuilder, ApplicationError> {
+    match (&route_configuration.client_certificate, &route_configuration.client_key) {
+        (Some(client_certificate), Some(client_key)) => {
+            log::debug!("Using client certificate {client_certificate}");
+            let key = rustls_pemfile::private_key(&mut read_file(client_key)?.as_slice())
+                .map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read client key {client_key}: {err}")))?
+                .ok_or_else(|| ApplicationError::ConfigurationError(format!("No PKCS#8, PKCS#1 or SEC1 private key found in {client_key}")))?;
+            let identity = reqwest::Identity::from_pkcs8_pem(&read_file(client_certificate)?, to_pkcs8_pem(&key)?.as_bytes())
+                .map_err(|err| ApplicationError::RoutingError(format!("Invalid client certificate {client_certificate} or key {client_key}: {err}")))?;
+            client_builder = client_builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => return Err(ApplicationError::RoutingError("Both client certificate and client key must be configured".to_string())),
+    }
+    if let Some(ca_certificate) = &route_configuration.ca_certificate {
+        log::debug!("Trusting ca certificate {ca_certificate}");
+        let certificates = reqwest::Certificate::from_pem_bundle(&read_file(ca_certificate)?).map_err(|err| ApplicationError::RoutingError(format!("Invalid ca certificate {ca_certificate}: {err}")))?;
+        for certificate in certificates {
+            client_builder = client_builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(client_builder)
+}
+
+/**
+ * Read the file.
+ *
+ * # Arguments
+ * `path`: The file path.
+ *
+ * # Returns
+ * The file content.
+ *
+ * # Errors
+ * An error if the file could not be read.
+ */
+fn read_file(path: &str) -> Result<Vec<u8>, ApplicationError> {
+    std::fs::read(path).map_err(|err| ApplicationError::FileError(format!("Failed to read file {path}: {err}")))
+}
+
+/**
+ * Get the headers of the original request. The host header is not included since it
+ * belongs to the original server. It can be set with the request header rules.
+ *
+ * # Arguments
+ * `req`: The original request.
+ *
+ * # Returns
+ * The headers as name and value.
+ *
+ * # Errors
+ * An error if a header value is invalid.
+ */
+fn get_request_headers(req: &HttpRequest) -> Result<Vec<(String, String)>, ApplicationError> {
+    let mut headers = Vec::new();
+    for (key, value) in req.headers() {
+        if key == actix_web::http::header::HOST {
+            continue;
+        }
+        let value = value.to_str().map_err(|err| ApplicationError::RoutingError(format!("Failed to map request header: {err}")))?;
+        headers.push((key.as_str().to_string(), value.to_string()));
+    }
+    Ok(headers)
+}
+
 /**
  * Get request object for client. 
  *
  * # Arguments
+ * `client`: The client.
  * `req`: The original request.
+ * `headers`: The headers sent.
  * `payload`: The payload.
  * `url`: The URL.
  *
@@ -427,13 +1150,13 @@ fn get_client(route_configuration: &RouteConfiguration) -> Result<reqwest::Clien
  *
  * # Example
  * ```
- * let request = get_request(req, payload, url).await?;
+ * let request = get_request(client, req, headers, payload, url).await?;
  * ```
  *
  */
-fn get_request(req: &HttpRequest, payload: Option<String>, url: String) -> Result<reqwest::Request, ApplicationError> {
+fn get_request(client: &reqwest::Client, req: &HttpRequest, headers: Vec<(String, String)>, payload: Option<String>, url: String) -> Result<reqwest::Request, ApplicationError> {
     log::debug!("Creating request");
-    let mut request_builder = reqwest::Client::new()
+    let mut request_builder = client
         .request(Method::from_bytes(req.method().as_str().as_bytes()).map_err(|err| ApplicationError::RoutingError(format!("Failed to map method {}: {err}", req.method().as_str())))?, url);
     request_builder = match req.version() {
         actix_web::http::Version::HTTP_09 => request_builder.version(reqwest::Version::HTTP_09),
@@ -443,9 +1166,8 @@ fn get_request(req: &HttpRequest, payload: Option<String>, url: String) -> Resul
         actix_web::http::Version::HTTP_3 => request_builder.version(reqwest::Version::HTTP_3),
         _ => return Err(ApplicationError::RoutingError("Invalid version".to_string())),
     };
-    for (key, value) in req.headers() {
-        let value = value.to_str().map_err(|err| ApplicationError::RoutingError(format!("Failed to map request header: {err}")))?;
-        request_builder = request_builder.header(key.as_str(), value);
+    for (key, value) in headers {
+        request_builder = request_builder.header(key, value);
     }
     if let Some(payload) = payload {
         let bytes = payload.clone().into_bytes();
@@ -456,8 +1178,8 @@ fn get_request(req: &HttpRequest, payload: Option<String>, url: String) -> Resul
             .split(QUERYPARAMSEPARATOR)
             .map(|x| {
                 let mut parts = x.split(KEYVALUESEPARATOR);
-                let key = parts.next().map_or_else(|| "", |x| x);
-                let value = parts.next().map_or_else(|| "", |x| x);
+                let key = parts.next().unwrap_or_default();
+                let value = parts.next().unwrap_or_default();
                 (key.to_owned(), value.to_owned())
             })
             .collect::<Vec<(String, String)>>(),
@@ -497,6 +1219,25 @@ async fn generate_mock_response(mock_response: &MockResponseConfiguration, param
     Ok(response_builder.finish())
 }
 
+/**
+ * Generate the alternate response for an error rate profile.
+ *
+ * # Arguments
+ * `error_rate`: The error rate profile.
+ * `params`: The parameters.
+ *
+ * # Returns
+ * The alternate response.
+ *
+ * # Errors
+ * An error if the status code is invalid.
+ */
+async fn generate_error_response(error_rate: &ErrorRateProfile, params: Vec<(String, String)>) -> Result<HttpResponse, ApplicationError> {
+    log::debug!("Returning error rate response");
+    let configuration = error_rate.configuration();
+    generate_mock_response(&MockResponseConfiguration::new(configuration.response.clone(), configuration.status.clone(), configuration.headers.clone(), 0), params).await
+}
+
 /**
  * Convert parameters to string. The parameters are in the format ${key} and will be replaced with the value from 
  * the parameters vector.
@@ -508,7 +1249,7 @@ async fn generate_mock_response(mock_response: &MockResponseConfiguration, param
  * # Returns
  * The converted value.
  */
-fn convert_params(value: &str, params: &Vec<(String, String)>) -> String {
+pub(super) fn convert_params(value: &str, params: &Vec<(String, String)>) -> String {
     let mut result = value.to_string();
     for (key, value) in params {
         let key = format!("${{{key}}}");
@@ -531,11 +1272,12 @@ fn convert_params(value: &str, params: &Vec<(String, String)>) -> String {
  * An error if the acceptor could not be created.
  * An error if the private key file could not be set.
  * An error if the certificate chain file could not be set.
+ * An error if a SNI certificate could not be set.
  *
  */
-fn ssl_builder(https_config: &HttpsConfiguration) -> Result<ServerConfig, ApplicationError> {
+pub(super) fn ssl_builder(https_config: &HttpsConfiguration) -> Result<ServerConfig, ApplicationError> {
     log::info!("Creating ssl builder");
-    let config_builder = ServerConfig::builder_with_protocol_versions(&get_protocol_versions(&https_config.supported_tls_versions));
+    let config_builder = ServerConfig::builder_with_protocol_versions(&get_protocol_versions(&https_config.supported_tls_versions)?);
     log::debug!("Supported TLS versions: {:?}", &https_config.supported_tls_versions);
 
     let config_builder = match https_config.clone().client_certificate {
@@ -547,21 +1289,50 @@ fn ssl_builder(https_config: &HttpsConfiguration) -> Result<ServerConfig, Applic
         None => config_builder.with_no_client_auth(),
     };
 
-    let cert_file = &mut BufReader::new(File::open(https_config.clone().server_certificate).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read certificate file: {err}")))?);
-    let key_file = &mut BufReader::new(File::open(https_config.clone().private_key).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read private key file: {err}")))?);
-
-    let cert_chain = certs(cert_file).collect::<Result<Vec<_>, _>>().map_err(|err| ApplicationError::ConfigurationError(format!("Failed to convert certificate to der: {err}")))?;
-    let mut keys = pkcs8_private_keys(key_file)
-        .map(|key| key.map(PrivateKeyDer::Pkcs8))
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|err| ApplicationError::ConfigurationError(format!("Failed to convert private key to der: {err}")))?;
-    let config = config_builder.with_single_cert(cert_chain, keys.remove(0)).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to create server config: {err}")))?;
+    let provider = config_builder.crypto_provider().clone();
+    let default_certificate = get_certified_key(&https_config.server_certificate, &https_config.private_key, &provider)?;
+    let mut sni_certificates = Vec::new();
+    for sni_certificate in https_config.sni_certificates.iter().flatten() {
+        log::debug!("Certificate for SNI hostname: {}", sni_certificate.hostname);
+        sni_certificates.push((sni_certificate.hostname.clone(), get_certified_key(&sni_certificate.server_certificate, &sni_certificate.private_key, &provider)?));
+    }
+    let config = config_builder.with_cert_resolver(Arc::new(SniCertificateResolver::new(default_certificate, sni_certificates)));
 
     Ok(config)
 }
 
 /**
- * Get the protocol versions.
+ * Load the certificate chain and the private key. The private key can be PKCS#8, PKCS#1 (RSA) or SEC1 (EC).
+ *
+ * # Arguments
+ * `certificate`: The certificate file.
+ * `private_key`: The private key file.
+ * `provider`: The crypto provider used to load the key.
+ *
+ * # Returns
+ * The certificate chain with the key.
+ *
+ * # Errors
+ * An error if the files could not be read.
+ * An error if no certificate or private key was found.
+ * An error if the private key does not match the certificate.
+ */
+fn get_certified_key(certificate: &str, private_key: &str, provider: &CryptoProvider) -> Result<CertifiedKey, ApplicationError> {
+    let cert_file = &mut BufReader::new(File::open(certificate).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read certificate file {certificate}: {err}")))?);
+    let key_file = &mut BufReader::new(File::open(private_key).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read private key file {private_key}: {err}")))?);
+
+    let cert_chain = certs(cert_file).collect::<Result<Vec<_>, _>>().map_err(|err| ApplicationError::ConfigurationError(format!("Failed to convert certificate to der: {err}")))?;
+    if cert_chain.is_empty() {
+        return Err(ApplicationError::ConfigurationError(format!("No certificate found in {certificate}")));
+    }
+    let key = rustls_pemfile::private_key(key_file)
+        .map_err(|err| ApplicationError::ConfigurationError(format!("Failed to convert private key to der: {err}")))?
+        .ok_or_else(|| ApplicationError::ConfigurationError(format!("No PKCS#8, PKCS#1 or SEC1 private key found in {private_key}")))?;
+    CertifiedKey::from_der(cert_chain, key, provider).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to load private key {private_key} for certificate {certificate}: {err}")))
+}
+
+/**
+ * Get the protocol versions. Rust tls only supports TLSv1.2 and TLSv1.3.
  *
  * # Arguments
  * `supported_tls_versions`: The supported TLS versions.
@@ -569,13 +1340,19 @@ fn ssl_builder(https_config: &HttpsConfiguration) -> Result<ServerConfig, Applic
  * # Returns
  * The protocol versions.
  *
+ * # Errors
+ * An error if TLSv1.0 or TLSv1.1 is supported.
  */
-fn get_protocol_versions(supported_tls_versions: &[TlsVersion]) -> Vec<&'static SupportedProtocolVersion> {
+fn get_protocol_versions(supported_tls_versions: &[TlsVersion]) -> Result<Vec<&'static SupportedProtocolVersion>, ApplicationError> {
     supported_tls_versions
         .iter()
         .map(|version| match version {
-            TlsVersion::TLSv1_0 | TlsVersion::TLSv1_1 | TlsVersion::TLSv1_2 => &TLS12,
-            TlsVersion::TLSv1_3 => &TLS13,
+            TlsVersion::TLSv1_0 | TlsVersion::TLSv1_1 => Err(ApplicationError::ConfigurationError(format!(
+                "{} is not supported. Build apinae-daemon with the openssl feature to serve TLSv1.0 and TLSv1.1",
+                String::from(version.clone())
+            ))),
+            TlsVersion::TLSv1_2 => Ok(&TLS12),
+            TlsVersion::TLSv1_3 => Ok(&TLS13),
         })
         .collect()
 }
@@ -623,6 +1400,8 @@ impl StartableServer for AppServer {
         let handles = vec![];
         self.start_server_http()?;
         self.start_server_https()?;
+        self.start_server_unix()?;
+        self.start_server_forward_proxy()?;
         Ok(handles)
     }
 }
@@ -630,11 +1409,71 @@ impl StartableServer for AppServer {
 struct AppState {
     server_configuration: ServerConfiguration,
     params: Vec<(String, String)>,
+    // Rate limiter for the server.
+    server_rate_limiter: Option<RateLimiter>,
+    // State in the same order as the endpoints.
+    endpoint_states: Vec<EndpointState>,
+    // Client for the requests the forward proxy passes through.
+    passthrough_client: Option<reqwest::Client>,
 }
 
 impl AppState {
-    fn new(server_configuration: ServerConfiguration, params: Vec<(String, String)>) -> Self {
-        AppState { server_configuration, params }
+    fn new(server_configuration: ServerConfiguration, params: Vec<(String, String)>) -> Result<Self, ApplicationError> {
+        let server_rate_limiter = server_configuration.rate_limit.clone().map(RateLimiter::new);
+        let endpoint_states = server_configuration.endpoints.iter().map(EndpointState::new).collect::<Result<Vec<EndpointState>, ApplicationError>>()?;
+        let passthrough_client = match server_configuration.forward_proxy {
+            Some(_) => Some(
+                reqwest::Client::builder()
+                    .no_proxy()
+                    .redirect(reqwest::redirect::Policy::none())
+                    .build()
+                    .map_err(|err| ApplicationError::ConfigurationError(format!("Failed to create pass through client: {err}")))?,
+            ),
+            None => None,
+        };
+        Ok(AppState { server_configuration, params, server_rate_limiter, endpoint_states, passthrough_client })
+    }
+}
+
+/**
+ * The state for an endpoint kept between requests.
+ */
+struct EndpointState {
+    // Random profiles.
+    profiles: EndpointProfiles,
+    // Rate limiter.
+    rate_limiter: Option<RateLimiter>,
+    // Upstream balancer for routes with upstreams.
+    balancer: Option<UpstreamBalancer>,
+    // Client for routes.
+    client: Option<reqwest::Client>,
+    // Clients for the upstreams in the same order as the upstreams.
+    upstream_clients: Vec<reqwest::Client>,
+    // Compiled path rewrites of the route.
+    path_rewrites: Vec<PathRewrite>,
+}
+
+impl EndpointState {
+    fn new(endpoint: &EndpointConfiguration) -> Result<Self, ApplicationError> {
+        let fault = match &endpoint.endpoint_type {
+            Some(EndpointType::Mock { configuration }) => configuration.fault.as_ref(),
+            Some(EndpointType::Route { configuration }) => configuration.fault.as_ref(),
+            None => None,
+        };
+        fault::check_probability(fault.and_then(|fault| fault.probability), "Fault probability")?;
+        let (balancer, client, upstream_clients, path_rewrites) = match &endpoint.endpoint_type {
+            Some(EndpointType::Route { configuration }) => {
+                if configuration.url.is_empty() && configuration.upstreams.as_deref().unwrap_or_default().is_empty() {
+                    return Err(ApplicationError::ConfigurationError("A route requires a url or upstreams".to_string()));
+                }
+                let client = get_client(configuration)?;
+                let upstream_clients = get_upstream_clients(configuration, &client)?;
+                let path_rewrites = compile_path_rewrites(configuration.path_rewrites.as_deref().unwrap_or_default())?;
+                (UpstreamBalancer::new(configuration), Some(client), upstream_clients, path_rewrites)
+            }
+            _ => (None, None, Vec::new(), Vec::new()),
+        };
+        Ok(EndpointState { profiles: EndpointProfiles::new(endpoint)?, rate_limiter: endpoint.rate_limit.clone().map(RateLimiter::new), balancer, client, upstream_clients, path_rewrites })
     }
 }
 
@@ -643,6 +1482,9 @@ mod test {
 
     use std::collections::HashMap;
 
+    use actix_web::test::TestRequest;
+    use apinae_lib::config::{ClientIdentityConfiguration, FaultConfiguration, FaultType, LatencyConfiguration, LatencyDistribution, SniCertificateConfiguration};
+
     use super::*;
 
     /**
@@ -651,7 +1493,34 @@ mod test {
     #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
     async fn test_valid_endpoint() {
         let endpoint = EndpointConfiguration::new(Some("^\\/test$".to_string()), Some("GET".to_string()), Some("".to_string()), None).unwrap();
-        assert!(is_valid_endpoint("/test", "GET", &endpoint, &Some("body".to_string())).unwrap());
+        assert!(is_valid_endpoint("/test", "GET", None, &endpoint, &Some("body".to_string()), None).unwrap());
+    }
+
+    /**
+     * Verifying that endpoints with a client identity only match requests with a matching client certificate.
+     */
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_valid_endpoint_client_identity() {
+        let mut endpoint = EndpointConfiguration::new(Some("^\\/test$".to_string()), None, None, None).unwrap();
+        endpoint.client_identity = Some(ClientIdentityConfiguration::new(Some("^CommonNameOrHostname$".to_string()), None, None, None));
+        let certificate = certs(&mut BufReader::new(File::open("tests/resources/client_cert.pem").unwrap())).next().unwrap().unwrap();
+        let identity = ClientIdentity::from_der(&certificate).unwrap();
+        assert!(is_valid_endpoint("/test", "GET", None, &endpoint, &None, Some(&identity)).unwrap());
+        assert!(!is_valid_endpoint("/test", "GET", None, &endpoint, &None, None).unwrap());
+        endpoint.client_identity = Some(ClientIdentityConfiguration::new(Some("^service-b$".to_string()), None, None, None));
+        assert!(!is_valid_endpoint("/test", "GET", None, &endpoint, &None, Some(&identity)).unwrap());
+    }
+
+    /**
+     * Verifying that endpoints with a host expression only match requests to a matching host.
+     */
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_valid_endpoint_host() {
+        let mut endpoint = EndpointConfiguration::new(Some("^\\/test$".to_string()), None, None, None).unwrap();
+        endpoint.host_expression = Some("^example\\.com$".to_string());
+        assert!(is_valid_endpoint("/test", "GET", Some("example.com"), &endpoint, &None, None).unwrap());
+        assert!(!is_valid_endpoint("/test", "GET", Some("example.org"), &endpoint, &None, None).unwrap());
+        assert!(!is_valid_endpoint("/test", "GET", None, &endpoint, &None, None).unwrap());
     }
 
     /**
@@ -679,9 +1548,11 @@ mod test {
      */
     #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
     async fn test_get_supported_tls_versions() {
-        let supported_tls_versions = vec![TlsVersion::TLSv1_0, TlsVersion::TLSv1_1, TlsVersion::TLSv1_2];
-        let protocol_versions = get_protocol_versions(&supported_tls_versions);
-        assert_eq!(protocol_versions.len(), 3);
+        let supported_tls_versions = vec![TlsVersion::TLSv1_2, TlsVersion::TLSv1_3];
+        let protocol_versions = get_protocol_versions(&supported_tls_versions).unwrap();
+        assert_eq!(protocol_versions.len(), 2);
+        assert!(get_protocol_versions(&[TlsVersion::TLSv1_0, TlsVersion::TLSv1_2]).is_err());
+        assert!(get_protocol_versions(&[TlsVersion::TLSv1_1]).is_err());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
@@ -691,7 +1562,7 @@ mod test {
             "tests/resources/server_key.pem".to_owned(),
             8080,
             None,
-            vec![TlsVersion::TLSv1_0, TlsVersion::TLSv1_1, TlsVersion::TLSv1_2],
+            vec![TlsVersion::TLSv1_2, TlsVersion::TLSv1_3],
         );
         let ssl_builder = ssl_builder(&https_config);
         assert!(ssl_builder.is_ok());
@@ -704,7 +1575,7 @@ mod test {
             "tests/resources/server_key.pem".to_owned(),
             8080,
             Some("tests/resources/client_cert.pem".to_owned()),
-            vec![TlsVersion::TLSv1_0, TlsVersion::TLSv1_1, TlsVersion::TLSv1_2],
+            vec![TlsVersion::TLSv1_2, TlsVersion::TLSv1_3],
         );
         let ssl_builder = ssl_builder(&https_config);
         assert!(ssl_builder.is_ok());
@@ -717,12 +1588,49 @@ mod test {
             "tests/resources/no_file.pem".to_owned(),
             8080,
             None,
-            vec![TlsVersion::TLSv1_0, TlsVersion::TLSv1_1, TlsVersion::TLSv1_2],
+            vec![TlsVersion::TLSv1_2, TlsVersion::TLSv1_3],
         );
         let ssl_builder = ssl_builder(&https_config);
         assert!(ssl_builder.is_err());
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_https_ssl_builder_key_formats() {
+        let pkcs1_config = HttpsConfiguration::new("tests/resources/server_cert.pem".to_owned(), "tests/resources/server_key_pkcs1.pem".to_owned(), 8080, None, vec![TlsVersion::TLSv1_2]);
+        assert!(ssl_builder(&pkcs1_config).is_ok());
+        let sec1_config = HttpsConfiguration::new("tests/resources/server_ec_cert.pem".to_owned(), "tests/resources/server_ec_key.pem".to_owned(), 8080, None, vec![TlsVersion::TLSv1_2]);
+        assert!(ssl_builder(&sec1_config).is_ok());
+        let no_key_config = HttpsConfiguration::new("tests/resources/server_cert.pem".to_owned(), "tests/resources/server_cert.pem".to_owned(), 8080, None, vec![TlsVersion::TLSv1_2]);
+        assert!(ssl_builder(&no_key_config).is_err());
+        let wrong_key_config = HttpsConfiguration::new("tests/resources/server_cert.pem".to_owned(), "tests/resources/server_ec_key.pem".to_owned(), 8080, None, vec![TlsVersion::TLSv1_2]);
+        assert!(ssl_builder(&wrong_key_config).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_https_ssl_builder_sni() {
+        let mut https_config = HttpsConfiguration::new("tests/resources/server_cert.pem".to_owned(), "tests/resources/server_key.pem".to_owned(), 8080, None, vec![TlsVersion::TLSv1_2]);
+        https_config.sni_certificates =
+            Some(vec![SniCertificateConfiguration::new("ec.localhost".to_owned(), "tests/resources/server_ec_cert.pem".to_owned(), "tests/resources/server_ec_key.pem".to_owned())]);
+        assert!(ssl_builder(&https_config).is_ok());
+        https_config.sni_certificates = Some(vec![SniCertificateConfiguration::new("ec.localhost".to_owned(), "tests/resources/no_file.pem".to_owned(), "tests/resources/server_ec_key.pem".to_owned())]);
+        assert!(ssl_builder(&https_config).is_err());
+    }
+
+    /**
+     * Verifying that a route is not called for a fault that breaks the connection.
+     */
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_route_connection_fault() {
+        let mut route = RouteConfiguration::new("http://127.0.0.1:1".to_string(), None, None, false, false, false, None, None, None, Some(100), None, None);
+        route.fault = Some(FaultConfiguration::new(FaultType::EmptyReply, None, None));
+        let endpoint = EndpointConfiguration::new(None, None, None, Some(EndpointType::Route { configuration: route.clone() })).unwrap();
+        let req = TestRequest::default().to_http_request();
+        assert!(handle_endpoint(&endpoint, &EndpointState::new(&endpoint).unwrap(), &req, &None, Vec::new()).await.is_ok());
+        route.fault = Some(FaultConfiguration::new(FaultType::CloseAfterHeaders, None, None));
+        let endpoint = EndpointConfiguration::new(None, None, None, Some(EndpointType::Route { configuration: route })).unwrap();
+        assert!(handle_endpoint(&endpoint, &EndpointState::new(&endpoint).unwrap(), &req, &None, Vec::new()).await.is_err());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
     async fn test_generate_mock_response() {
         let mock_response = MockResponseConfiguration::new(Some("Test".to_owned()), String::from("200"), HashMap::new(), 0);
@@ -744,6 +1652,38 @@ mod test {
         assert!(client.is_ok());
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_get_client_with_client_certificate() {
+        let mut route_configuration = RouteConfiguration::new("https://localhost:8080".to_owned(), None, None, false, false, false, None, None, None, None, None, None);
+        route_configuration.client_certificate = Some("./tests/resources/client_cert.pem".to_owned());
+        route_configuration.client_key = Some("./tests/resources/client_key.pem".to_owned());
+        route_configuration.ca_certificate = Some("./tests/resources/server_cert.pem".to_owned());
+        assert!(get_client(&route_configuration).is_ok());
+        route_configuration.client_key = None;
+        assert!(get_client(&route_configuration).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_get_client_tls_versions() {
+        let route_configuration = RouteConfiguration::new("https://localhost:8080".to_owned(), None, None, false, false, false, Some(TlsVersion::TLSv1_2), Some(TlsVersion::TLSv1_3), None, None, None, None);
+        assert!(get_client(&route_configuration).is_ok());
+        let route_configuration = RouteConfiguration::new("https://localhost:8080".to_owned(), None, None, false, false, false, Some(TlsVersion::TLSv1_1), Some(TlsVersion::TLSv1_2), None, None, None, None);
+        assert!(get_client(&route_configuration).is_ok());
+        let route_configuration = RouteConfiguration::new("https://localhost:8080".to_owned(), None, None, false, false, false, Some(TlsVersion::TLSv1_2), Some(TlsVersion::TLSv1_1), None, None, None, None);
+        assert!(get_client(&route_configuration).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_get_client_pool_settings() {
+        let mut route_configuration = RouteConfiguration::new("http://localhost:8080".to_owned(), None, None, false, false, false, None, None, None, None, None, None);
+        route_configuration.pool_idle_timeout = Some(1000);
+        route_configuration.pool_max_idle_per_host = Some(2);
+        route_configuration.http2_prior_knowledge = true;
+        assert!(get_client(&route_configuration).is_ok());
+        route_configuration.http1_only = true;
+        assert!(get_client(&route_configuration).is_err());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
     async fn convert_params_test() {
         let params = vec![("param1".to_string(), "value1".to_string()), ("param2".to_string(), "value2".to_string())];
@@ -769,4 +1709,36 @@ mod test {
         assert_eq!(result, "This is a test with value1 and value2");
     }
 
+    /**
+     * Verifying that a route without url and upstreams fails when the endpoint state is created.
+     */
+    #[test]
+    fn test_endpoint_state_route_without_url() {
+        let route = RouteConfiguration::new(String::new(), None, None, false, false, false, None, None, None, None, None, None);
+        let endpoint = EndpointConfiguration::new(None, None, None, Some(EndpointType::Route { configuration: route })).unwrap();
+        assert!(matches!(EndpointState::new(&endpoint), Err(ApplicationError::ConfigurationError(_))));
+    }
+
+    /**
+     * Verifying that a fault probability outside 0.0 to 1.0 fails when the endpoint state is created.
+     */
+    #[test]
+    fn test_endpoint_state_invalid_fault_probability() {
+        let mut mock = MockResponseConfiguration::new(None, "200".to_string(), HashMap::new(), 0);
+        mock.fault = Some(FaultConfiguration::new(FaultType::EmptyReply, Some(1.5), None));
+        let endpoint = EndpointConfiguration::new(None, None, None, Some(EndpointType::Mock { configuration: mock })).unwrap();
+        assert!(matches!(EndpointState::new(&endpoint), Err(ApplicationError::ConfigurationError(_))));
+    }
+
+    /**
+     * Verifying that an invalid latency configuration fails when the endpoint state is created.
+     */
+    #[test]
+    fn test_endpoint_state_invalid_latency() {
+        let mut mock = MockResponseConfiguration::new(None, "200".to_string(), HashMap::new(), 0);
+        mock.latency = Some(LatencyConfiguration::new(LatencyDistribution::LogNormal, None, None, Some(-1.0), Some(1.0), None, None));
+        let endpoint = EndpointConfiguration::new(None, None, None, Some(EndpointType::Mock { configuration: mock })).unwrap();
+        assert!(matches!(EndpointState::new(&endpoint), Err(ApplicationError::ConfigurationError(_))));
+    }
+
 }
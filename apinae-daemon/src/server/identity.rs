@@ -0,0 +1,228 @@
+use std::{any::Any, net::IpAddr};
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::{dev::Extensions, rt::net::TcpStream};
+use apinae_lib::{config::ClientIdentityConfiguration, error::ApplicationError};
+use log::error;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+/**
+ * Prefix of the parameters with the client certificate fields.
+ */
+const PARAM_PREFIX: &str = "clientCert.";
+
+/**
+ * The fields of the client certificate verified during the TLS handshake. It is stored in the connection data.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientIdentity {
+    // The common name of the subject.
+    pub subject_cn: Option<String>,
+    // The subject distinguished name.
+    pub subject: String,
+    // The issuer distinguished name.
+    pub issuer: String,
+    // The subject alternative names.
+    pub sans: Vec<String>,
+    // The SHA-256 fingerprint as lower case hex.
+    pub fingerprint: String,
+    // The serial number as hex.
+    pub serial: String,
+}
+
+impl ClientIdentity {
+    /**
+     * Read the identity from a DER encoded certificate.
+     *
+     * # Arguments
+     * `der`: The certificate.
+     *
+     * # Returns
+     * The identity.
+     *
+     * # Errors
+     * An error if the certificate could not be parsed.
+     */
+    pub fn from_der(der: &[u8]) -> Result<Self, ApplicationError> {
+        let (_, certificate) = X509Certificate::from_der(der).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to parse client certificate: {err}")))?;
+        let subject_cn = certificate.subject().iter_common_name().next().and_then(|common_name| common_name.as_str().ok()).map(ToString::to_string);
+        let sans = certificate
+            .subject_alternative_name()
+            .map_err(|err| ApplicationError::ConfigurationError(format!("Failed to parse client certificate subject alternative names: {err}")))?
+            .map(|extension| extension.value.general_names.iter().filter_map(get_general_name).collect())
+            .unwrap_or_default();
+        let fingerprint = Sha256::digest(der).iter().map(|byte| format!("{byte:02x}")).collect();
+        Ok(ClientIdentity { subject_cn, subject: certificate.subject().to_string(), issuer: certificate.issuer().to_string(), sans, fingerprint, serial: certificate.raw_serial_as_string() })
+    }
+
+    /**
+     * Check if the identity matches the configuration.
+     *
+     * # Arguments
+     * `configuration`: The client identity configuration.
+     *
+     * # Returns
+     * True if all the specified expressions match.
+     *
+     * # Errors
+     * An error if a regular expression is invalid.
+     */
+    pub fn matches(&self, configuration: &ClientIdentityConfiguration) -> Result<bool, ApplicationError> {
+        if let Some(subject_cn) = &configuration.subject_cn {
+            if !get_regex(subject_cn)?.is_match(self.subject_cn.as_deref().unwrap_or_default()) {
+                return Ok(false);
+            }
+        }
+        if let Some(san) = &configuration.san {
+            let regex = get_regex(san)?;
+            if !self.sans.iter().any(|name| regex.is_match(name)) {
+                return Ok(false);
+            }
+        }
+        if let Some(issuer) = &configuration.issuer {
+            if !get_regex(issuer)?.is_match(&self.issuer) {
+                return Ok(false);
+            }
+        }
+        if let Some(fingerprint) = &configuration.fingerprint {
+            if fingerprint.replace(':', "").to_lowercase() != self.fingerprint {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /**
+     * Get the fields as parameters for the response templates. Example `${clientCert.subjectCn}`.
+     *
+     * # Returns
+     * The parameters.
+     */
+    pub fn params(&self) -> Vec<(String, String)> {
+        vec![
+            (format!("{PARAM_PREFIX}subjectCn"), self.subject_cn.clone().unwrap_or_default()),
+            (format!("{PARAM_PREFIX}subject"), self.subject.clone()),
+            (format!("{PARAM_PREFIX}issuer"), self.issuer.clone()),
+            (format!("{PARAM_PREFIX}san"), self.sans.join(",")),
+            (format!("{PARAM_PREFIX}fingerprint"), self.fingerprint.clone()),
+            (format!("{PARAM_PREFIX}serial"), self.serial.clone()),
+        ]
+    }
+}
+
+/**
+ * Called when a connection is accepted. Stores the identity of the verified client certificate in the connection data.
+ *
+ * # Arguments
+ * `connection`: The accepted connection. Only tls streams have a client certificate.
+ * `data`: The connection data.
+ */
+pub fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+    let Some(certificate) = stream.get_ref().1.peer_certificates().and_then(<[_]>::first) else {
+        return;
+    };
+    match ClientIdentity::from_der(certificate) {
+        Ok(identity) => {
+            log::debug!("Client certificate subject: {}", identity.subject);
+            data.insert(identity);
+        }
+        Err(err) => error!("{err}"),
+    }
+}
+
+/**
+ * Get a subject alternative name as a string. Other names, directory names and registered ids are skipped.
+ *
+ * # Arguments
+ * `name`: The general name.
+ *
+ * # Returns
+ * The name.
+ */
+fn get_general_name(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(name) | GeneralName::RFC822Name(name) | GeneralName::URI(name) => Some((*name).to_string()),
+        GeneralName::IPAddress(address) => match address.len() {
+            4 => <[u8; 4]>::try_from(*address).ok().map(|address| IpAddr::from(address).to_string()),
+            16 => <[u8; 16]>::try_from(*address).ok().map(|address| IpAddr::from(address).to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/**
+ * Compile the regular expression.
+ *
+ * # Arguments
+ * `expression`: The regular expression.
+ *
+ * # Returns
+ * The regular expression.
+ *
+ * # Errors
+ * An error if the regular expression is invalid.
+ */
+fn get_regex(expression: &str) -> Result<Regex, ApplicationError> {
+    Regex::new(expression).map_err(|err| ApplicationError::ConfigurationError(format!("Error in regular expression {expression}: {err}")))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /**
+     * Read the identity from a pem file in the test resources.
+     */
+    fn get_identity(file: &str) -> ClientIdentity {
+        let certificate = rustls_pemfile::certs(&mut std::io::BufReader::new(std::fs::File::open(file).unwrap())).next().unwrap().unwrap();
+        ClientIdentity::from_der(&certificate).unwrap()
+    }
+
+    /**
+     * Verify the fields read from the certificate.
+     */
+    #[test]
+    fn test_from_der() {
+        let identity = get_identity("./tests/resources/client_cert.pem");
+        assert_eq!(identity.subject_cn, Some("CommonNameOrHostname".to_string()));
+        assert!(identity.issuer.contains("O=CompanyName"));
+        assert!(identity.sans.is_empty());
+        assert_eq!(identity.fingerprint, "3a408d58827693b02a28b0a1d9ab0f43e8a6a9894264bbb6697c94081c95ea71");
+        let identity = get_identity("./tests/resources/server_ec_cert.pem");
+        assert!(identity.sans.contains(&"ec.localhost".to_string()));
+    }
+
+    /**
+     * Verify the matching of the client identity.
+     */
+    #[test]
+    fn test_matches() {
+        let identity = get_identity("./tests/resources/server_ec_cert.pem");
+        assert!(identity.matches(&ClientIdentityConfiguration::default()).unwrap());
+        assert!(identity.matches(&ClientIdentityConfiguration::new(None, Some("^ec\\.".to_string()), None, None)).unwrap());
+        assert!(!identity.matches(&ClientIdentityConfiguration::new(None, Some("^other\\.".to_string()), None, None)).unwrap());
+        let client = get_identity("./tests/resources/client_cert.pem");
+        let fingerprint = "3A:40:8D:58:82:76:93:B0:2A:28:B0:A1:D9:AB:0F:43:E8:A6:A9:89:42:64:BB:B6:69:7C:94:08:1C:95:EA:71".to_string();
+        assert!(client.matches(&ClientIdentityConfiguration::new(Some("^CommonName".to_string()), None, Some("O=CompanyName".to_string()), Some(fingerprint))).unwrap());
+        assert!(!client.matches(&ClientIdentityConfiguration::new(Some("^service-a$".to_string()), None, None, None)).unwrap());
+        assert!(!identity.matches(&ClientIdentityConfiguration::new(None, None, None, Some("00".to_string()))).unwrap());
+        assert!(client.matches(&ClientIdentityConfiguration::new(Some("(".to_string()), None, None, None)).is_err());
+    }
+
+    /**
+     * Verify the template parameters.
+     */
+    #[test]
+    fn test_params() {
+        let params = get_identity("./tests/resources/client_cert.pem").params();
+        assert!(params.contains(&("clientCert.subjectCn".to_string(), "CommonNameOrHostname".to_string())));
+        assert!(params.contains(&("clientCert.san".to_string(), String::new())));
+    }
+}
@@ -0,0 +1,164 @@
+use std::any::Any;
+
+use actix_tls::accept::openssl::TlsStream;
+use actix_web::{dev::Extensions, rt::net::TcpStream};
+use apinae_lib::{
+    config::{HttpsConfiguration, TlsVersion},
+    error::ApplicationError,
+};
+use log::error;
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslOptions, SslVerifyMode, SslVersion};
+
+use super::identity::ClientIdentity;
+
+/**
+ * Cipher list allowing the ciphers used by TLSv1.0 and TLSv1.1. Security level 0 allows SHA-1 signatures.
+ */
+const LEGACY_CIPHER_LIST: &str = "DEFAULT:@SECLEVEL=0";
+
+/**
+ * Check if the supported versions need OpenSSL.
+ *
+ * # Arguments
+ * `supported_tls_versions`: The supported TLS versions.
+ *
+ * # Returns
+ * True if TLSv1.0 or TLSv1.1 is supported.
+ */
+pub fn is_legacy(supported_tls_versions: &[TlsVersion]) -> bool {
+    supported_tls_versions.iter().any(|version| matches!(version, TlsVersion::TLSv1_0 | TlsVersion::TLSv1_1))
+}
+
+/**
+ * Create the OpenSSL acceptor. The versions from the lowest to the highest supported version are enabled.
+ *
+ * # Arguments
+ * `https_config`: The HTTPS configuration.
+ *
+ * # Returns
+ * The acceptor builder.
+ *
+ * # Errors
+ * An error if SNI certificates are configured.
+ * An error if the certificate, private key or client certificate could not be loaded.
+ */
+pub fn ssl_acceptor(https_config: &HttpsConfiguration) -> Result<SslAcceptorBuilder, ApplicationError> {
+    log::info!("Creating OpenSSL acceptor");
+    if https_config.sni_certificates.as_ref().is_some_and(|sni_certificates| !sni_certificates.is_empty()) {
+        return Err(ApplicationError::ConfigurationError("SNI certificates are not supported with TLSv1.0 and TLSv1.1".to_string()));
+    }
+    let versions: Vec<SslVersion> = https_config.supported_tls_versions.iter().map(get_ssl_version).collect();
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls_server()).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to create OpenSSL acceptor: {err}")))?;
+    builder.clear_options(SslOptions::NO_TLSV1_3);
+    builder.set_min_proto_version(versions.iter().min_by_key(|version| get_version_order(**version)).copied()).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to set minimum TLS version: {err}")))?;
+    builder.set_max_proto_version(versions.iter().max_by_key(|version| get_version_order(**version)).copied()).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to set maximum TLS version: {err}")))?;
+    builder.set_cipher_list(LEGACY_CIPHER_LIST).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to set cipher list: {err}")))?;
+    builder
+        .set_certificate_chain_file(&https_config.server_certificate)
+        .map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read certificate file {}: {err}", https_config.server_certificate)))?;
+    builder
+        .set_private_key_file(&https_config.private_key, SslFiletype::PEM)
+        .map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read private key file {}: {err}", https_config.private_key)))?;
+    builder.check_private_key().map_err(|err| ApplicationError::ConfigurationError(format!("Private key {} does not match certificate: {err}", https_config.private_key)))?;
+    if let Some(client_certificate) = &https_config.client_certificate {
+        log::debug!("Require client certificate: {client_certificate}");
+        builder.set_ca_file(client_certificate).map_err(|err| ApplicationError::ConfigurationError(format!("Failed to read client certificate: {err}")))?;
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+    Ok(builder)
+}
+
+/**
+ * Called when a connection is accepted. Stores the identity of the verified client certificate in the connection data.
+ *
+ * # Arguments
+ * `connection`: The accepted connection. Only OpenSSL streams are handled.
+ * `data`: The connection data.
+ */
+pub fn on_connect(connection: &dyn Any, data: &mut Extensions) {
+    let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+    let Some(certificate) = stream.ssl().peer_certificate() else {
+        return;
+    };
+    match certificate.to_der().map_err(|err| ApplicationError::ConfigurationError(format!("Failed to encode client certificate: {err}"))).and_then(|der| ClientIdentity::from_der(&der)) {
+        Ok(identity) => {
+            data.insert(identity);
+        }
+        Err(err) => error!("{err}"),
+    }
+}
+
+/**
+ * Get the OpenSSL version.
+ *
+ * # Arguments
+ * `tls_version`: The TLS version.
+ *
+ * # Returns
+ * The OpenSSL version.
+ */
+fn get_ssl_version(tls_version: &TlsVersion) -> SslVersion {
+    match tls_version {
+        TlsVersion::TLSv1_0 => SslVersion::TLS1,
+        TlsVersion::TLSv1_1 => SslVersion::TLS1_1,
+        TlsVersion::TLSv1_2 => SslVersion::TLS1_2,
+        TlsVersion::TLSv1_3 => SslVersion::TLS1_3,
+    }
+}
+
+/**
+ * Get the order of the OpenSSL version. `SslVersion` is not ordered.
+ *
+ * # Arguments
+ * `version`: The OpenSSL version.
+ *
+ * # Returns
+ * The order.
+ */
+fn get_version_order(version: SslVersion) -> u8 {
+    match version {
+        SslVersion::TLS1 => 0,
+        SslVersion::TLS1_1 => 1,
+        SslVersion::TLS1_2 => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use apinae_lib::config::SniCertificateConfiguration;
+
+    use super::*;
+
+    /**
+     * Verify that legacy versions are detected.
+     */
+    #[test]
+    fn test_is_legacy() {
+        assert!(is_legacy(&[TlsVersion::TLSv1_0, TlsVersion::TLSv1_2]));
+        assert!(!is_legacy(&[TlsVersion::TLSv1_2, TlsVersion::TLSv1_3]));
+    }
+
+    /**
+     * Verify that the acceptor is created and that SNI certificates are an error.
+     */
+    #[test]
+    fn test_ssl_acceptor() {
+        let mut https_config = HttpsConfiguration::new(
+            "tests/resources/server_cert.pem".to_owned(),
+            "tests/resources/server_key.pem".to_owned(),
+            8080,
+            Some("tests/resources/client_cert.pem".to_owned()),
+            vec![TlsVersion::TLSv1_0, TlsVersion::TLSv1_1],
+        );
+        assert!(ssl_acceptor(&https_config).is_ok());
+        https_config.sni_certificates = Some(vec![SniCertificateConfiguration::new("ec.localhost".to_owned(), "tests/resources/server_ec_cert.pem".to_owned(), "tests/resources/server_ec_key.pem".to_owned())]);
+        assert!(ssl_acceptor(&https_config).is_err());
+        https_config.sni_certificates = None;
+        https_config.private_key = "tests/resources/server_ec_key.pem".to_owned();
+        assert!(ssl_acceptor(&https_config).is_err());
+    }
+}
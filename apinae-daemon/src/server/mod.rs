@@ -1,4 +1,26 @@
+mod balancer;
 mod common;
+mod dns;
+mod fault;
+mod forward_proxy;
+mod framing;
 mod http;
+mod identity;
+#[cfg(feature = "openssl")]
+mod legacy_tls;
+mod payload;
+mod pkcs8;
+mod prefixed;
+mod profile;
+mod proxy;
+mod ratelimit;
+mod rewrite;
+mod rules;
+mod script;
 pub mod setup;
+mod smtp;
+mod sni;
 mod tcp;
+mod udp;
+#[cfg(unix)]
+mod unix;
@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+
+use apinae_lib::{config::DataEncoding, error::ApplicationError};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::info;
+use regex::bytes::Regex;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::http::convert_params;
+
+/**
+ * The number of bytes on each line of a hex dump.
+ */
+const HEX_DUMP_LINE_LENGTH: usize = 16;
+
+/**
+ * Matches received bytes.
+ */
+#[derive(Debug)]
+pub(super) enum Matcher {
+    // The bytes must contain the bytes.
+    Bytes(Vec<u8>),
+    // The bytes must match the regular expression.
+    Regex(Regex),
+}
+
+/**
+ * Data to send.
+ */
+#[derive(Debug)]
+pub(super) enum Payload {
+    // Bytes from inline data or hex.
+    Bytes(Vec<u8>),
+    // The file is read when it is sent.
+    File(String),
+}
+
+impl Payload {
+    /**
+     * Get the bytes of the payload. The file is read each time.
+     *
+     * # Returns
+     * The bytes.
+     *
+     * # Errors
+     * An error if the file could not be read.
+     */
+    pub(super) async fn load(&self) -> Result<Cow<'_, [u8]>, ApplicationError> {
+        match self {
+            Payload::Bytes(bytes) => Ok(Cow::Borrowed(bytes)),
+            Payload::File(file) => tokio::fs::read(file).await.map(Cow::Owned).map_err(|err| ApplicationError::FileError(format!("Failed to read file {file}: {err}"))),
+        }
+    }
+}
+
+impl Matcher {
+    /**
+     * Find the match in the input. An empty byte pattern matches at the start.
+     *
+     * # Arguments
+     * `input`: The input.
+     *
+     * # Returns
+     * The end of the match, if any.
+     */
+    pub(super) fn find(&self, input: &[u8]) -> Option<usize> {
+        match self {
+            Matcher::Bytes(bytes) if bytes.is_empty() => Some(0),
+            Matcher::Bytes(bytes) => input.windows(bytes.len()).position(|window| window == bytes.as_slice()).map(|position| position + bytes.len()),
+            Matcher::Regex(regex) => regex.find(input).map(|found| found.end()),
+        }
+    }
+}
+
+/**
+ * Write the payload to the stream.
+ *
+ * # Arguments
+ * `stream`: The stream.
+ * `payload`: The payload.
+ *
+ * # Errors
+ * An error if the file could not be read or the stream could not be written.
+ */
+pub(super) async fn send<S: AsyncWrite + Unpin>(stream: &mut S, payload: &Payload) -> Result<(), ApplicationError> {
+    let data = payload.load().await?;
+    info!("Sending: {:?}", String::from_utf8_lossy(&data));
+    stream.write_all(&data).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to write: {err}")))?;
+    stream.flush().await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to write: {err}")))
+}
+
+/**
+ * Get the payload. Data is used before hex and hex before file. Parameters are replaced in the data.
+ *
+ * # Arguments
+ * `data`: Text.
+ * `hex`: Bytes as hex.
+ * `file`: The file.
+ * `params`: The parameters.
+ *
+ * # Returns
+ * The payload, or None if nothing is set.
+ *
+ * # Errors
+ * An error if the hex is invalid.
+ */
+pub(super) fn get_payload(data: Option<&String>, hex: Option<&String>, file: Option<&String>, params: &Vec<(String, String)>) -> Result<Option<Payload>, ApplicationError> {
+    if let Some(data) = data {
+        return Ok(Some(Payload::Bytes(convert_params(data, params).into_bytes())));
+    }
+    if let Some(hex) = hex {
+        return Ok(Some(Payload::Bytes(decode_hex(hex)?)));
+    }
+    Ok(file.map(|file| Payload::File(file.clone())))
+}
+
+/**
+ * Decode hex. Whitespace is ignored.
+ *
+ * # Arguments
+ * `hex`: The hex string.
+ *
+ * # Returns
+ * The bytes.
+ *
+ * # Errors
+ * An error if the hex is invalid.
+ */
+pub(super) fn decode_hex(hex: &str) -> Result<Vec<u8>, ApplicationError> {
+    let hex: String = hex.chars().filter(|character| !character.is_whitespace()).collect();
+    hex::decode(&hex).map_err(|err| ApplicationError::ConfigurationError(format!("Invalid hex {hex}: {err}")))
+}
+
+/**
+ * Decode inline data. Parameters are replaced before the data is decoded.
+ *
+ * # Arguments
+ * `data`: The data.
+ * `encoding`: How the data is encoded.
+ * `params`: The parameters.
+ *
+ * # Returns
+ * The bytes.
+ *
+ * # Errors
+ * An error if the data is not valid for the encoding.
+ */
+pub(super) fn decode_data(data: &str, encoding: &DataEncoding, params: &Vec<(String, String)>) -> Result<Vec<u8>, ApplicationError> {
+    let data = convert_params(data, params);
+    match encoding {
+        DataEncoding::Text => Ok(data.into_bytes()),
+        DataEncoding::Escaped => unescape(&data),
+        DataEncoding::Hex => decode_hex(&data),
+        DataEncoding::Base64 => STANDARD.decode(data.trim()).map_err(|err| ApplicationError::ConfigurationError(format!("Invalid base64 {data}: {err}"))),
+    }
+}
+
+/**
+ * Replace the escape sequences \n, \r, \t, \0, \\ and \xHH with the bytes.
+ *
+ * # Arguments
+ * `data`: The text with escape sequences.
+ *
+ * # Returns
+ * The bytes.
+ *
+ * # Errors
+ * An error if an escape sequence is invalid.
+ */
+fn unescape(data: &str) -> Result<Vec<u8>, ApplicationError> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut characters = data.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        match characters.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = characters.by_ref().take(2).collect();
+                let byte = if hex.len() == 2 { u8::from_str_radix(&hex, 16).ok() } else { None };
+                bytes.push(byte.ok_or_else(|| ApplicationError::ConfigurationError(format!("Invalid escape sequence \\x{hex}")))?);
+            }
+            Some(other) => return Err(ApplicationError::ConfigurationError(format!("Invalid escape sequence \\{other}"))),
+            None => return Err(ApplicationError::ConfigurationError("Escape sequence at the end of the data".to_string())),
+        }
+    }
+    Ok(bytes)
+}
+
+/**
+ * Format the bytes as a hex dump with the offset, the hex bytes and the printable characters.
+ *
+ * # Arguments
+ * `bytes`: The bytes.
+ *
+ * # Returns
+ * The hex dump with a line for each 16 bytes.
+ */
+pub(super) fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(HEX_DUMP_LINE_LENGTH)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+            let text: String = chunk.iter().map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { char::from(*byte) } else { '.' }).collect();
+            format!("{:08x}  {:<47}  |{text}|", index * HEX_DUMP_LINE_LENGTH, hex.join(" "))
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/**
+ * Log the received bytes as text and as a hex dump.
+ *
+ * # Arguments
+ * `bytes`: The received bytes.
+ */
+pub(super) fn log_received(bytes: &[u8]) {
+    info!("Received: {:?}\n{}", String::from_utf8_lossy(bytes), hex_dump(bytes));
+}
+
+/**
+ * Compile the regular expression.
+ *
+ * # Arguments
+ * `expression`: The regular expression.
+ *
+ * # Returns
+ * The regular expression.
+ *
+ * # Errors
+ * An error if the regular expression is invalid.
+ */
+pub(super) fn get_regex(expression: &str) -> Result<Regex, ApplicationError> {
+    Regex::new(expression).map_err(|err| ApplicationError::ConfigurationError(format!("Error in regular expression {expression}: {err}")))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /**
+     * Verify decoding hex with whitespace.
+     */
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("02 41\n42 03").unwrap(), vec![0x02, 0x41, 0x42, 0x03]);
+        assert!(decode_hex("0").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    /**
+     * Verify decoding the data encodings with parameters.
+     */
+    #[test]
+    fn test_decode_data() {
+        let params = vec![("name".to_string(), "ok".to_string())];
+        assert_eq!(decode_data("${name}", &DataEncoding::Text, &params).unwrap(), b"ok".to_vec());
+        assert_eq!(decode_data("\\x02${name}\\r\\n\\x03\\\\", &DataEncoding::Escaped, &params).unwrap(), b"\x02ok\r\n\x03\\".to_vec());
+        assert_eq!(decode_data("0a ff", &DataEncoding::Hex, &params).unwrap(), vec![0x0a, 0xff]);
+        assert_eq!(decode_data("AAEC", &DataEncoding::Base64, &params).unwrap(), vec![0, 1, 2]);
+        assert!(decode_data("\\x4", &DataEncoding::Escaped, &params).is_err());
+        assert!(decode_data("\\q", &DataEncoding::Escaped, &params).is_err());
+        assert!(decode_data("!", &DataEncoding::Base64, &params).is_err());
+    }
+
+    /**
+     * Verify the hex dump format.
+     */
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"0123456789abcdef\x02A");
+        assert_eq!(dump, "00000000  30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  |0123456789abcdef|\n00000010  02 41                                            |.A|");
+    }
+
+    /**
+     * Verify finding bytes and regular expressions.
+     */
+    #[test]
+    fn test_find() {
+        assert_eq!(Matcher::Bytes(b"BC".to_vec()).find(b"ABCD"), Some(3));
+        assert_eq!(Matcher::Bytes(b"X".to_vec()).find(b"ABCD"), None);
+        assert_eq!(Matcher::Regex(get_regex("^A.").unwrap()).find(b"ABCD"), Some(2));
+        assert!(get_regex("(").is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/**
+ * A stream that returns bytes already read from the inner stream before reading from it.
+ */
+pub struct PrefixedStream<S> {
+    // The bytes returned first.
+    prefix: Vec<u8>,
+    // The position in the prefix.
+    position: usize,
+    // The inner stream.
+    stream: S,
+}
+
+impl<S> PrefixedStream<S> {
+    /**
+     * Create a new prefixed stream.
+     *
+     * # Arguments
+     * `prefix`: The bytes returned first.
+     * `stream`: The inner stream.
+     *
+     * # Returns
+     * The prefixed stream.
+     */
+    pub fn new(prefix: Vec<u8>, stream: S) -> Self {
+        PrefixedStream { prefix, position: 0, stream }
+    }
+
+    /**
+     * Get the inner stream.
+     *
+     * # Returns
+     * The inner stream.
+     */
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for PrefixedStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.position < this.prefix.len() {
+            let length = buf.remaining().min(this.prefix.len() - this.position);
+            buf.put_slice(&this.prefix[this.position..this.position + length]);
+            this.position += length;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.stream).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for PrefixedStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,379 @@
+use std::{sync::Mutex, time::Duration};
+
+use apinae_lib::{
+    config::{EndpointConfiguration, EndpointType, ErrorRateConfiguration, LatencyConfiguration, LatencyDistribution},
+    error::ApplicationError,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, LogNormal, Normal};
+
+/**
+ * The random profiles for an endpoint. The random generators are kept between requests so
+ * that a seeded endpoint gives the same sequence of delays and errors on every run.
+ */
+pub struct EndpointProfiles {
+    // Random latency.
+    pub latency: Option<LatencyProfile>,
+    // Error rate.
+    pub error_rate: Option<ErrorRateProfile>,
+}
+
+impl EndpointProfiles {
+    /**
+     * Create the profiles for an endpoint.
+     *
+     * # Arguments
+     * `endpoint`: The endpoint configuration.
+     *
+     * # Returns
+     * The endpoint profiles.
+     *
+     * # Errors
+     * An error if the latency or error rate configuration is invalid.
+     */
+    pub fn new(endpoint: &EndpointConfiguration) -> Result<Self, ApplicationError> {
+        let (latency, error_rate) = match &endpoint.endpoint_type {
+            Some(EndpointType::Mock { configuration }) => (configuration.latency.clone(), configuration.error_rate.clone()),
+            Some(EndpointType::Route { configuration }) => (configuration.latency.clone(), configuration.error_rate.clone()),
+            None => (None, None),
+        };
+        Ok(EndpointProfiles { latency: latency.map(LatencyProfile::new).transpose()?, error_rate: error_rate.map(ErrorRateProfile::new).transpose()? })
+    }
+}
+
+/**
+ * Random latency for an endpoint.
+ */
+pub struct LatencyProfile {
+    // The latency configuration.
+    configuration: LatencyConfiguration,
+    // The distribution of the delays.
+    sampler: LatencySampler,
+    // The random generator.
+    rng: Mutex<StdRng>,
+}
+
+/**
+ * The distribution of the delays created from the latency configuration.
+ */
+enum LatencySampler {
+    // Uniform between min and max.
+    Uniform(u64, u64),
+    // Normal distribution.
+    Normal(Normal<f64>),
+    // Log-normal distribution.
+    LogNormal(LogNormal<f64>),
+    // The configured percentiles and delays.
+    Percentiles(Vec<(f64, f64)>),
+}
+
+impl LatencyProfile {
+    /**
+     * Create a new latency profile.
+     *
+     * # Arguments
+     * `configuration`: The latency configuration.
+     *
+     * # Returns
+     * The latency profile.
+     *
+     * # Errors
+     * An error if the configuration is missing values required by the distribution or the values are invalid.
+     */
+    pub fn new(configuration: LatencyConfiguration) -> Result<Self, ApplicationError> {
+        let sampler = match configuration.distribution {
+            LatencyDistribution::Uniform => {
+                let min = configuration.min.ok_or_else(|| missing_value("min", "uniform"))?;
+                let max = configuration.max.ok_or_else(|| missing_value("max", "uniform"))?;
+                LatencySampler::Uniform(min, max)
+            }
+            LatencyDistribution::Normal => {
+                let (mean, std_dev) = get_mean_and_std_dev(&configuration, "normal")?;
+                LatencySampler::Normal(Normal::new(mean, std_dev).map_err(|err| ApplicationError::ConfigurationError(format!("Invalid normal distribution: {err}")))?)
+            }
+            LatencyDistribution::LogNormal => {
+                let (mean, std_dev) = get_mean_and_std_dev(&configuration, "log-normal")?;
+                if mean <= 0.0 {
+                    return Err(ApplicationError::ConfigurationError("Mean must be positive for log-normal latency".to_string()));
+                }
+                // Convert the mean and standard deviation of the delays to the parameters of the underlying normal distribution.
+                let sigma_squared = (1.0 + (std_dev * std_dev) / (mean * mean)).ln();
+                let mu = mean.ln() - sigma_squared / 2.0;
+                LatencySampler::LogNormal(LogNormal::new(mu, sigma_squared.sqrt()).map_err(|err| ApplicationError::ConfigurationError(format!("Invalid log-normal distribution: {err}")))?)
+            }
+            LatencyDistribution::Percentiles => {
+                let percentiles = configuration.percentiles.as_ref().ok_or_else(|| missing_value("percentiles", "percentiles"))?;
+                LatencySampler::Percentiles(percentiles.iter().map(|p| (p.percentile, p.delay as f64)).collect())
+            }
+        };
+        let rng = Mutex::new(get_rng(configuration.seed));
+        Ok(LatencyProfile { configuration, sampler, rng })
+    }
+
+    /**
+     * Get the next delay.
+     *
+     * # Returns
+     * The delay.
+     *
+     * # Errors
+     * An error if the random generator is poisoned.
+     */
+    pub fn next_delay(&self) -> Result<Duration, ApplicationError> {
+        let mut rng = self.rng.lock().map_err(|err| ApplicationError::ConfigurationError(format!("Failed to lock random generator: {err}")))?;
+        let delay = match &self.sampler {
+            LatencySampler::Uniform(min, max) => {
+                if min >= max {
+                    *min as f64
+                } else {
+                    rng.random_range(*min..=*max) as f64
+                }
+            }
+            LatencySampler::Normal(normal) => normal.sample(&mut *rng),
+            LatencySampler::LogNormal(log_normal) => log_normal.sample(&mut *rng),
+            LatencySampler::Percentiles(percentiles) => interpolate_percentiles(percentiles.clone(), self.configuration.min, self.configuration.max, rng.random_range(0.0..100.0)),
+        };
+        Ok(Duration::from_millis(self.clamp(delay)))
+    }
+
+    /**
+     * Clamp the delay between min and max. Negative delays become zero.
+     *
+     * # Arguments
+     * `delay`: The delay in milliseconds.
+     *
+     * # Returns
+     * The clamped delay in milliseconds.
+     */
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn clamp(&self, delay: f64) -> u64 {
+        let mut delay = delay.max(0.0).round() as u64;
+        if let Some(min) = self.configuration.min {
+            delay = delay.max(min);
+        }
+        if let Some(max) = self.configuration.max {
+            delay = delay.min(max);
+        }
+        delay
+    }
+}
+
+/**
+ * Error rate for an endpoint.
+ */
+pub struct ErrorRateProfile {
+    // The error rate configuration.
+    configuration: ErrorRateConfiguration,
+    // The random generator.
+    rng: Mutex<StdRng>,
+}
+
+impl ErrorRateProfile {
+    /**
+     * Create a new error rate profile.
+     *
+     * # Arguments
+     * `configuration`: The error rate configuration.
+     *
+     * # Returns
+     * The error rate profile.
+     *
+     * # Errors
+     * An error if the percentage is outside 0 to 100.
+     */
+    pub fn new(configuration: ErrorRateConfiguration) -> Result<Self, ApplicationError> {
+        if !(0.0..=100.0).contains(&configuration.percentage) {
+            return Err(ApplicationError::ConfigurationError(format!("Error rate percentage {} must be between 0 and 100", configuration.percentage)));
+        }
+        let rng = Mutex::new(get_rng(configuration.seed));
+        Ok(ErrorRateProfile { configuration, rng })
+    }
+
+    /**
+     * Get the error rate configuration.
+     *
+     * # Returns
+     * The error rate configuration.
+     */
+    pub fn configuration(&self) -> &ErrorRateConfiguration {
+        &self.configuration
+    }
+
+    /**
+     * Check if the next response should be the alternate response.
+     *
+     * # Returns
+     * True if the alternate response should be returned.
+     *
+     * # Errors
+     * An error if the random generator is poisoned.
+     */
+    pub fn is_error(&self) -> Result<bool, ApplicationError> {
+        let mut rng = self.rng.lock().map_err(|err| ApplicationError::ConfigurationError(format!("Failed to lock random generator: {err}")))?;
+        Ok(rng.random_range(0.0..100.0) < self.configuration.percentage)
+    }
+}
+
+/**
+ * Get a random generator. If a seed is provided the generator is seeded.
+ *
+ * # Arguments
+ * `seed`: The optional seed.
+ *
+ * # Returns
+ * The random generator.
+ */
+fn get_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
+}
+
+/**
+ * Get the mean and standard deviation.
+ *
+ * # Arguments
+ * `configuration`: The latency configuration.
+ * `distribution`: Name of the distribution used in the error message.
+ *
+ * # Returns
+ * The mean and the standard deviation.
+ *
+ * # Errors
+ * An error if the mean or the standard deviation is missing, or the standard deviation is negative.
+ */
+fn get_mean_and_std_dev(configuration: &LatencyConfiguration, distribution: &str) -> Result<(f64, f64), ApplicationError> {
+    let mean = configuration.mean.ok_or_else(|| missing_value("mean", distribution))?;
+    let std_dev = configuration.std_dev.ok_or_else(|| missing_value("stdDev", distribution))?;
+    if std_dev < 0.0 {
+        return Err(ApplicationError::ConfigurationError(format!("Standard deviation must not be negative for {distribution} latency")));
+    }
+    Ok((mean, std_dev))
+}
+
+/**
+ * Get the delay for a percentile by linear interpolation between the configured percentiles.
+ * The 0th percentile is min or zero and the 100th percentile is max or the highest configured delay.
+ *
+ * # Arguments
+ * `percentiles`: The configured percentiles and delays.
+ * `min`: The minimum delay.
+ * `max`: The maximum delay.
+ * `percentile`: The percentile to get the delay for.
+ *
+ * # Returns
+ * The delay in milliseconds.
+ */
+#[allow(clippy::cast_precision_loss)]
+fn interpolate_percentiles(mut percentiles: Vec<(f64, f64)>, min: Option<u64>, max: Option<u64>, percentile: f64) -> f64 {
+    percentiles.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let highest = percentiles.last().map_or(0.0, |last| last.1);
+    let mut points = vec![(0.0, min.map_or(0.0, |min| min as f64))];
+    points.extend(percentiles);
+    points.push((100.0, max.map_or(highest, |max| max as f64)));
+    for window in points.windows(2) {
+        let (lower, upper) = (window[0], window[1]);
+        if percentile <= upper.0 {
+            if upper.0 <= lower.0 {
+                return upper.1;
+            }
+            return lower.1 + (upper.1 - lower.1) * (percentile - lower.0) / (upper.0 - lower.0);
+        }
+    }
+    highest
+}
+
+/**
+ * Create an error for a missing latency value.
+ *
+ * # Arguments
+ * `value`: The name of the missing value.
+ * `distribution`: The name of the distribution.
+ *
+ * # Returns
+ * The error.
+ */
+fn missing_value(value: &str, distribution: &str) -> ApplicationError {
+    ApplicationError::ConfigurationError(format!("Missing {value} for {distribution} latency"))
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::HashMap;
+
+    use apinae_lib::config::LatencyPercentile;
+
+    use super::*;
+
+    /**
+     * Verify that seeded latency is reproducible and inside the bounds.
+     */
+    #[test]
+    fn test_uniform_latency_seeded() {
+        let configuration = LatencyConfiguration::new(LatencyDistribution::Uniform, Some(10), Some(20), None, None, None, Some(42));
+        let first = LatencyProfile::new(configuration.clone()).unwrap();
+        let second = LatencyProfile::new(configuration).unwrap();
+        for _ in 0..100 {
+            let delay = first.next_delay().unwrap();
+            assert_eq!(delay, second.next_delay().unwrap());
+            assert!(delay >= Duration::from_millis(10) && delay <= Duration::from_millis(20));
+        }
+    }
+
+    /**
+     * Verify that normal and log-normal latencies are clamped.
+     */
+    #[test]
+    fn test_normal_latency_clamped() {
+        let normal = LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::Normal, Some(50), Some(150), Some(100.0), Some(200.0), None, Some(1))).unwrap();
+        let log_normal = LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::LogNormal, None, Some(1000), Some(100.0), Some(50.0), None, Some(1))).unwrap();
+        for _ in 0..100 {
+            let delay = normal.next_delay().unwrap();
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+            assert!(log_normal.next_delay().unwrap() <= Duration::from_millis(1000));
+        }
+    }
+
+    /**
+     * Verify that missing and invalid values are reported when the profile is created.
+     */
+    #[test]
+    fn test_latency_invalid_values() {
+        assert!(LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::Uniform, Some(10), None, None, None, None, None)).is_err());
+        assert!(LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::Normal, None, None, Some(10.0), None, None, None)).is_err());
+        assert!(LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::Percentiles, None, None, None, None, None, None)).is_err());
+        assert!(LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::Normal, None, None, Some(10.0), Some(-1.0), None, None)).is_err());
+        assert!(LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::LogNormal, None, None, Some(0.0), Some(1.0), None, None)).is_err());
+    }
+
+    /**
+     * Verify the percentile interpolation.
+     */
+    #[test]
+    fn test_interpolate_percentiles() {
+        let percentiles = vec![(99.0, 800.0), (50.0, 100.0)];
+        assert!((interpolate_percentiles(percentiles.clone(), None, None, 25.0) - 50.0).abs() < f64::EPSILON);
+        assert!((interpolate_percentiles(percentiles.clone(), None, None, 50.0) - 100.0).abs() < f64::EPSILON);
+        assert!((interpolate_percentiles(percentiles.clone(), None, Some(1000), 99.5) - 900.0).abs() < f64::EPSILON);
+        assert!((interpolate_percentiles(percentiles, Some(20), None, 0.0) - 20.0).abs() < f64::EPSILON);
+        let profile = LatencyProfile::new(LatencyConfiguration::new(LatencyDistribution::Percentiles, None, None, None, None, Some(vec![LatencyPercentile { percentile: 50.0, delay: 100 }]), Some(3))).unwrap();
+        assert!(profile.next_delay().unwrap() <= Duration::from_millis(100));
+    }
+
+    /**
+     * Verify the error rate boundaries.
+     */
+    #[test]
+    fn test_error_rate() {
+        let never = ErrorRateProfile::new(ErrorRateConfiguration::new(0.0, "500".to_string(), HashMap::new(), None, Some(7))).unwrap();
+        let always = ErrorRateProfile::new(ErrorRateConfiguration::new(100.0, "500".to_string(), HashMap::new(), None, None)).unwrap();
+        for _ in 0..100 {
+            assert!(!never.is_error().unwrap());
+            assert!(always.is_error().unwrap());
+        }
+        assert!(ErrorRateProfile::new(ErrorRateConfiguration::new(100.5, "500".to_string(), HashMap::new(), None, None)).is_err());
+        assert!(ErrorRateProfile::new(ErrorRateConfiguration::new(-1.0, "500".to_string(), HashMap::new(), None, None)).is_err());
+    }
+}
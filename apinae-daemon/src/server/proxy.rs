@@ -0,0 +1,528 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix_web::{web, App, HttpResponse, HttpServer};
+use apinae_lib::{
+    config::{TcpProxyConfiguration, ToxicConfiguration, ToxicType},
+    error::ApplicationError,
+};
+use log::{error, info};
+use rand::Rng;
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::tcp::READ_BUFFER_SIZE;
+
+/**
+ * The direction of the data a toxic is applied to.
+ */
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum ToxicDirection {
+    // From the client to the upstream.
+    Upstream,
+    // From the upstream to the client.
+    Downstream,
+}
+
+/**
+ * The compiled toxic.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ToxicKind {
+    // Delay and jitter.
+    Latency(u64, u64),
+    // Bytes per second.
+    Bandwidth(u64),
+    // Fragment size and delay between the fragments.
+    Slicer(usize, u64),
+    // Close the connection after the bytes.
+    LimitData(u64),
+    // Discard the data.
+    Blackhole,
+    // Shut down the write side after the bytes.
+    HalfClose(u64),
+}
+
+/**
+ * A toxic with the enabled state. The state can be changed while the proxy is running.
+ */
+#[derive(Debug)]
+pub struct Toxic {
+    // The unique name.
+    name: String,
+    // The direction the toxic is applied to.
+    direction: ToxicDirection,
+    // The configured type.
+    toxic_type: ToxicType,
+    // The compiled toxic.
+    kind: ToxicKind,
+    // The toxic is applied.
+    enabled: AtomicBool,
+}
+
+/**
+ * The state of a toxic returned by the control api.
+ */
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToxicState {
+    // The unique name.
+    name: String,
+    // The direction the toxic is applied to.
+    direction: ToxicDirection,
+    // The type of toxic.
+    toxic_type: ToxicType,
+    // The toxic is applied.
+    enabled: bool,
+}
+
+/**
+ * How a direction of the proxied connection ended.
+ */
+#[derive(Debug, PartialEq)]
+enum Flow {
+    // The reader was closed. The other direction continues.
+    Ended,
+    // The whole connection must be closed.
+    Closed,
+}
+
+/**
+ * A tcp proxy forwarding the connections to the upstream with the toxics applied.
+ */
+#[derive(Debug)]
+pub struct Proxy {
+    // The upstream address.
+    upstream: String,
+    // Toxics for the data from the client to the upstream.
+    upstream_toxics: Vec<Arc<Toxic>>,
+    // Toxics for the data from the upstream to the client.
+    downstream_toxics: Vec<Arc<Toxic>>,
+    // Port of the control api.
+    control_port: Option<u16>,
+}
+
+impl Proxy {
+    /**
+     * Compile the proxy configuration.
+     *
+     * # Arguments
+     * `configuration`: The proxy configuration.
+     *
+     * # Returns
+     * The proxy.
+     *
+     * # Errors
+     * An error if a toxic name is used more than once or a toxic is missing the fields used by its type.
+     */
+    pub fn new(configuration: &TcpProxyConfiguration) -> Result<Self, ApplicationError> {
+        let mut names = HashSet::new();
+        for toxic in configuration.upstream_toxics.iter().chain(&configuration.downstream_toxics) {
+            if !names.insert(toxic.name.as_str()) {
+                return Err(ApplicationError::ConfigurationError(format!("Toxic name {} is used more than once", toxic.name)));
+            }
+        }
+        Ok(Proxy {
+            upstream: configuration.upstream.clone(),
+            upstream_toxics: configuration.upstream_toxics.iter().map(|toxic| Toxic::new(toxic, ToxicDirection::Upstream).map(Arc::new)).collect::<Result<Vec<Arc<Toxic>>, ApplicationError>>()?,
+            downstream_toxics: configuration
+                .downstream_toxics
+                .iter()
+                .map(|toxic| Toxic::new(toxic, ToxicDirection::Downstream).map(Arc::new))
+                .collect::<Result<Vec<Arc<Toxic>>, ApplicationError>>()?,
+            control_port: configuration.control_port,
+        })
+    }
+
+    /**
+     * Start the control api if a control port is configured.
+     *
+     * `GET /toxics` lists the toxics. `POST /toxics/{name}/enable` and `POST /toxics/{name}/disable` change the state.
+     *
+     * # Errors
+     * An error if the control api could not be started.
+     */
+    pub fn start_control(&self) -> Result<(), ApplicationError> {
+        let Some(control_port) = self.control_port else {
+            return Ok(());
+        };
+        let toxics = web::Data::new(self.upstream_toxics.iter().chain(&self.downstream_toxics).cloned().collect::<Vec<Arc<Toxic>>>());
+        let server = HttpServer::new(move || App::new().app_data(toxics.clone()).configure(configure_control))
+            .bind(("127.0.0.1", control_port))
+            .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create proxy control api: {err}")))?
+            .workers(1)
+            .run();
+        info!("Proxy control api on port: {control_port}");
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                error!("Proxy control api error: {err}");
+            }
+        });
+        Ok(())
+    }
+
+    /**
+     * Forward the connection to the upstream until both directions end or a toxic closes the connection.
+     *
+     * # Arguments
+     * `client`: The client stream.
+     *
+     * # Returns
+     * Ok if the connection ended.
+     *
+     * # Errors
+     * An error if the upstream could not be reached or a stream could not be read or written.
+     */
+    pub async fn run<S: AsyncRead + AsyncWrite + Unpin>(&self, client: &mut S) -> Result<(), ApplicationError> {
+        let upstream = tokio::net::TcpStream::connect(&self.upstream).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to connect to upstream {}: {err}", self.upstream)))?;
+        info!("Proxying to: {}", self.upstream);
+        let (client_reader, client_writer) = tokio::io::split(client);
+        let (upstream_reader, upstream_writer) = upstream.into_split();
+        let to_upstream = pipe(client_reader, upstream_writer, &self.upstream_toxics);
+        let to_client = pipe(upstream_reader, client_writer, &self.downstream_toxics);
+        tokio::pin!(to_upstream, to_client);
+        let (mut upstream_ended, mut client_ended) = (false, false);
+        while !(upstream_ended && client_ended) {
+            let flow = tokio::select! {
+                flow = &mut to_upstream, if !upstream_ended => {
+                    upstream_ended = true;
+                    flow?
+                }
+                flow = &mut to_client, if !client_ended => {
+                    client_ended = true;
+                    flow?
+                }
+            };
+            if flow == Flow::Closed {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Toxic {
+    /**
+     * Compile the toxic configuration.
+     *
+     * # Arguments
+     * `configuration`: The toxic configuration.
+     * `direction`: The direction the toxic is applied to.
+     *
+     * # Returns
+     * The toxic.
+     *
+     * # Errors
+     * An error if the fields used by the toxic type are missing.
+     */
+    fn new(configuration: &ToxicConfiguration, direction: ToxicDirection) -> Result<Self, ApplicationError> {
+        let missing = |field: &str| ApplicationError::ConfigurationError(format!("Toxic {} requires {field}", configuration.name));
+        let kind = match configuration.toxic_type {
+            ToxicType::Latency => ToxicKind::Latency(configuration.latency_ms.ok_or_else(|| missing("latencyMs"))?, configuration.jitter_ms.unwrap_or_default()),
+            ToxicType::Bandwidth => ToxicKind::Bandwidth(configuration.bytes_per_second.filter(|bytes_per_second| *bytes_per_second > 0).ok_or_else(|| missing("bytesPerSecond above 0"))?),
+            ToxicType::Slicer => {
+                ToxicKind::Slicer(configuration.slice_size.filter(|slice_size| *slice_size > 0).ok_or_else(|| missing("sliceSize above 0"))?, configuration.slice_delay_ms.unwrap_or_default())
+            }
+            ToxicType::LimitData => ToxicKind::LimitData(configuration.bytes.unwrap_or_default()),
+            ToxicType::Blackhole => ToxicKind::Blackhole,
+            ToxicType::HalfClose => ToxicKind::HalfClose(configuration.bytes.unwrap_or_default()),
+        };
+        Ok(Toxic { name: configuration.name.clone(), direction, toxic_type: configuration.toxic_type.clone(), kind, enabled: AtomicBool::new(configuration.enabled) })
+    }
+
+    /**
+     * Check if the toxic is applied.
+     *
+     * # Returns
+     * True if the toxic is enabled.
+     */
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /**
+     * Get the state returned by the control api.
+     *
+     * # Returns
+     * The state of the toxic.
+     */
+    fn state(&self) -> ToxicState {
+        ToxicState { name: self.name.clone(), direction: self.direction, toxic_type: self.toxic_type.clone(), enabled: self.is_enabled() }
+    }
+}
+
+/**
+ * Forward the data from the reader to the writer with the enabled toxics applied. The toxics are checked for each
+ * read, so enabling or disabling a toxic changes the data read after it.
+ *
+ * # Arguments
+ * `reader`: The stream read from.
+ * `writer`: The stream written to.
+ * `toxics`: The toxics for the direction.
+ *
+ * # Returns
+ * How the direction ended.
+ *
+ * # Errors
+ * An error if a stream could not be read or written.
+ */
+async fn pipe<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(mut reader: R, mut writer: W, toxics: &[Arc<Toxic>]) -> Result<Flow, ApplicationError> {
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    let mut forwarded: u64 = 0;
+    let mut half_closed = false;
+    loop {
+        let read = reader.read(&mut buffer).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to read: {err}")))?;
+        if read == 0 {
+            let _ = writer.shutdown().await;
+            return Ok(Flow::Ended);
+        }
+        if half_closed {
+            continue;
+        }
+        let mut data = &buffer[..read];
+        let mut slice = None;
+        let mut end = None;
+        for toxic in toxics.iter().filter(|toxic| toxic.is_enabled()) {
+            match toxic.kind {
+                ToxicKind::Latency(latency, jitter) => {
+                    let delay = rand::rng().random_range(latency.saturating_sub(jitter)..=latency.saturating_add(jitter));
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                ToxicKind::Bandwidth(bytes_per_second) => tokio::time::sleep(Duration::from_millis(data.len() as u64 * 1000 / bytes_per_second)).await,
+                ToxicKind::Slicer(slice_size, slice_delay) => slice = Some((slice_size, slice_delay)),
+                ToxicKind::Blackhole => data = &[],
+                ToxicKind::LimitData(bytes) | ToxicKind::HalfClose(bytes) => {
+                    let allowed = usize::try_from(bytes.saturating_sub(forwarded)).unwrap_or(usize::MAX);
+                    if allowed <= data.len() {
+                        data = &data[..allowed];
+                        end = Some(toxic.kind);
+                    }
+                }
+            }
+        }
+        write(&mut writer, data, slice).await?;
+        forwarded += data.len() as u64;
+        match end {
+            Some(ToxicKind::LimitData(_)) => {
+                info!("Closing proxied connection after {forwarded} bytes");
+                return Ok(Flow::Closed);
+            }
+            Some(_) => {
+                info!("Half closing proxied connection after {forwarded} bytes");
+                let _ = writer.shutdown().await;
+                half_closed = true;
+            }
+            None => {}
+        }
+    }
+}
+
+/**
+ * Write the data, in fragments if the slicer is enabled.
+ *
+ * # Arguments
+ * `writer`: The stream written to.
+ * `data`: The data.
+ * `slice`: The fragment size and the delay between the fragments.
+ *
+ * # Errors
+ * An error if the stream could not be written.
+ */
+async fn write<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8], slice: Option<(usize, u64)>) -> Result<(), ApplicationError> {
+    let (slice_size, slice_delay) = slice.unwrap_or((data.len().max(1), 0));
+    for (index, fragment) in data.chunks(slice_size).enumerate() {
+        if index > 0 && slice_delay > 0 {
+            tokio::time::sleep(Duration::from_millis(slice_delay)).await;
+        }
+        writer.write_all(fragment).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to write: {err}")))?;
+        writer.flush().await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to write: {err}")))?;
+    }
+    Ok(())
+}
+
+/**
+ * Configure the routes of the control api.
+ *
+ * # Arguments
+ * `config`: The service configuration.
+ */
+fn configure_control(config: &mut web::ServiceConfig) {
+    config.route("/toxics", web::get().to(list_toxics)).route("/toxics/{name}/enable", web::post().to(enable_toxic)).route("/toxics/{name}/disable", web::post().to(disable_toxic));
+}
+
+/**
+ * List the toxics.
+ *
+ * # Arguments
+ * `toxics`: The toxics of the proxy.
+ *
+ * # Returns
+ * The toxic states.
+ */
+async fn list_toxics(toxics: web::Data<Vec<Arc<Toxic>>>) -> HttpResponse {
+    HttpResponse::Ok().json(toxics.iter().map(|toxic| toxic.state()).collect::<Vec<ToxicState>>())
+}
+
+/**
+ * Enable a toxic.
+ *
+ * # Arguments
+ * `toxics`: The toxics of the proxy.
+ * `name`: The name of the toxic.
+ *
+ * # Returns
+ * The toxic state, or not found.
+ */
+async fn enable_toxic(toxics: web::Data<Vec<Arc<Toxic>>>, name: web::Path<String>) -> HttpResponse {
+    set_enabled(&toxics, &name, true)
+}
+
+/**
+ * Disable a toxic.
+ *
+ * # Arguments
+ * `toxics`: The toxics of the proxy.
+ * `name`: The name of the toxic.
+ *
+ * # Returns
+ * The toxic state, or not found.
+ */
+async fn disable_toxic(toxics: web::Data<Vec<Arc<Toxic>>>, name: web::Path<String>) -> HttpResponse {
+    set_enabled(&toxics, &name, false)
+}
+
+/**
+ * Change the state of a toxic.
+ *
+ * # Arguments
+ * `toxics`: The toxics of the proxy.
+ * `name`: The name of the toxic.
+ * `enabled`: The new state.
+ *
+ * # Returns
+ * The toxic state, or not found.
+ */
+fn set_enabled(toxics: &[Arc<Toxic>], name: &str, enabled: bool) -> HttpResponse {
+    match toxics.iter().find(|toxic| toxic.name == name) {
+        Some(toxic) => {
+            toxic.enabled.store(enabled, Ordering::Relaxed);
+            info!("Toxic {name} enabled: {enabled}");
+            HttpResponse::Ok().json(toxic.state())
+        }
+        None => HttpResponse::NotFound().body(format!("Unknown toxic {name}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+
+    use super::*;
+
+    /**
+     * Create the toxics from the configurations.
+     */
+    fn get_toxics(configurations: &[ToxicConfiguration]) -> Vec<Arc<Toxic>> {
+        configurations.iter().map(|configuration| Arc::new(Toxic::new(configuration, ToxicDirection::Downstream).unwrap())).collect()
+    }
+
+    /**
+     * Write the chunks through the pipe and return the flow and the forwarded data.
+     */
+    async fn run_pipe(toxics: &[Arc<Toxic>], chunks: &[&[u8]]) -> (Flow, Vec<u8>) {
+        let (mut source, reader) = tokio::io::duplex(1024);
+        let (writer, mut target) = tokio::io::duplex(1024);
+        for chunk in chunks {
+            source.write_all(chunk).await.unwrap();
+        }
+        drop(source);
+        let flow = pipe(reader, writer, toxics).await.unwrap();
+        let mut output = Vec::new();
+        target.read_to_end(&mut output).await.unwrap();
+        (flow, output)
+    }
+
+    /**
+     * Verify that the data is forwarded without toxics and with disabled toxics.
+     */
+    #[tokio::test]
+    async fn test_pipe() {
+        assert_eq!(run_pipe(&[], &[b"Test"]).await, (Flow::Ended, b"Test".to_vec()));
+        let mut blackhole = ToxicConfiguration::new("blackhole".to_string(), ToxicType::Blackhole);
+        blackhole.enabled = false;
+        let toxics = get_toxics(&[blackhole]);
+        assert_eq!(run_pipe(&toxics, &[b"Test"]).await, (Flow::Ended, b"Test".to_vec()));
+        toxics[0].enabled.store(true, Ordering::Relaxed);
+        assert_eq!(run_pipe(&toxics, &[b"Test"]).await, (Flow::Ended, Vec::new()));
+    }
+
+    /**
+     * Verify that limit data closes the connection and half close keeps reading after the bytes.
+     */
+    #[tokio::test]
+    async fn test_pipe_limits() {
+        let mut limit = ToxicConfiguration::new("limit".to_string(), ToxicType::LimitData);
+        limit.bytes = Some(2);
+        assert_eq!(run_pipe(&get_toxics(&[limit]), &[b"Test"]).await, (Flow::Closed, b"Te".to_vec()));
+        let mut half_close = ToxicConfiguration::new("half".to_string(), ToxicType::HalfClose);
+        half_close.bytes = Some(3);
+        assert_eq!(run_pipe(&get_toxics(&[half_close]), &[b"Test"]).await, (Flow::Ended, b"Tes".to_vec()));
+    }
+
+    /**
+     * Verify that latency, bandwidth and slicing delay the data.
+     */
+    #[tokio::test]
+    async fn test_pipe_delays() {
+        let mut latency = ToxicConfiguration::new("latency".to_string(), ToxicType::Latency);
+        latency.latency_ms = Some(100);
+        latency.jitter_ms = Some(20);
+        let mut bandwidth = ToxicConfiguration::new("bandwidth".to_string(), ToxicType::Bandwidth);
+        bandwidth.bytes_per_second = Some(100);
+        let mut slicer = ToxicConfiguration::new("slicer".to_string(), ToxicType::Slicer);
+        slicer.slice_size = Some(1);
+        slicer.slice_delay_ms = Some(10);
+        let start = std::time::Instant::now();
+        assert_eq!(run_pipe(&get_toxics(&[latency, bandwidth, slicer]), &[b"Test"]).await, (Flow::Ended, b"Test".to_vec()));
+        assert!(start.elapsed() >= Duration::from_millis(80 + 40 + 30));
+    }
+
+    /**
+     * Verify that invalid toxics are configuration errors.
+     */
+    #[test]
+    fn test_proxy_invalid() {
+        let toxic = ToxicConfiguration::new("toxic".to_string(), ToxicType::Blackhole);
+        assert!(Proxy::new(&TcpProxyConfiguration::new("localhost:1".to_string(), vec![toxic.clone()], vec![toxic], None)).is_err());
+        assert!(Proxy::new(&TcpProxyConfiguration::new("localhost:1".to_string(), vec![ToxicConfiguration::new("latency".to_string(), ToxicType::Latency)], vec![], None)).is_err());
+        assert!(Proxy::new(&TcpProxyConfiguration::new("localhost:1".to_string(), vec![ToxicConfiguration::new("bandwidth".to_string(), ToxicType::Bandwidth)], vec![], None)).is_err());
+        assert!(Proxy::new(&TcpProxyConfiguration::new("localhost:1".to_string(), vec![ToxicConfiguration::new("slicer".to_string(), ToxicType::Slicer)], vec![], None)).is_err());
+    }
+
+    /**
+     * Verify listing, enabling and disabling toxics with the control api.
+     */
+    #[actix_web::test]
+    async fn test_control() {
+        let toxics = get_toxics(&[ToxicConfiguration::new("blackhole".to_string(), ToxicType::Blackhole)]);
+        let app = init_service(App::new().app_data(web::Data::new(toxics.clone())).configure(configure_control)).await;
+        let response = call_service(&app, TestRequest::post().uri("/toxics/blackhole/disable").to_request()).await;
+        assert!(response.status().is_success());
+        assert!(!toxics[0].is_enabled());
+        let response = call_service(&app, TestRequest::get().uri("/toxics").to_request()).await;
+        let body = read_body(response).await;
+        assert_eq!(body, r#"[{"name":"blackhole","direction":"Downstream","toxicType":"Blackhole","enabled":false}]"#);
+        let response = call_service(&app, TestRequest::post().uri("/toxics/blackhole/enable").to_request()).await;
+        assert!(response.status().is_success());
+        assert!(toxics[0].is_enabled());
+        let response = call_service(&app, TestRequest::post().uri("/toxics/unknown/enable").to_request()).await;
+        assert_eq!(response.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+}
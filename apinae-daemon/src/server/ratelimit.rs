@@ -0,0 +1,353 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    http::header::{HeaderName, HeaderValue, RETRY_AFTER},
+    HttpRequest, HttpResponse,
+};
+use apinae_lib::{
+    config::{RateLimitAlgorithm, RateLimitConfiguration, RateLimitKey},
+    error::ApplicationError,
+};
+
+/**
+ * Header with the number of requests allowed per period.
+ */
+const RATE_LIMIT_LIMIT_HEADER: &str = "x-ratelimit-limit";
+/**
+ * Header with the number of requests remaining in the current period.
+ */
+const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+/**
+ * Header with the number of seconds until the limit is reset.
+ */
+const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+/**
+ * The status code returned when the limit is exceeded, if not configured.
+ */
+const DEFAULT_RATE_LIMIT_STATUS: &str = "429";
+
+/**
+ * The result of a rate limit check.
+ */
+#[derive(Debug, PartialEq)]
+pub struct RateLimitDecision {
+    // True if the request is allowed.
+    pub allowed: bool,
+    // Number of requests allowed per period.
+    pub limit: u64,
+    // Number of requests remaining.
+    pub remaining: u64,
+    // Time until the limit is reset.
+    pub reset: Duration,
+    // Time until a new request is allowed.
+    pub retry_after: Duration,
+}
+
+impl RateLimitDecision {
+    /**
+     * Add the rate limit headers to the response. Headers already added by a more specific limit are kept.
+     *
+     * # Arguments
+     * `response`: The response.
+     */
+    pub fn add_headers(&self, response: &mut HttpResponse) {
+        let headers = response.headers_mut();
+        if headers.contains_key(RATE_LIMIT_LIMIT_HEADER) {
+            return;
+        }
+        headers.insert(HeaderName::from_static(RATE_LIMIT_LIMIT_HEADER), HeaderValue::from(self.limit));
+        headers.insert(HeaderName::from_static(RATE_LIMIT_REMAINING_HEADER), HeaderValue::from(self.remaining));
+        headers.insert(HeaderName::from_static(RATE_LIMIT_RESET_HEADER), HeaderValue::from(ceil_seconds(self.reset)));
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(ceil_seconds(self.retry_after)));
+        }
+    }
+}
+
+/**
+ * The state counted for one key.
+ */
+enum RateLimitState {
+    // Tokens available and when they were last refilled.
+    TokenBucket { tokens: f64, refilled: Instant },
+    // Requests in the window and when the window started.
+    FixedWindow { count: u64, started: Instant },
+}
+
+impl RateLimitState {
+    /**
+     * Check if the state is expired. An expired state is the same as a new state, so it can be removed.
+     *
+     * # Arguments
+     * `now`: The current time.
+     * `period`: The period of the limit.
+     *
+     * # Returns
+     * True if the bucket is refilled or the window is over.
+     */
+    fn is_expired(&self, now: Instant, period: Duration) -> bool {
+        match self {
+            RateLimitState::TokenBucket { refilled, .. } => now.duration_since(*refilled) >= period,
+            RateLimitState::FixedWindow { started, .. } => now.duration_since(*started) >= period,
+        }
+    }
+}
+
+/**
+ * The states of the keys.
+ */
+struct KeyStates {
+    // The state for each key.
+    states: HashMap<String, RateLimitState>,
+    // When the expired states were last removed.
+    pruned: Instant,
+}
+
+/**
+ * Rate limiter for a server or an endpoint.
+ */
+pub struct RateLimiter {
+    // The rate limit configuration.
+    configuration: RateLimitConfiguration,
+    // The state for each key.
+    state: Mutex<KeyStates>,
+}
+
+impl RateLimiter {
+    /**
+     * Create a new rate limiter.
+     *
+     * # Arguments
+     * `configuration`: The rate limit configuration.
+     *
+     * # Returns
+     * The rate limiter.
+     */
+    pub fn new(configuration: RateLimitConfiguration) -> Self {
+        RateLimiter { configuration, state: Mutex::new(KeyStates { states: HashMap::new(), pruned: Instant::now() }) }
+    }
+
+    /**
+     * Get the rate limit configuration.
+     *
+     * # Returns
+     * The rate limit configuration.
+     */
+    pub fn configuration(&self) -> &RateLimitConfiguration {
+        &self.configuration
+    }
+
+    /**
+     * Check the request against the limit. The request is counted if it is allowed.
+     *
+     * # Arguments
+     * `req`: The request.
+     *
+     * # Returns
+     * The decision.
+     *
+     * # Errors
+     * An error if the state is poisoned.
+     */
+    pub fn check(&self, req: &HttpRequest) -> Result<RateLimitDecision, ApplicationError> {
+        self.check_key(self.get_key(req), Instant::now())
+    }
+
+    /**
+     * Get the status code returned when the limit is exceeded.
+     *
+     * # Returns
+     * The status code.
+     */
+    pub fn status(&self) -> String {
+        self.configuration.status.clone().unwrap_or_else(|| DEFAULT_RATE_LIMIT_STATUS.to_string())
+    }
+
+    /**
+     * Get the key the request is counted by.
+     *
+     * # Arguments
+     * `req`: The request.
+     *
+     * # Returns
+     * The key.
+     */
+    fn get_key(&self, req: &HttpRequest) -> String {
+        match self.configuration.key {
+            RateLimitKey::Global => String::new(),
+            RateLimitKey::Ip => req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default(),
+            RateLimitKey::Header => self.configuration.key_header.as_ref().and_then(|header| req.headers().get(header)).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string(),
+        }
+    }
+
+    /**
+     * Check the key against the limit at the specified time. The expired states are removed at most once per period when a new key is added,
+     * so keys like ip addresses or header values do not fill the memory.
+     *
+     * # Arguments
+     * `key`: The key the request is counted by.
+     * `now`: The current time.
+     *
+     * # Returns
+     * The decision.
+     *
+     * # Errors
+     * An error if the state is poisoned.
+     */
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn check_key(&self, key: String, now: Instant) -> Result<RateLimitDecision, ApplicationError> {
+        let mut state = self.state.lock().map_err(|err| ApplicationError::ConfigurationError(format!("Failed to lock rate limit state: {err}")))?;
+        let limit = self.configuration.limit;
+        let period = Duration::from_millis(self.configuration.period_ms);
+        if !state.states.contains_key(&key) && now.duration_since(state.pruned) >= period {
+            state.states.retain(|_, state| !state.is_expired(now, period));
+            state.pruned = now;
+        }
+        let state = state.states.entry(key).or_insert_with(|| match self.configuration.algorithm {
+            RateLimitAlgorithm::TokenBucket => RateLimitState::TokenBucket { tokens: limit as f64, refilled: now },
+            RateLimitAlgorithm::FixedWindow => RateLimitState::FixedWindow { count: 0, started: now },
+        });
+        let decision = match state {
+            // Without tokens the bucket is never refilled, so every request is denied for the period.
+            RateLimitState::TokenBucket { .. } if limit == 0 => RateLimitDecision { allowed: false, limit, remaining: 0, reset: period, retry_after: period },
+            RateLimitState::TokenBucket { tokens, refilled } => {
+                let tokens_per_second = if period.is_zero() { f64::MAX } else { limit as f64 / period.as_secs_f64() };
+                *tokens = (*tokens + now.duration_since(*refilled).as_secs_f64() * tokens_per_second).min(limit as f64);
+                *refilled = now;
+                let allowed = *tokens >= 1.0;
+                if allowed {
+                    *tokens -= 1.0;
+                }
+                let retry_after = if allowed { Duration::ZERO } else { Duration::from_secs_f64((1.0 - *tokens) / tokens_per_second) };
+                let reset = Duration::from_secs_f64((limit as f64 - *tokens) / tokens_per_second);
+                RateLimitDecision { allowed, limit, remaining: tokens.floor() as u64, reset, retry_after }
+            }
+            RateLimitState::FixedWindow { count, started } => {
+                if now.duration_since(*started) >= period {
+                    *count = 0;
+                    *started = now;
+                }
+                let allowed = *count < limit;
+                if allowed {
+                    *count += 1;
+                }
+                let reset = period.saturating_sub(now.duration_since(*started));
+                RateLimitDecision { allowed, limit, remaining: limit - *count, reset, retry_after: if allowed { Duration::ZERO } else { reset } }
+            }
+        };
+        Ok(decision)
+    }
+}
+
+/**
+ * Round the duration up to whole seconds.
+ *
+ * # Arguments
+ * `duration`: The duration.
+ *
+ * # Returns
+ * The number of seconds.
+ */
+fn ceil_seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod test {
+
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    /**
+     * Verify that the fixed window allows limit requests per window.
+     */
+    #[test]
+    fn test_fixed_window() {
+        let rate_limiter = RateLimiter::new(RateLimitConfiguration::new(RateLimitAlgorithm::FixedWindow, 2, 1000, RateLimitKey::Global, None));
+        let now = Instant::now();
+        assert!(rate_limiter.check_key(String::new(), now).unwrap().allowed);
+        assert_eq!(rate_limiter.check_key(String::new(), now).unwrap().remaining, 0);
+        let decision = rate_limiter.check_key(String::new(), now + Duration::from_millis(400)).unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Duration::from_millis(600));
+        assert!(rate_limiter.check_key(String::new(), now + Duration::from_millis(1000)).unwrap().allowed);
+    }
+
+    /**
+     * Verify that the token bucket is refilled over time.
+     */
+    #[test]
+    fn test_token_bucket() {
+        let rate_limiter = RateLimiter::new(RateLimitConfiguration::new(RateLimitAlgorithm::TokenBucket, 2, 1000, RateLimitKey::Global, None));
+        let now = Instant::now();
+        assert!(rate_limiter.check_key(String::new(), now).unwrap().allowed);
+        assert!(rate_limiter.check_key(String::new(), now).unwrap().allowed);
+        let decision = rate_limiter.check_key(String::new(), now).unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Duration::from_millis(500));
+        assert!(rate_limiter.check_key(String::new(), now + Duration::from_millis(500)).unwrap().allowed);
+    }
+
+    /**
+     * Verify that a limit of 0 denies every request.
+     */
+    #[test]
+    fn test_zero_limit() {
+        let now = Instant::now();
+        for algorithm in [RateLimitAlgorithm::TokenBucket, RateLimitAlgorithm::FixedWindow] {
+            let rate_limiter = RateLimiter::new(RateLimitConfiguration::new(algorithm, 0, 1000, RateLimitKey::Global, None));
+            let decision = rate_limiter.check_key(String::new(), now).unwrap();
+            assert!(!decision.allowed);
+            assert_eq!(decision.remaining, 0);
+            assert_eq!(decision.retry_after, Duration::from_millis(1000));
+            assert_eq!(decision.reset, Duration::from_millis(1000));
+        }
+    }
+
+    /**
+     * Verify that expired states are removed when a new key is added.
+     */
+    #[test]
+    fn test_prune() {
+        let rate_limiter = RateLimiter::new(RateLimitConfiguration::new(RateLimitAlgorithm::FixedWindow, 1, 1000, RateLimitKey::Ip, None));
+        let now = Instant::now();
+        for key in ["a", "b", "c"] {
+            assert!(rate_limiter.check_key(key.to_string(), now).unwrap().allowed);
+        }
+        assert!(!rate_limiter.check_key("a".to_string(), now + Duration::from_millis(500)).unwrap().allowed);
+        assert!(rate_limiter.check_key("d".to_string(), now + Duration::from_millis(1000)).unwrap().allowed);
+        assert_eq!(rate_limiter.state.lock().unwrap().states.len(), 1);
+    }
+
+    /**
+     * Verify that the limit is counted per header value.
+     */
+    #[test]
+    fn test_header_key() {
+        let rate_limiter = RateLimiter::new(RateLimitConfiguration::new(RateLimitAlgorithm::FixedWindow, 1, 60000, RateLimitKey::Header, Some("X-Client".to_string())));
+        let first = TestRequest::default().insert_header(("X-Client", "a")).to_http_request();
+        let second = TestRequest::default().insert_header(("X-Client", "b")).to_http_request();
+        assert!(rate_limiter.check(&first).unwrap().allowed);
+        assert!(rate_limiter.check(&second).unwrap().allowed);
+        assert!(!rate_limiter.check(&first).unwrap().allowed);
+    }
+
+    /**
+     * Verify the rate limit headers.
+     */
+    #[test]
+    fn test_add_headers() {
+        let decision = RateLimitDecision { allowed: false, limit: 10, remaining: 0, reset: Duration::from_millis(1500), retry_after: Duration::from_millis(200) };
+        let mut response = HttpResponse::TooManyRequests().finish();
+        decision.add_headers(&mut response);
+        assert_eq!(response.headers().get(RATE_LIMIT_LIMIT_HEADER).unwrap(), "10");
+        assert_eq!(response.headers().get(RATE_LIMIT_REMAINING_HEADER).unwrap(), "0");
+        assert_eq!(response.headers().get(RATE_LIMIT_RESET_HEADER).unwrap(), "2");
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+    }
+}
@@ -0,0 +1,198 @@
+use apinae_lib::{
+    config::{HeaderRulesConfiguration, PathRewriteConfiguration, PathRewriteType},
+    error::ApplicationError,
+};
+use regex::Regex;
+
+use super::http::convert_params;
+
+/**
+ * A compiled path rewrite rule.
+ */
+#[derive(Debug)]
+pub enum PathRewrite {
+    // Strip the prefix.
+    StripPrefix(String),
+    // Replace the prefix with the replacement.
+    ReplacePrefix(String, String),
+    // Replace the matches of the expression with the replacement.
+    Regex(Regex, String),
+}
+
+/**
+ * Compile the path rewrite rules.
+ *
+ * # Arguments
+ * `rules`: The path rewrite rules.
+ *
+ * # Returns
+ * The compiled rules in the same order.
+ *
+ * # Errors
+ * An error if a regular expression is invalid.
+ */
+pub fn compile_path_rewrites(rules: &[PathRewriteConfiguration]) -> Result<Vec<PathRewrite>, ApplicationError> {
+    rules
+        .iter()
+        .map(|rule| {
+            let replacement = rule.replacement.clone().unwrap_or_default();
+            Ok(match rule.rewrite_type {
+                PathRewriteType::StripPrefix => PathRewrite::StripPrefix(rule.pattern.clone()),
+                PathRewriteType::ReplacePrefix => PathRewrite::ReplacePrefix(rule.pattern.clone(), replacement),
+                PathRewriteType::Regex => {
+                    let regex = Regex::new(&rule.pattern).map_err(|err| ApplicationError::ConfigurationError(format!("Invalid path rewrite expression {}: {err}", rule.pattern)))?;
+                    PathRewrite::Regex(regex, replacement)
+                }
+            })
+        })
+        .collect()
+}
+
+/**
+ * Rewrite the path with the rules in order. Parameters are substituted in the replacements.
+ *
+ * # Arguments
+ * `path`: The request path.
+ * `rules`: The compiled path rewrite rules.
+ * `params`: The parameters.
+ *
+ * # Returns
+ * The rewritten path.
+ */
+pub fn rewrite_path(path: &str, rules: &[PathRewrite], params: &Vec<(String, String)>) -> String {
+    let mut path = path.to_string();
+    for rule in rules {
+        path = match rule {
+            PathRewrite::StripPrefix(prefix) => strip_path_prefix(&path, prefix).map_or(path.clone(), ToString::to_string),
+            PathRewrite::ReplacePrefix(prefix, replacement) => strip_path_prefix(&path, prefix).map_or(path.clone(), |rest| format!("{}{rest}", convert_params(replacement, params))),
+            PathRewrite::Regex(regex, replacement) => regex.replace_all(&path, convert_params(replacement, params).as_str()).to_string(),
+        };
+    }
+    if !path.starts_with('/') {
+        path.insert(0, '/');
+    }
+    log::debug!("Rewritten path: {path}");
+    path
+}
+
+/**
+ * Strip the prefix if it ends at a path segment boundary, so `/api` is stripped from `/api/v1` but not from `/apiv2`.
+ *
+ * # Arguments
+ * `path`: The path.
+ * `prefix`: The prefix.
+ *
+ * # Returns
+ * The rest of the path, or None if the path does not start with the prefix.
+ */
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    path.strip_prefix(prefix).filter(|rest| rest.is_empty() || rest.starts_with(['/', '?']) || prefix.ends_with('/'))
+}
+
+/**
+ * Apply the header rules to the headers. Headers are removed first, then added and finally set.
+ * Header names are compared case insensitive.
+ *
+ * # Arguments
+ * `headers`: The headers as name and value.
+ * `rules`: The header rules.
+ * `params`: The parameters.
+ *
+ * # Returns
+ * The changed headers.
+ */
+pub fn apply_header_rules(mut headers: Vec<(String, String)>, rules: Option<&HeaderRulesConfiguration>, params: &Vec<(String, String)>) -> Vec<(String, String)> {
+    let Some(rules) = rules else {
+        return headers;
+    };
+    headers.retain(|(name, _)| !rules.remove.iter().any(|remove| remove.eq_ignore_ascii_case(name)));
+    for (name, value) in &rules.add {
+        headers.push((name.clone(), convert_params(value, params)));
+    }
+    for (name, value) in &rules.set {
+        headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        headers.push((name.clone(), convert_params(value, params)));
+    }
+    headers
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /**
+     * Compile a single path rewrite rule.
+     */
+    fn compile(rewrite_type: PathRewriteType, pattern: &str, replacement: Option<&str>) -> Vec<PathRewrite> {
+        compile_path_rewrites(&[PathRewriteConfiguration::new(rewrite_type, pattern.to_string(), replacement.map(ToString::to_string))]).unwrap()
+    }
+
+    /**
+     * Verify the path rewrite rules.
+     */
+    #[test]
+    fn test_rewrite_path() {
+        let params = vec![("version".to_string(), "v2".to_string())];
+        let strip = compile(PathRewriteType::StripPrefix, "/api", None);
+        assert_eq!(rewrite_path("/api/v1/users", &strip, &params), "/v1/users");
+        assert_eq!(rewrite_path("/other", &strip, &params), "/other");
+        assert_eq!(rewrite_path("/api", &strip, &params), "/");
+        let replace = compile(PathRewriteType::ReplacePrefix, "/api/v1", Some("/${version}"));
+        assert_eq!(rewrite_path("/api/v1/users", &replace, &params), "/v2/users");
+        let regex = compile(PathRewriteType::Regex, "^/users/(\\d+)$", Some("/accounts/$1/profile"));
+        assert_eq!(rewrite_path("/users/42", &regex, &params), "/accounts/42/profile");
+    }
+
+    /**
+     * Verify that prefixes are only rewritten at a path segment boundary.
+     */
+    #[test]
+    fn test_rewrite_path_segment_boundary() {
+        let strip = compile(PathRewriteType::StripPrefix, "/api", None);
+        assert_eq!(rewrite_path("/apiv2/x", &strip, &Vec::new()), "/apiv2/x");
+        assert_eq!(rewrite_path("/api?debug=true", &strip, &Vec::new()), "/?debug=true");
+        let replace = compile(PathRewriteType::ReplacePrefix, "/api", Some("/v2"));
+        assert_eq!(rewrite_path("/apiv2/x", &replace, &Vec::new()), "/apiv2/x");
+        assert_eq!(rewrite_path("/api/x", &replace, &Vec::new()), "/v2/x");
+        let strip_slash = compile(PathRewriteType::StripPrefix, "/api/", None);
+        assert_eq!(rewrite_path("/api/x", &strip_slash, &Vec::new()), "/x");
+    }
+
+    /**
+     * Verify that an invalid expression is an error.
+     */
+    #[test]
+    fn test_compile_path_rewrites_invalid_regex() {
+        let regex = PathRewriteConfiguration::new(PathRewriteType::Regex, "(".to_string(), None);
+        assert!(compile_path_rewrites(&[regex]).is_err());
+    }
+
+    /**
+     * Verify the header rules.
+     */
+    #[test]
+    fn test_apply_header_rules() {
+        let params = vec![("token".to_string(), "secret".to_string())];
+        let headers = vec![("host".to_string(), "localhost".to_string()), ("accept".to_string(), "text/plain".to_string()), ("x-trace".to_string(), "1".to_string())];
+        let rules = HeaderRulesConfiguration::new(
+            vec!["X-Trace".to_string()],
+            HashMap::from([("Accept".to_string(), "application/json".to_string())]),
+            HashMap::from([("Authorization".to_string(), "Bearer ${token}".to_string()), ("HOST".to_string(), "upstream".to_string())]),
+        );
+        let mut result = apply_header_rules(headers.clone(), Some(&rules), &params);
+        result.sort();
+        assert_eq!(
+            result,
+            vec![
+                ("Accept".to_string(), "application/json".to_string()),
+                ("Authorization".to_string(), "Bearer secret".to_string()),
+                ("HOST".to_string(), "upstream".to_string()),
+                ("accept".to_string(), "text/plain".to_string()),
+            ]
+        );
+        assert_eq!(apply_header_rules(headers.clone(), None, &params), headers);
+    }
+}
@@ -0,0 +1,125 @@
+use apinae_lib::{
+    config::{TcpResponseConfiguration, TcpRuleConfiguration, UdpRuleConfiguration},
+    error::ApplicationError,
+};
+
+use super::payload::{decode_hex, get_payload, get_regex, Matcher, Payload};
+
+/**
+ * A compiled tcp rule.
+ */
+#[derive(Debug)]
+pub struct Rule {
+    // The matchers. All must match.
+    matchers: Vec<Matcher>,
+    // The response. Nothing is sent if not set.
+    pub response: Option<Payload>,
+    // Close the connection after the response.
+    pub close: bool,
+}
+
+/**
+ * Compile the rules.
+ *
+ * # Arguments
+ * `rules`: The rule configurations.
+ * `params`: The parameters replaced in the response data.
+ *
+ * # Returns
+ * The compiled rules.
+ *
+ * # Errors
+ * An error if a regular expression or hex data is invalid.
+ */
+pub fn compile_rules(rules: &[TcpRuleConfiguration], params: &Vec<(String, String)>) -> Result<Vec<Rule>, ApplicationError> {
+    rules.iter().map(|rule| compile_rule(rule.regex.as_ref(), rule.hex.as_ref(), rule.response.as_ref(), rule.close, params)).collect()
+}
+
+/**
+ * Compile the udp rules. The rules never close.
+ *
+ * # Arguments
+ * `rules`: The udp rule configurations.
+ * `params`: The parameters replaced in the response data.
+ *
+ * # Returns
+ * The compiled rules.
+ *
+ * # Errors
+ * An error if a regular expression or hex data is invalid.
+ */
+pub fn compile_udp_rules(rules: &[UdpRuleConfiguration], params: &Vec<(String, String)>) -> Result<Vec<Rule>, ApplicationError> {
+    rules.iter().map(|rule| compile_rule(rule.regex.as_ref(), rule.hex.as_ref(), rule.response.as_ref(), false, params)).collect()
+}
+
+/**
+ * Compile a rule.
+ *
+ * # Arguments
+ * `regex`: Regular expression the message must match.
+ * `hex`: Bytes as hex the message must contain.
+ * `response`: The response.
+ * `close`: Close the connection after the response.
+ * `params`: The parameters replaced in the response data.
+ *
+ * # Returns
+ * The compiled rule.
+ *
+ * # Errors
+ * An error if the regular expression or hex data is invalid.
+ */
+fn compile_rule(regex: Option<&String>, hex: Option<&String>, response: Option<&TcpResponseConfiguration>, close: bool, params: &Vec<(String, String)>) -> Result<Rule, ApplicationError> {
+    let mut matchers = Vec::new();
+    if let Some(regex) = regex {
+        matchers.push(Matcher::Regex(get_regex(regex)?));
+    }
+    if let Some(hex) = hex {
+        matchers.push(Matcher::Bytes(decode_hex(hex)?));
+    }
+    let response = match response {
+        Some(response) => get_payload(response.data.as_ref(), response.hex.as_ref(), response.file.as_ref(), params)?,
+        None => None,
+    };
+    Ok(Rule { matchers, response, close })
+}
+
+/**
+ * Find the first rule matching the message.
+ *
+ * # Arguments
+ * `rules`: The compiled rules.
+ * `message`: The message.
+ *
+ * # Returns
+ * The matching rule, if any.
+ */
+pub fn find_rule<'a>(rules: &'a [Rule], message: &[u8]) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.matchers.iter().all(|matcher| matcher.find(message).is_some()))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /**
+     * Verify that the first matching rule is found.
+     */
+    #[test]
+    fn test_find_rule() {
+        let rules = compile_rules(
+            &[
+                TcpRuleConfiguration::new(Some("^QUIT".to_string()), None, Some(TcpResponseConfiguration::new(Some("BYE".to_string()), None, None)), true),
+                TcpRuleConfiguration::new(None, Some("02 01".to_string()), Some(TcpResponseConfiguration::new(None, Some("06".to_string()), None)), false),
+                TcpRuleConfiguration::new(None, None, None, false),
+            ],
+            &Vec::new(),
+        )
+        .unwrap();
+        assert!(find_rule(&rules, b"QUIT").is_some_and(|rule| rule.close));
+        assert!(matches!(find_rule(&rules, b"\x02\x01\x03").and_then(|rule| rule.response.as_ref()), Some(Payload::Bytes(bytes)) if bytes == &vec![0x06]));
+        assert!(find_rule(&rules, b"OTHER").is_some_and(|rule| rule.response.is_none()));
+        assert!(find_rule(&rules[..2], b"OTHER").is_none());
+        assert!(compile_rules(&[TcpRuleConfiguration::new(Some("(".to_string()), None, None, false)], &Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use std::time::Duration;
+
+use apinae_lib::{
+    config::{TcpScriptConfiguration, TcpScriptStep, TcpScriptStepType},
+    error::ApplicationError,
+};
+use log::info;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite},
+    time::Instant,
+};
+
+use super::{
+    http::convert_params,
+    payload::{decode_hex, get_payload, get_regex, log_received, send, Matcher, Payload},
+    tcp::READ_BUFFER_SIZE,
+};
+
+/**
+ * A conversation script with the patterns compiled and the hex data decoded.
+ */
+#[derive(Debug)]
+pub struct Script {
+    // The steps of the conversation.
+    steps: Vec<Step>,
+    // The response to unexpected input.
+    fallback: Option<Fallback>,
+}
+
+/**
+ * A compiled script step.
+ */
+#[derive(Debug)]
+enum Step {
+    // Read until the input matches.
+    Expect(Matcher, Option<Duration>),
+    // Send the payload.
+    Send(Payload),
+    // Wait the duration.
+    Wait(Duration),
+    // Close the connection.
+    Close,
+    // Continue from the step index.
+    Loop(usize),
+}
+
+/**
+ * The compiled fallback.
+ */
+#[derive(Debug)]
+struct Fallback {
+    // The response. Nothing is sent if not set.
+    payload: Option<Payload>,
+    // Close the connection after the response.
+    close: bool,
+}
+
+/**
+ * The result of an expect step.
+ */
+#[derive(Debug, PartialEq)]
+enum Expectation {
+    // The input matched. The matched input is consumed.
+    Matched,
+    // The input did not match.
+    Unexpected,
+    // The client closed the connection.
+    Closed,
+}
+
+impl Script {
+    /**
+     * Compile the script configuration.
+     *
+     * # Arguments
+     * `configuration`: The script configuration.
+     * `params`: The parameters replaced in the data.
+     *
+     * # Returns
+     * The compiled script.
+     *
+     * # Errors
+     * An error if a step is missing the fields used by its type.
+     * An error if a regular expression or hex data is invalid.
+     * An error if a loop target is not a step.
+     * An error if loops jump to each other without another step in between.
+     */
+    pub fn new(configuration: &TcpScriptConfiguration, params: &Vec<(String, String)>) -> Result<Self, ApplicationError> {
+        let steps = configuration.steps.iter().map(|step| Self::compile_step(step, configuration.steps.len(), params)).collect::<Result<Vec<Step>, ApplicationError>>()?;
+        check_loops(&steps)?;
+        let fallback = match &configuration.fallback {
+            Some(fallback) => Some(Fallback { payload: get_payload(fallback.data.as_ref(), fallback.hex.as_ref(), fallback.file.as_ref(), params)?, close: fallback.close }),
+            None => None,
+        };
+        Ok(Script { steps, fallback })
+    }
+
+    /**
+     * Run the conversation. It ends when the steps run out, a close step is reached or the client closes the connection.
+     *
+     * # Arguments
+     * `stream`: The stream.
+     *
+     * # Returns
+     * Ok if the conversation ended.
+     *
+     * # Errors
+     * An error if the stream could not be read or written, or a file could not be read.
+     */
+    pub async fn run<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut S) -> Result<(), ApplicationError> {
+        let mut input = Vec::new();
+        let mut index = 0;
+        while let Some(step) = self.steps.get(index) {
+            index += 1;
+            match step {
+                Step::Expect(matcher, timeout) => match expect(stream, &mut input, matcher, *timeout).await? {
+                    Expectation::Matched => {}
+                    Expectation::Closed => return Ok(()),
+                    Expectation::Unexpected => {
+                        info!("Unexpected input: {:?}", String::from_utf8_lossy(&input));
+                        input.clear();
+                        let Some(fallback) = &self.fallback else {
+                            return Ok(());
+                        };
+                        if let Some(payload) = &fallback.payload {
+                            send(stream, payload).await?;
+                        }
+                        if fallback.close {
+                            return Ok(());
+                        }
+                        index -= 1;
+                    }
+                },
+                Step::Send(payload) => send(stream, payload).await?,
+                Step::Wait(duration) => tokio::time::sleep(*duration).await,
+                Step::Close => return Ok(()),
+                Step::Loop(target) => index = *target,
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Compile a step.
+     *
+     * # Arguments
+     * `step`: The step configuration.
+     * `length`: The number of steps in the script.
+     * `params`: The parameters replaced in the data.
+     *
+     * # Returns
+     * The compiled step.
+     *
+     * # Errors
+     * An error if the step is invalid.
+     */
+    fn compile_step(step: &TcpScriptStep, length: usize, params: &Vec<(String, String)>) -> Result<Step, ApplicationError> {
+        match step.step_type {
+            TcpScriptStepType::Expect => {
+                let matcher = if let Some(regex) = &step.regex {
+                    Matcher::Regex(get_regex(regex)?)
+                } else if let Some(data) = &step.data {
+                    Matcher::Bytes(convert_params(data, params).into_bytes())
+                } else if let Some(hex) = &step.hex {
+                    Matcher::Bytes(decode_hex(hex)?)
+                } else {
+                    return Err(ApplicationError::ConfigurationError("Expect step requires regex, data or hex".to_string()));
+                };
+                Ok(Step::Expect(matcher, step.timeout_ms.map(Duration::from_millis)))
+            }
+            TcpScriptStepType::Send => get_payload(step.data.as_ref(), step.hex.as_ref(), step.file.as_ref(), params)?
+                .map(Step::Send)
+                .ok_or_else(|| ApplicationError::ConfigurationError("Send step requires data, hex or file".to_string())),
+            TcpScriptStepType::Wait => {
+                step.delay_ms.map(|delay_ms| Step::Wait(Duration::from_millis(delay_ms))).ok_or_else(|| ApplicationError::ConfigurationError("Wait step requires delayMs".to_string()))
+            }
+            TcpScriptStepType::Close => Ok(Step::Close),
+            TcpScriptStepType::Loop => {
+                let target = step.target.unwrap_or_default();
+                if target >= length {
+                    return Err(ApplicationError::ConfigurationError(format!("Loop target {target} is not a step")));
+                }
+                Ok(Step::Loop(target))
+            }
+        }
+    }
+}
+
+/**
+ * Check that every loop reaches a step that is not a loop. Loops that only jump to each other would never wait and block the thread.
+ *
+ * # Arguments
+ * `steps`: The compiled steps.
+ *
+ * # Errors
+ * An error if the jumps of a loop come back to a loop without another step in between.
+ */
+fn check_loops(steps: &[Step]) -> Result<(), ApplicationError> {
+    for start in 0..steps.len() {
+        let mut visited = Vec::new();
+        let mut index = start;
+        while let Some(Step::Loop(target)) = steps.get(index) {
+            if visited.contains(&index) {
+                return Err(ApplicationError::ConfigurationError(format!("Loop at step {start} never reaches a step that is not a loop")));
+            }
+            visited.push(index);
+            index = *target;
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Read until the input matches. With a timeout, input is collected until it matches or the timeout elapses.
+ * Without a timeout, the input must match after each read.
+ *
+ * # Arguments
+ * `stream`: The stream.
+ * `input`: The input not consumed by earlier steps.
+ * `matcher`: The matcher.
+ * `timeout`: The time to wait for matching input.
+ *
+ * # Returns
+ * The result of the expectation.
+ *
+ * # Errors
+ * An error if the stream could not be read.
+ */
+async fn expect<S: AsyncRead + Unpin>(stream: &mut S, input: &mut Vec<u8>, matcher: &Matcher, timeout: Option<Duration>) -> Result<Expectation, ApplicationError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        if let Some(end) = matcher.find(input) {
+            input.drain(..end);
+            return Ok(Expectation::Matched);
+        }
+        let read = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, stream.read(&mut buffer)).await {
+                Ok(read) => read,
+                Err(_) => return Ok(Expectation::Unexpected),
+            },
+            None if !input.is_empty() => return Ok(Expectation::Unexpected),
+            None => stream.read(&mut buffer).await,
+        }
+        .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to read: {err}")))?;
+        if read == 0 {
+            return Ok(Expectation::Closed);
+        }
+        log_received(&buffer[..read]);
+        input.extend_from_slice(&buffer[..read]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use apinae_lib::config::TcpScriptFallback;
+    use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+    use super::*;
+
+    /**
+     * Create a step with data.
+     */
+    fn step(step_type: TcpScriptStepType, data: Option<&str>) -> TcpScriptStep {
+        let mut step = TcpScriptStep::new(step_type);
+        step.data = data.map(ToString::to_string);
+        step
+    }
+
+    /**
+     * Run the script on one end of a duplex stream and return the other end.
+     */
+    fn start(configuration: &TcpScriptConfiguration) -> DuplexStream {
+        let script = Script::new(configuration, &Vec::new()).unwrap();
+        let (mut server, client) = duplex(1024);
+        tokio::spawn(async move {
+            let _ = script.run(&mut server).await;
+            let _ = server.shutdown().await;
+        });
+        client
+    }
+
+    /**
+     * Read everything until the script closes the connection.
+     */
+    async fn read_all(client: &mut DuplexStream) -> String {
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        output
+    }
+
+    /**
+     * Verify a greeting followed by a looped command and response.
+     */
+    #[tokio::test]
+    async fn test_run_script() {
+        let mut expect = step(TcpScriptStepType::Expect, None);
+        expect.regex = Some("^PING\\r?\\n".to_string());
+        let mut hex = TcpScriptStep::new(TcpScriptStepType::Send);
+        hex.hex = Some("50 4f 4e 47 0a".to_string());
+        let mut repeat = TcpScriptStep::new(TcpScriptStepType::Loop);
+        repeat.target = Some(1);
+        let configuration = TcpScriptConfiguration::new(vec![step(TcpScriptStepType::Send, Some("HELLO\n")), expect, hex, repeat], None);
+        let mut client = start(&configuration);
+        client.write_all(b"PING\n").await.unwrap();
+        client.write_all(b"PING\r\n").await.unwrap();
+        client.write_all(b"QUIT\n").await.unwrap();
+        assert_eq!(read_all(&mut client).await, "HELLO\nPONG\nPONG\n");
+    }
+
+    /**
+     * Verify that input split over several reads is collected when a timeout is set, and that the fallback is used when the timeout elapses.
+     */
+    #[tokio::test]
+    async fn test_run_script_timeout() {
+        let mut expect = step(TcpScriptStepType::Expect, Some("END"));
+        expect.timeout_ms = Some(200);
+        let configuration = TcpScriptConfiguration::new(
+            vec![expect.clone(), step(TcpScriptStepType::Send, Some("OK\n")), expect, step(TcpScriptStepType::Send, Some("NEVER\n"))],
+            Some(TcpScriptFallback::new(Some("TIMEOUT\n".to_string()), None, None, true)),
+        );
+        let mut client = start(&configuration);
+        client.write_all(b"E").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.write_all(b"ND").await.unwrap();
+        assert_eq!(read_all(&mut client).await, "OK\nTIMEOUT\n");
+    }
+
+    /**
+     * Verify that a fallback without close discards the unexpected input and retries the step.
+     */
+    #[tokio::test]
+    async fn test_run_script_fallback_retry() {
+        let configuration = TcpScriptConfiguration::new(
+            vec![
+                step(TcpScriptStepType::Expect, Some("LOGIN")),
+                step(TcpScriptStepType::Send, Some("WELCOME\n")),
+                step(TcpScriptStepType::Close, None),
+                step(TcpScriptStepType::Send, Some("NEVER\n")),
+            ],
+            Some(TcpScriptFallback::new(Some("ERR\n".to_string()), None, None, false)),
+        );
+        let mut client = start(&configuration);
+        client.write_all(b"HELP").await.unwrap();
+        let mut response = [0; 4];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"ERR\n");
+        client.write_all(b"LOGIN").await.unwrap();
+        assert_eq!(read_all(&mut client).await, "WELCOME\n");
+    }
+
+    /**
+     * Verify that invalid steps are configuration errors.
+     */
+    #[test]
+    fn test_script_invalid() {
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![step(TcpScriptStepType::Expect, None)], None), &Vec::new()).is_err());
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![step(TcpScriptStepType::Send, None)], None), &Vec::new()).is_err());
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![step(TcpScriptStepType::Wait, None)], None), &Vec::new()).is_err());
+        let mut repeat = TcpScriptStep::new(TcpScriptStepType::Loop);
+        repeat.target = Some(1);
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![repeat], None), &Vec::new()).is_err());
+        let mut first = TcpScriptStep::new(TcpScriptStepType::Loop);
+        first.target = Some(0);
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![first], None), &Vec::new()).is_err());
+        let mut first = TcpScriptStep::new(TcpScriptStepType::Loop);
+        first.target = Some(2);
+        let mut last = TcpScriptStep::new(TcpScriptStepType::Loop);
+        last.target = Some(0);
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![first, step(TcpScriptStepType::Send, Some("skipped")), last], None), &Vec::new()).is_err());
+        let mut hex = TcpScriptStep::new(TcpScriptStepType::Send);
+        hex.hex = Some("4g".to_string());
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![hex], None), &Vec::new()).is_err());
+        let mut regex = TcpScriptStep::new(TcpScriptStepType::Expect);
+        regex.regex = Some("(".to_string());
+        assert!(Script::new(&TcpScriptConfiguration::new(vec![regex], None), &Vec::new()).is_err());
+    }
+}
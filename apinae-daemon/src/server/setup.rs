@@ -3,7 +3,7 @@ use std::rc::Rc;
 use apinae_lib::{config::SetupConfiguration, error::ApplicationError};
 use tokio::sync::RwLock;
 
-use super::{common::StartableServer, http::AppServer, tcp::AppListener};
+use super::{common::StartableServer, dns::AppDnsServer, http::AppServer, smtp::AppSmtpServer, tcp::AppListener, udp::AppUdpListener};
 
 /**
  * The `ServerSetup` struct is used to start and stop servers.
@@ -38,11 +38,20 @@ impl ServerSetup {
      */
     pub async fn setup(&mut self, setup_configuration: &SetupConfiguration, params: Vec<(String, String)>) -> Result<(), ApplicationError> {
         log::info!("Setting up setup with id {}", &setup_configuration.id);
-        let servers: Vec<Box<dyn StartableServer>> =
-        setup_configuration.servers.iter().map(|server_configuration| Box::new(AppServer::new(server_configuration.clone(), params.clone())) as Box<dyn StartableServer>).collect();
-        let listeners: Vec<Box<dyn StartableServer>> = setup_configuration.listeners.iter().map(|tcp_listener_data| Box::new(AppListener::new(tcp_listener_data)) as Box<dyn StartableServer>).collect();
+        let servers: Vec<Box<dyn StartableServer>> = setup_configuration
+            .servers
+            .iter()
+            .map(|server_configuration| AppServer::new(server_configuration.clone(), params.clone()).map(|server| Box::new(server) as Box<dyn StartableServer>))
+            .collect::<Result<Vec<Box<dyn StartableServer>>, ApplicationError>>()?;
+        let listeners: Vec<Box<dyn StartableServer>> = setup_configuration.listeners.iter().map(|tcp_listener_data| Box::new(AppListener::new(tcp_listener_data, params.clone())) as Box<dyn StartableServer>).collect();
+        let udp_listeners: Vec<Box<dyn StartableServer>> = setup_configuration.udp_listeners.iter().map(|udp_listener_data| Box::new(AppUdpListener::new(udp_listener_data, params.clone())) as Box<dyn StartableServer>).collect();
+        let dns_servers: Vec<Box<dyn StartableServer>> = setup_configuration.dns_servers.iter().map(|dns_server| Box::new(AppDnsServer::new(dns_server)) as Box<dyn StartableServer>).collect();
+        let smtp_servers: Vec<Box<dyn StartableServer>> = setup_configuration.smtp_servers.iter().map(|smtp_server| Box::new(AppSmtpServer::new(smtp_server)) as Box<dyn StartableServer>).collect();
         self.servers.write().await.extend(servers);
         self.servers.write().await.extend(listeners);
+        self.servers.write().await.extend(udp_listeners);
+        self.servers.write().await.extend(dns_servers);
+        self.servers.write().await.extend(smtp_servers);
         log::info!("Test setup complete");
         Ok(())
     }
@@ -68,7 +77,7 @@ impl ServerSetup {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use apinae_lib::config::ServerConfiguration;
+    use apinae_lib::config::{HttpProtocol, ServerConfiguration};
 
     #[tokio::test]
     async fn test_setup() {
@@ -77,8 +86,11 @@ mod tests {
             id: "test".to_string(),
             name: "Test".to_string(),
             description: "Test description".to_string(),
-            servers: vec![ServerConfiguration { id: "test".to_string(), name: "Test server".to_string(), http_port: Some(8080), https_config: None, endpoints: vec![] }],
+            servers: vec![ServerConfiguration { id: "test".to_string(), name: "Test server".to_string(), http_port: Some(8080), https_config: None, endpoints: vec![], rate_limit: None, unix_socket: None, http_protocol: HttpProtocol::Http1, forward_proxy: None }],
             listeners: vec![],
+            udp_listeners: vec![],
+            dns_servers: vec![],
+            smtp_servers: vec![],
             params: None,
             predefined_params: None,
         };
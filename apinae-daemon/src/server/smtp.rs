@@ -0,0 +1,663 @@
+use std::{
+    net::SocketAddr,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use apinae_lib::{
+    config::{SmtpReplyConfiguration, SmtpServerConfiguration, SmtpStage},
+    error::ApplicationError,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, error, info};
+use regex::Regex;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    task::JoinHandle,
+};
+use tokio_rustls::TlsAcceptor;
+
+use super::{
+    common::StartableServer,
+    fault::check_probability,
+    tcp::{get_tls_acceptor, READ_BUFFER_SIZE},
+};
+
+/**
+ * The domain in the greeting if not configured.
+ */
+const DEFAULT_DOMAIN: &str = "localhost";
+
+/**
+ * The text of an injected reply if not configured.
+ */
+const DEFAULT_REPLY_TEXT: &str = "Injected reply";
+
+/**
+ * The reply code after which the connection is closed.
+ */
+const CLOSING_CODE: u16 = 421;
+
+/**
+ * The maximum message size in bytes if not configured.
+ */
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
+
+/**
+ * The `AppSmtpServer` struct is used to configure and start the smtp server.
+ */
+pub struct AppSmtpServer {
+    // Server configuration.
+    smtp_server: SmtpServerConfiguration,
+}
+
+/**
+ * The server configuration with the TLS acceptor and recipient patterns compiled. It is shared by the connections.
+ */
+struct Mailbox {
+    // The server configuration.
+    smtp_server: SmtpServerConfiguration,
+    // The TLS acceptor used by STARTTLS.
+    tls_acceptor: Option<TlsAcceptor>,
+    // The rejected recipients.
+    reject_recipients: Vec<Regex>,
+    // The number of captured messages. Used in the message identifiers.
+    captured: AtomicUsize,
+}
+
+/**
+ * The state of a conversation.
+ */
+#[derive(Default)]
+struct Session {
+    // The client authenticated.
+    authenticated: bool,
+    // The sender of the current message.
+    from: Option<String>,
+    // The recipients of the current message.
+    recipients: Vec<String>,
+}
+
+/**
+ * How a conversation ended.
+ */
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    // The connection should be closed.
+    Closed,
+    // The client requested TLS.
+    StartTls,
+}
+
+impl AppSmtpServer {
+    /**
+     * Create a new `AppSmtpServer`.
+     *
+     * # Arguments
+     * `smtp_server`: The SMTP server configuration.
+     *
+     * # Returns
+     * The created `AppSmtpServer`.
+     */
+    pub fn new(smtp_server: &SmtpServerConfiguration) -> Self {
+        AppSmtpServer { smtp_server: smtp_server.clone() }
+    }
+
+    /**
+     * Start the server. Each connection is handled in its own task.
+     *
+     * # Returns
+     * Ok if the server was started.
+     *
+     * # Errors
+     * An error if the port could not be bound, the certificates could not be loaded, a recipient pattern is invalid or a reply probability is outside 0.0 to 1.0.
+     */
+    pub async fn start_server(&self) -> Result<(), ApplicationError> {
+        let mailbox = Arc::new(Mailbox::new(&self.smtp_server)?);
+        let tcp_listener =
+            TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], self.smtp_server.port))).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create smtp listener: {err}")))?;
+        info!("Smtp listening on: {}", self.smtp_server.port);
+        tokio::spawn(async move {
+            loop {
+                let stream = match tcp_listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        error!("Failed to accept smtp connection: {err}");
+                        continue;
+                    }
+                };
+                let mailbox = mailbox.clone();
+                tokio::spawn(async move {
+                    let _ = mailbox.handle_connection(stream).await.map_err(|err| {
+                        error!("Error handling smtp connection: {err}");
+                    });
+                });
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Mailbox {
+    /**
+     * Compile the server configuration.
+     *
+     * # Arguments
+     * `smtp_server`: The SMTP server configuration.
+     *
+     * # Returns
+     * The mailbox.
+     *
+     * # Errors
+     * An error if the certificates could not be loaded, a recipient pattern is invalid or a reply probability is outside 0.0 to 1.0.
+     */
+    fn new(smtp_server: &SmtpServerConfiguration) -> Result<Self, ApplicationError> {
+        for reply in smtp_server.replies.as_deref().unwrap_or_default() {
+            check_probability(reply.probability, "Smtp reply probability")?;
+        }
+        let reject_recipients = smtp_server
+            .reject_recipients
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|err| ApplicationError::ConfigurationError(format!("Error in regular expression {pattern}: {err}"))))
+            .collect::<Result<Vec<Regex>, ApplicationError>>()?;
+        Ok(Mailbox { smtp_server: smtp_server.clone(), tls_acceptor: smtp_server.tls.as_ref().map(get_tls_acceptor).transpose()?, reject_recipients, captured: AtomicUsize::new(0) })
+    }
+
+    /**
+     * Greet the client and converse until the connection is closed. The conversation starts over on the TLS stream after STARTTLS.
+     *
+     * # Arguments
+     * `stream`: The stream.
+     *
+     * # Returns
+     * Ok if the conversation ended.
+     *
+     * # Errors
+     * An error if the stream could not be read or written, the TLS handshake failed or a message could not be captured.
+     */
+    async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) -> Result<(), ApplicationError> {
+        let mut stream = BufReader::new(stream);
+        if let Some(injected) = self.get_injected_reply(&SmtpStage::Connect) {
+            if send_injected_reply(&mut stream, injected).await? {
+                return Ok(());
+            }
+        } else {
+            send_reply(&mut stream, &format!("220 {} ESMTP apinae", self.get_domain())).await?;
+        }
+        if self.converse(&mut stream, false).await? == Outcome::StartTls {
+            let Some(tls_acceptor) = &self.tls_acceptor else {
+                return Ok(());
+            };
+            let tls_stream = tls_acceptor.accept(stream.into_inner()).await.map_err(|err| ApplicationError::ServerStartUpError(format!("TLS handshake failed: {err}")))?;
+            let mut tls_stream = BufReader::new(tls_stream);
+            self.converse(&mut tls_stream, true).await?;
+            let _ = tls_stream.shutdown().await;
+        } else {
+            let _ = stream.shutdown().await;
+        }
+        Ok(())
+    }
+
+    /**
+     * Read commands and write the replies until the client quits or requests TLS.
+     *
+     * # Arguments
+     * `stream`: The stream.
+     * `tls_active`: The stream is already encrypted.
+     *
+     * # Returns
+     * How the conversation ended.
+     *
+     * # Errors
+     * An error if the stream could not be read or written or a message could not be captured.
+     */
+    async fn converse<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut BufReader<S>, tls_active: bool) -> Result<Outcome, ApplicationError> {
+        let mut session = Session::default();
+        loop {
+            let Some(line) = read_line(stream).await? else {
+                return Ok(Outcome::Closed);
+            };
+            debug!("Smtp command: {line}");
+            let (verb, argument) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            let verb = verb.to_ascii_uppercase();
+            let stage = match verb.as_str() {
+                "EHLO" | "HELO" => Some(SmtpStage::Hello),
+                "AUTH" => Some(SmtpStage::Auth),
+                "MAIL" => Some(SmtpStage::Mail),
+                "RCPT" => Some(SmtpStage::Rcpt),
+                "DATA" => Some(SmtpStage::Data),
+                _ => None,
+            };
+            if let Some(injected) = stage.and_then(|stage| self.get_injected_reply(&stage)) {
+                if send_injected_reply(stream, injected).await? {
+                    return Ok(Outcome::Closed);
+                }
+                continue;
+            }
+            let reply = match verb.as_str() {
+                "EHLO" => {
+                    let mut extensions =
+                        vec![format!("{} greets {argument}", self.get_domain()), "8BITMIME".to_string(), format!("SIZE {}", self.get_max_message_size()), "AUTH PLAIN".to_string()];
+                    if self.tls_acceptor.is_some() && !tls_active {
+                        extensions.push("STARTTLS".to_string());
+                    }
+                    let last = extensions.len() - 1;
+                    extensions.iter().enumerate().map(|(index, extension)| format!("250{}{extension}", if index == last { ' ' } else { '-' })).collect::<Vec<String>>().join("\r\n")
+                }
+                "HELO" => format!("250 {}", self.get_domain()),
+                "AUTH" => self.authenticate(stream, argument, &mut session).await?,
+                "STARTTLS" if tls_active => "503 5.5.1 TLS already active".to_string(),
+                "STARTTLS" if self.tls_acceptor.is_some() => {
+                    send_reply(stream, "220 2.0.0 Ready to start TLS").await?;
+                    return Ok(Outcome::StartTls);
+                }
+                "MAIL" if self.smtp_server.require_auth && !session.authenticated => "530 5.7.0 Authentication required".to_string(),
+                "MAIL" => match get_address(argument) {
+                    Some(from) => {
+                        session.from = Some(from);
+                        session.recipients.clear();
+                        "250 2.1.0 OK".to_string()
+                    }
+                    None => "501 5.5.4 Syntax error in MAIL".to_string(),
+                },
+                "RCPT" if session.from.is_none() => "503 5.5.1 Need MAIL command".to_string(),
+                "RCPT" => match get_address(argument) {
+                    Some(recipient) if self.reject_recipients.iter().any(|regex| regex.is_match(&recipient)) => {
+                        info!("Rejecting recipient {recipient}");
+                        format!("550 5.1.1 Recipient rejected: {recipient}")
+                    }
+                    Some(recipient) => {
+                        session.recipients.push(recipient);
+                        "250 2.1.5 OK".to_string()
+                    }
+                    None => "501 5.5.4 Syntax error in RCPT".to_string(),
+                },
+                "DATA" if session.recipients.is_empty() => "503 5.5.1 Need RCPT command".to_string(),
+                "DATA" => self.receive_message(stream, &mut session).await?,
+                "RSET" => {
+                    session.from = None;
+                    session.recipients.clear();
+                    "250 2.0.0 OK".to_string()
+                }
+                "NOOP" => "250 2.0.0 OK".to_string(),
+                "VRFY" => "252 2.5.2 Cannot verify user".to_string(),
+                "QUIT" => {
+                    send_reply(stream, "221 2.0.0 Bye").await?;
+                    return Ok(Outcome::Closed);
+                }
+                _ => "500 5.5.2 Command not recognized".to_string(),
+            };
+            send_reply(stream, &reply).await?;
+        }
+    }
+
+    /**
+     * Authenticate with AUTH PLAIN. The credentials are read from the next line if not in the command.
+     *
+     * # Arguments
+     * `stream`: The stream.
+     * `argument`: The mechanism and the optional initial response.
+     * `session`: The session.
+     *
+     * # Returns
+     * The reply.
+     *
+     * # Errors
+     * An error if the stream could not be read or written.
+     */
+    async fn authenticate<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut BufReader<S>, argument: &str, session: &mut Session) -> Result<String, ApplicationError> {
+        let (mechanism, initial_response) = argument.split_once(' ').unwrap_or((argument, ""));
+        if !mechanism.eq_ignore_ascii_case("PLAIN") {
+            return Ok("504 5.5.4 Unrecognized authentication mechanism".to_string());
+        }
+        let response = if initial_response.is_empty() {
+            send_reply(stream, "334 ").await?;
+            read_line(stream).await?.unwrap_or_default()
+        } else {
+            initial_response.to_string()
+        };
+        let credentials = STANDARD.decode(response.trim()).ok().map(|credentials| String::from_utf8_lossy(&credentials).split('\0').map(str::to_string).collect::<Vec<String>>());
+        let Some([_, username, password]) = credentials.as_deref() else {
+            return Ok("501 5.5.2 Invalid AUTH PLAIN response".to_string());
+        };
+        let accepted = self.smtp_server.users.as_ref().is_none_or(|users| users.iter().any(|user| &user.username == username && &user.password == password));
+        if !accepted {
+            info!("Rejecting credentials for {username}");
+            return Ok("535 5.7.8 Authentication credentials invalid".to_string());
+        }
+        info!("Authenticated {username}");
+        session.authenticated = true;
+        Ok("235 2.7.0 Authentication successful".to_string())
+    }
+
+    /**
+     * Receive the message after DATA and capture it. The transaction is reset afterwards.
+     * A message larger than the maximum size is read to the end and rejected with 552 without being kept.
+     *
+     * # Arguments
+     * `stream`: The stream.
+     * `session`: The session.
+     *
+     * # Returns
+     * The reply.
+     *
+     * # Errors
+     * An error if the stream could not be read or written or the message could not be captured.
+     */
+    async fn receive_message<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut BufReader<S>, session: &mut Session) -> Result<String, ApplicationError> {
+        send_reply(stream, "354 End data with <CR><LF>.<CR><LF>").await?;
+        let max_message_size = self.get_max_message_size();
+        let mut message = Vec::new();
+        let mut exceeded = false;
+        let mut line_start = true;
+        let mut line = Vec::new();
+        loop {
+            // Long lines are read in parts so that a line without end does not fill the memory.
+            line.clear();
+            let read = (&mut *stream).take(READ_BUFFER_SIZE as u64).read_until(b'\n', &mut line).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to read: {err}")))?;
+            if read == 0 {
+                return Err(ApplicationError::ServerStartUpError("Connection closed during DATA".to_string()));
+            }
+            if line_start && (line == b".\r\n" || line == b".\n") {
+                break;
+            }
+            let unstuffed = if line_start && line.starts_with(b"..") { &line[1..] } else { &line[..] };
+            if message.len() + unstuffed.len() > max_message_size {
+                exceeded = true;
+                message = Vec::new();
+            } else if !exceeded {
+                message.extend_from_slice(unstuffed);
+            }
+            line_start = line.ends_with(b"\n");
+        }
+        if exceeded {
+            info!("Rejecting message larger than {max_message_size} bytes");
+            session.from = None;
+            session.recipients.clear();
+            return Ok("552 5.3.4 Message size exceeds fixed maximum message size".to_string());
+        }
+        let from = session.from.take().unwrap_or_default();
+        let recipients = std::mem::take(&mut session.recipients);
+        if let Some(injected) = self.get_injected_reply(&SmtpStage::Message) {
+            info!("Not capturing message from {from}");
+            return Ok(format!("{} {}", injected.code, injected.text.as_deref().unwrap_or(DEFAULT_REPLY_TEXT)));
+        }
+        let id = self.capture(&from, &recipients, &message).await?;
+        Ok(format!("250 2.0.0 OK queued as {id}"))
+    }
+
+    /**
+     * Capture the message. The message is written to the capture directory with the envelope as headers, if configured.
+     *
+     * # Arguments
+     * `from`: The sender.
+     * `recipients`: The recipients.
+     * `message`: The message.
+     *
+     * # Returns
+     * The identifier of the message.
+     *
+     * # Errors
+     * An error if the message could not be written.
+     */
+    async fn capture(&self, from: &str, recipients: &[String], message: &[u8]) -> Result<String, ApplicationError> {
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|duration| duration.as_millis()).unwrap_or_default();
+        let id = format!("{timestamp}-{}", self.captured.fetch_add(1, Ordering::SeqCst));
+        info!("Captured message {id} from {from} to {}:\n{}", recipients.join(", "), String::from_utf8_lossy(message));
+        if let Some(capture_directory) = &self.smtp_server.capture_directory {
+            let map_err = |err: std::io::Error| ApplicationError::FileError(format!("Failed to capture message in {capture_directory}: {err}"));
+            tokio::fs::create_dir_all(capture_directory).await.map_err(map_err)?;
+            let mut data = format!("X-Envelope-From: {from}\r\nX-Envelope-To: {}\r\n", recipients.join(", ")).into_bytes();
+            data.extend_from_slice(message);
+            tokio::fs::write(Path::new(capture_directory).join(format!("{id}.eml")), data).await.map_err(map_err)?;
+        }
+        Ok(id)
+    }
+
+    /**
+     * Get the reply injected at the stage.
+     *
+     * # Arguments
+     * `stage`: The stage.
+     *
+     * # Returns
+     * The first reply for the stage, if it is injected.
+     */
+    fn get_injected_reply(&self, stage: &SmtpStage) -> Option<&SmtpReplyConfiguration> {
+        self.smtp_server.replies.as_deref().unwrap_or_default().iter().find(|reply| &reply.stage == stage && reply.probability.is_none_or(|probability| rand::random::<f64>() < probability))
+    }
+
+    /**
+     * Get the maximum message size.
+     *
+     * # Returns
+     * The configured maximum message size in bytes or the default.
+     */
+    fn get_max_message_size(&self) -> usize {
+        self.smtp_server.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /**
+     * Get the domain in the greeting.
+     *
+     * # Returns
+     * The domain.
+     */
+    fn get_domain(&self) -> &str {
+        self.smtp_server.domain.as_deref().unwrap_or(DEFAULT_DOMAIN)
+    }
+}
+
+/**
+ * Read a line without the line ending.
+ *
+ * # Arguments
+ * `stream`: The stream.
+ *
+ * # Returns
+ * The line, or None if the client closed the connection.
+ *
+ * # Errors
+ * An error if the stream could not be read.
+ */
+async fn read_line<S: AsyncRead + Unpin>(stream: &mut BufReader<S>) -> Result<Option<String>, ApplicationError> {
+    let mut line = Vec::new();
+    let read = stream.read_until(b'\n', &mut line).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to read: {err}")))?;
+    if read == 0 {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/**
+ * Write the reply.
+ *
+ * # Arguments
+ * `stream`: The stream.
+ * `reply`: The reply without the line ending.
+ *
+ * # Errors
+ * An error if the stream could not be written.
+ */
+async fn send_reply<S: AsyncWrite + Unpin>(stream: &mut S, reply: &str) -> Result<(), ApplicationError> {
+    debug!("Smtp reply: {reply}");
+    let map_err = |err: std::io::Error| ApplicationError::ServerStartUpError(format!("Failed to write: {err}"));
+    stream.write_all(format!("{reply}\r\n").as_bytes()).await.map_err(map_err)?;
+    stream.flush().await.map_err(map_err)
+}
+
+/**
+ * Write the injected reply.
+ *
+ * # Arguments
+ * `stream`: The stream.
+ * `injected`: The injected reply.
+ *
+ * # Returns
+ * True if the connection should be closed.
+ *
+ * # Errors
+ * An error if the stream could not be written.
+ */
+async fn send_injected_reply<S: AsyncWrite + Unpin>(stream: &mut S, injected: &SmtpReplyConfiguration) -> Result<bool, ApplicationError> {
+    info!("Injecting reply {}", injected.code);
+    send_reply(stream, &format!("{} {}", injected.code, injected.text.as_deref().unwrap_or(DEFAULT_REPLY_TEXT))).await?;
+    Ok(injected.code == CLOSING_CODE)
+}
+
+/**
+ * Get the address between angle brackets in a MAIL or RCPT argument.
+ *
+ * # Arguments
+ * `argument`: The argument, like FROM:<user@example.com>.
+ *
+ * # Returns
+ * The address, or None if there are no angle brackets.
+ */
+fn get_address(argument: &str) -> Option<String> {
+    let start = argument.find('<')?;
+    let end = argument[start..].find('>')? + start;
+    Some(argument[start + 1..end].to_string())
+}
+
+impl StartableServer for AppSmtpServer {
+    fn start_server(&mut self) -> Result<Vec<JoinHandle<()>>, ApplicationError> {
+        let smtp_server = self.smtp_server.clone();
+        let handle = tokio::spawn(async move {
+            let _ = AppSmtpServer { smtp_server }.start_server().await.map_err(|err| {
+                error!("Failed to start smtp server: {err}");
+            });
+        });
+        Ok(vec![handle])
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use apinae_lib::config::SmtpUserConfiguration;
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    use super::*;
+
+    /**
+     * The client side of a conversation.
+     */
+    struct Client {
+        reader: BufReader<ReadHalf<DuplexStream>>,
+        writer: WriteHalf<DuplexStream>,
+    }
+
+    impl Client {
+        /**
+         * Start a conversation with the mailbox and read the greeting.
+         */
+        async fn connect(smtp_server: &SmtpServerConfiguration) -> (Client, String) {
+            let mailbox = Mailbox::new(smtp_server).unwrap();
+            let (server, client) = tokio::io::duplex(1024);
+            tokio::spawn(async move { mailbox.handle_connection(server).await });
+            let (reader, writer) = tokio::io::split(client);
+            let mut client = Client { reader: BufReader::new(reader), writer };
+            let greeting = client.read_reply().await;
+            (client, greeting)
+        }
+
+        /**
+         * Send the line and read the reply.
+         */
+        async fn send(&mut self, line: &str) -> String {
+            self.writer.write_all(format!("{line}\r\n").as_bytes()).await.unwrap();
+            self.read_reply().await
+        }
+
+        /**
+         * Read the reply. The lines of a multiline reply are joined.
+         */
+        async fn read_reply(&mut self) -> String {
+            let mut reply = Vec::new();
+            while let Some(line) = read_line(&mut self.reader).await.unwrap() {
+                let last = line.as_bytes().get(3) != Some(&b'-');
+                reply.push(line);
+                if last {
+                    break;
+                }
+            }
+            reply.join("\n")
+        }
+    }
+
+    /**
+     * Verify a conversation with authentication, a rejected recipient and a captured message.
+     */
+    #[tokio::test]
+    async fn test_capture() {
+        let capture_directory = std::env::temp_dir().join(format!("apinae-smtp-{}", std::process::id()));
+        let mut smtp_server = SmtpServerConfiguration::new(0, Some(capture_directory.to_string_lossy().to_string())).unwrap();
+        smtp_server.require_auth = true;
+        smtp_server.users = Some(vec![SmtpUserConfiguration::new("user".to_string(), "secret".to_string())]);
+        smtp_server.reject_recipients = Some(vec!["^blocked@".to_string()]);
+        let (mut client, greeting) = Client::connect(&smtp_server).await;
+        assert!(greeting.starts_with("220 localhost"));
+        let ehlo = client.send("EHLO client").await;
+        assert!(ehlo.contains("250 AUTH PLAIN") && !ehlo.contains("STARTTLS"));
+        assert!(client.send("MAIL FROM:<sender@test>").await.starts_with("530"));
+        assert!(client.send(&format!("AUTH PLAIN {}", STANDARD.encode("\0user\0wrong"))).await.starts_with("535"));
+        assert!(client.send("AUTH PLAIN").await.starts_with("334"));
+        assert!(client.send(&STANDARD.encode("\0user\0secret")).await.starts_with("235"));
+        assert!(client.send("MAIL FROM:<sender@test> SIZE=20").await.starts_with("250"));
+        assert!(client.send("RCPT TO:<blocked@test>").await.starts_with("550"));
+        assert!(client.send("RCPT TO:<receiver@test>").await.starts_with("250"));
+        assert!(client.send("DATA").await.starts_with("354"));
+        let queued = client.send("Subject: Test\r\n\r\n..Dotted\r\n.").await;
+        assert!(queued.starts_with("250"));
+        assert!(client.send("QUIT").await.starts_with("221"));
+        let id = queued.rsplit(' ').next().unwrap();
+        let captured = std::fs::read_to_string(capture_directory.join(format!("{id}.eml"))).unwrap();
+        assert_eq!(captured, "X-Envelope-From: sender@test\r\nX-Envelope-To: receiver@test\r\nSubject: Test\r\n\r\n.Dotted\r\n");
+        let _ = std::fs::remove_dir_all(capture_directory);
+    }
+
+    /**
+     * Verify that a message larger than the maximum size is rejected and the session continues.
+     */
+    #[tokio::test]
+    async fn test_max_message_size() {
+        let mut smtp_server = SmtpServerConfiguration::new(0, None).unwrap();
+        smtp_server.max_message_size = Some(16);
+        let (mut client, _) = Client::connect(&smtp_server).await;
+        assert!(client.send("EHLO client").await.contains("250-SIZE 16"));
+        assert!(client.send("MAIL FROM:<sender@test>").await.starts_with("250"));
+        assert!(client.send("RCPT TO:<receiver@test>").await.starts_with("250"));
+        assert!(client.send("DATA").await.starts_with("354"));
+        assert!(client.send(&format!("Subject: Large\r\n\r\n{}\r\n.", "x".repeat(20_000))).await.starts_with("552"));
+        assert!(client.send("RCPT TO:<receiver@test>").await.starts_with("503"));
+        assert!(client.send("MAIL FROM:<sender@test>").await.starts_with("250"));
+        assert!(client.send("RCPT TO:<receiver@test>").await.starts_with("250"));
+        assert!(client.send("DATA").await.starts_with("354"));
+        assert!(client.send("Small\r\n.").await.starts_with("250"));
+    }
+
+    /**
+     * Verify injected replies and that the connection is closed after 421.
+     */
+    #[tokio::test]
+    async fn test_injected_replies() {
+        let mut smtp_server = SmtpServerConfiguration::new(0, None).unwrap();
+        smtp_server.replies = Some(vec![SmtpReplyConfiguration::new(SmtpStage::Rcpt, 450, Some("Mailbox busy".to_string()), None), SmtpReplyConfiguration::new(SmtpStage::Data, 421, None, None)]);
+        let (mut client, _) = Client::connect(&smtp_server).await;
+        assert!(client.send("HELO client").await.starts_with("250"));
+        assert!(client.send("MAIL FROM:<sender@test>").await.starts_with("250"));
+        assert_eq!(client.send("RCPT TO:<receiver@test>").await, "450 Mailbox busy");
+        assert_eq!(client.send("DATA").await, "421 Injected reply");
+        assert_eq!(client.read_reply().await, "");
+        smtp_server.replies = Some(vec![SmtpReplyConfiguration::new(SmtpStage::Data, 421, None, Some(2.0))]);
+        assert!(Mailbox::new(&smtp_server).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::{collections::HashMap, sync::Arc};
+
+use rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+
+/**
+ * Selects the server certificate by the SNI hostname requested by the client.
+ */
+#[derive(Debug)]
+pub struct SniCertificateResolver {
+    // Certificate used if no hostname matches or the client does not send SNI.
+    default_certificate: Arc<CertifiedKey>,
+    // Certificates by lower case hostname.
+    certificates: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl SniCertificateResolver {
+    /**
+     * Create a new resolver.
+     *
+     * # Arguments
+     * `default_certificate`: Certificate used if no hostname matches.
+     * `certificates`: Certificates by hostname. A leading `*.` matches any single label.
+     *
+     * # Returns
+     * The resolver.
+     */
+    pub fn new(default_certificate: CertifiedKey, certificates: Vec<(String, CertifiedKey)>) -> Self {
+        let certificates = certificates.into_iter().map(|(hostname, certificate)| (hostname.to_lowercase(), Arc::new(certificate))).collect();
+        SniCertificateResolver { default_certificate: Arc::new(default_certificate), certificates }
+    }
+
+    /**
+     * Find the certificate for the hostname. An exact match is preferred over a wildcard.
+     *
+     * # Arguments
+     * `hostname`: The SNI hostname.
+     *
+     * # Returns
+     * The certificate.
+     */
+    fn find(&self, hostname: Option<&str>) -> Arc<CertifiedKey> {
+        let Some(hostname) = hostname.map(str::to_lowercase) else {
+            return self.default_certificate.clone();
+        };
+        let wildcard = hostname.split_once('.').map(|(_, domain)| format!("*.{domain}"));
+        self.certificates
+            .get(&hostname)
+            .or_else(|| wildcard.and_then(|wildcard| self.certificates.get(&wildcard)))
+            .inspect(|_| log::debug!("Using certificate for SNI hostname {hostname}"))
+            .unwrap_or(&self.default_certificate)
+            .clone()
+    }
+}
+
+impl ResolvesServerCert for SniCertificateResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.find(client_hello.server_name()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use rustls::{crypto::CryptoProvider, pki_types::CertificateDer, sign::SigningKey};
+
+    use super::*;
+
+    /**
+     * Load a certified key from the test resources.
+     */
+    fn get_certified_key(certificate: &str, key: &str) -> CertifiedKey {
+        let provider = CryptoProvider::get_default().cloned().unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()));
+        let certificates: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut std::io::BufReader::new(std::fs::File::open(certificate).unwrap())).collect::<Result<_, _>>().unwrap();
+        let key = rustls_pemfile::private_key(&mut std::io::BufReader::new(std::fs::File::open(key).unwrap())).unwrap().unwrap();
+        let signing_key: Arc<dyn SigningKey> = provider.key_provider.load_private_key(key).unwrap();
+        CertifiedKey::new(certificates, signing_key)
+    }
+
+    /**
+     * Verify that the certificate is selected by hostname.
+     */
+    #[test]
+    fn test_find() {
+        let rsa = get_certified_key("./tests/resources/server_cert.pem", "./tests/resources/server_key.pem");
+        let ec = get_certified_key("./tests/resources/server_ec_cert.pem", "./tests/resources/server_ec_key.pem");
+        let wildcard = get_certified_key("./tests/resources/server_ec_cert.pem", "./tests/resources/server_ec_key.pem");
+        let ec_certificate = ec.cert[0].clone();
+        let resolver = SniCertificateResolver::new(rsa, vec![("EC.localhost".to_string(), ec), ("*.example.com".to_string(), wildcard)]);
+        assert_eq!(resolver.find(Some("ec.localhost")).cert[0], ec_certificate);
+        assert_eq!(resolver.find(Some("api.example.com")).cert[0], ec_certificate);
+        assert_ne!(resolver.find(Some("example.com")).cert[0], ec_certificate);
+        assert_ne!(resolver.find(Some("other.localhost")).cert[0], ec_certificate);
+        assert_ne!(resolver.find(None).cert[0], ec_certificate);
+    }
+}
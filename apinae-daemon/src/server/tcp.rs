@@ -1,17 +1,52 @@
 use std::{
-    fs::File,
-    io::{BufReader, Read},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
 use apinae_lib::{
-    config::{CloseConnectionWhen, TcpListenerData},
+    config::{CloseConnectionWhen, HttpsConfiguration, TcpListenerData, TcpTlsConfiguration},
     error::ApplicationError,
 };
 use log::{error, info};
-use tokio::{io::Interest, task::JoinHandle};
+use socket2::SockRef;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, Interest},
+    sync::Semaphore,
+    task::JoinHandle,
+};
+use tokio_rustls::TlsAcceptor;
+
+use super::{
+    common::StartableServer,
+    framing::Framer,
+    http::ssl_builder,
+    payload::{decode_data, log_received, send, Payload},
+    proxy::Proxy,
+    rules::{compile_rules, find_rule, Rule},
+    script::Script,
+};
+
+#[cfg(unix)]
+use super::unix::bind_unix_socket;
+
+/**
+ * The size of the buffer used when reading from the connection.
+ */
+pub(super) const READ_BUFFER_SIZE: usize = 8192;
 
-use super::common::StartableServer;
+/**
+ * Interval for checking if a connection that is never read was closed.
+ */
+const NEVER_READ_POLL_INTERVAL_MS: u64 = 100;
+
+/**
+ * The listen backlog used if none is configured.
+ */
+const DEFAULT_BACKLOG: u32 = 1024;
 
 /**
  * The `AppListener` struct is used to configure and start the listener.
@@ -19,6 +54,48 @@ use super::common::StartableServer;
 pub struct AppListener {
     // Server configuration
     tcp_listener: TcpListenerData,
+    // The parameters replaced in the data.
+    params: Vec<(String, String)>,
+}
+
+/**
+ * The bound listener.
+ */
+enum Server {
+    // Listens on the port.
+    Tcp(tokio::net::TcpListener),
+    // Listens on the unix socket.
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+/**
+ * An accepted connection.
+ */
+enum Connection {
+    // Connection on the port.
+    Tcp(tokio::net::TcpStream),
+    // Connection on the unix socket.
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+/**
+ * The listener configuration with the proxy, script, rules, framing and data compiled. It is shared by the connections.
+ */
+struct Conversation {
+    // The listener configuration.
+    tcp_listener_data: TcpListenerData,
+    // The proxy.
+    proxy: Option<Proxy>,
+    // The conversation script.
+    script: Option<Script>,
+    // The rules.
+    rules: Vec<Rule>,
+    // Splits the input into messages. It is cloned for each connection.
+    framer: Framer,
+    // The response used if no rule matches.
+    response: Option<Payload>,
 }
 
 impl AppListener {
@@ -27,16 +104,18 @@ impl AppListener {
      *
      * # Arguments
      * `tcp_listener`: The TCP listener configuration.
+     * `params`: The parameters replaced in the data.
      *
      * # Returns
      * The created `AppListener`.
      */
-    pub fn new(tcp_listener: &TcpListenerData) -> Self {
-        AppListener { tcp_listener: tcp_listener.clone() }
+    pub fn new(tcp_listener: &TcpListenerData, params: Vec<(String, String)>) -> Self {
+        AppListener { tcp_listener: tcp_listener.clone(), params }
     }
 
     /**
-     * Start the listener.
+     * Start the listener. Each connection is handled in its own task. If a connection limit is configured the next
+     * connection is not accepted until a connection closes.
      *
      * # Returns
      * Ok if the listener was started.
@@ -47,28 +126,67 @@ impl AppListener {
      */
     pub async fn start_listener(&self) -> Result<(), ApplicationError> {
         let server = self.bind_listener().await?;
+        let tls_acceptor = self.tcp_listener.tls.as_ref().map(get_tls_acceptor).transpose()?;
+        let conversation = Arc::new(self.compile_conversation()?);
+        if let Some(proxy) = &conversation.proxy {
+            proxy.start_control()?;
+        }
         let tcp_listener_data = self.tcp_listener.clone();
+        let open_connections = Arc::new(AtomicUsize::new(0));
+        let connection_limit = self.tcp_listener.connection_limit.map(|connection_limit| Arc::new(Semaphore::new(connection_limit)));
 
         tokio::spawn(async move {
             loop {
-                let Some(stream) = Self::wait_for_accept(&server, &tcp_listener_data).await else {
+                let permit = match &connection_limit {
+                    Some(connection_limit) => match connection_limit.clone().acquire_owned().await {
+                        Ok(permit) => Some(permit),
+                        Err(err) => {
+                            error!("Failed to wait for connection limit: {err}");
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let Some(connection) = Self::wait_for_accept(&server, &tcp_listener_data).await else {
                     continue;
                 };
-                let tcp_listener_data = tcp_listener_data.clone();
-                let _ = tokio::spawn(async move {
-                    let _ = Self::handle_tcp_stream(stream, tcp_listener_data).await.map_err(|err| {
+                if tcp_listener_data.reset_on_close {
+                    connection.set_reset_on_close();
+                }
+                if let Some(max_connections) = tcp_listener_data.max_connections.filter(|max_connections| open_connections.load(Ordering::SeqCst) >= *max_connections) {
+                    info!("Refusing connection, {max_connections} connections are open");
+                    connection.set_reset_on_close();
+                    continue;
+                }
+                let open_connection = OpenConnection::new(&open_connections);
+                let tls_acceptor = tls_acceptor.clone();
+                let conversation = conversation.clone();
+                tokio::spawn(async move {
+                    let _open_connection = open_connection;
+                    let _permit = permit;
+                    if conversation.tcp_listener_data.never_read {
+                        log::debug!("Holding connection without reading");
+                        connection.wait_for_close().await;
+                        info!("Connection closed");
+                        return;
+                    }
+                    let result = match connection {
+                        Connection::Tcp(stream) => Self::handle_connection(stream, tls_acceptor, &conversation).await,
+                        #[cfg(unix)]
+                        Connection::Unix(stream) => Self::handle_connection(stream, tls_acceptor, &conversation).await,
+                    };
+                    let _ = result.map_err(|err| {
                         error!("Error handling tcp connection: {err}");
                     });
                     info!("Connection closed");
-                })
-                .await;
+                });
             }
         });
         Ok(())
     }
 
     /**
-     * Bind the listener.
+     * Bind the listener. The unix socket is used instead of the port if configured.
      *
      * # Returns
      * The bound listener.
@@ -76,86 +194,117 @@ impl AppListener {
      * # Errors
      *  An error if the listener could not be bound.
      */
-    async fn bind_listener(&self) -> Result<tokio::net::TcpListener, ApplicationError> {
-        let server = tokio::net::TcpListener::bind(("127.0.0.1", self.tcp_listener.port)).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create tcp listener: {err}")))?;
+    async fn bind_listener(&self) -> Result<Server, ApplicationError> {
+        if let Some(unix_socket) = &self.tcp_listener.unix_socket {
+            #[cfg(unix)]
+            return tokio::net::UnixListener::from_std(bind_unix_socket(unix_socket)?)
+                .map(Server::Unix)
+                .map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create unix socket listener: {err}")));
+            #[cfg(not(unix))]
+            return Err(ApplicationError::ConfigurationError(format!("Unix socket {} is only supported on unix", unix_socket.path)));
+        }
+        let map_err = |err: std::io::Error| ApplicationError::ServerStartUpError(format!("Failed to create tcp listener: {err}"));
+        let socket = tokio::net::TcpSocket::new_v4().map_err(map_err)?;
+        #[cfg(unix)]
+        socket.set_reuseaddr(true).map_err(map_err)?;
+        socket.bind(SocketAddr::from(([127, 0, 0, 1], self.tcp_listener.port))).map_err(map_err)?;
+        let server = socket.listen(self.tcp_listener.backlog.unwrap_or(DEFAULT_BACKLOG)).map_err(map_err)?;
         log::info!("Listening on: {}", self.tcp_listener.port);
-        Ok(server)
+        Ok(Server::Tcp(server))
     }
 
     /**
-     * Write the data to the output stream.
+     * Compile the proxy, script, rules, framing and data.
      *
-     * # Arguments
-     * `stream`: The output stream.
-     * `data`: The data to write.
+     * # Returns
+     * The compiled conversation.
      *
+     * # Errors
+     * An error if the proxy, script, rules, framing or data is invalid.
      */
-    fn output_string_data(stream: &mut tokio::net::TcpStream, data: &String) {
-        info!("Sending: {data}");
-        let _ = stream.try_write(data.as_bytes()).map_err(|err| {
-            error!("Failed to write data: {err}");
-        });
+    fn compile_conversation(&self) -> Result<Conversation, ApplicationError> {
+        let response = match &self.tcp_listener.data {
+            Some(data) => Some(Payload::Bytes(decode_data(data, &self.tcp_listener.data_encoding, &self.params)?)),
+            None => self.tcp_listener.file.clone().map(Payload::File),
+        };
+        Ok(Conversation {
+            tcp_listener_data: self.tcp_listener.clone(),
+            proxy: self.tcp_listener.proxy.as_ref().map(Proxy::new).transpose()?,
+            script: self.tcp_listener.script.as_ref().map(|script| Script::new(script, &self.params)).transpose()?,
+            rules: compile_rules(self.tcp_listener.rules.as_deref().unwrap_or_default(), &self.params)?,
+            framer: Framer::new(self.tcp_listener.framing.as_ref())?,
+            response,
+        })
     }
 
     /**
-     * Write the file data to the output stream.
+     * Wait for the accept. The accept is delayed if configured.
      *
      * # Arguments
-     * `stream`: The output stream.
-     * `file`: The file to write.
-     *
-     */
-    fn output_file_data(stream: &mut tokio::net::TcpStream, file: &String) {
-        match File::open(file) {
-            Ok(file) => {
-                let mut output = BufReader::new(file);
-                let mut buffer = Vec::new();
-                info!("Sending: {}", String::from_utf8_lossy(&buffer));
-                let _ = output.read_to_end(&mut buffer).map_err(|err| {
-                    error!("Failed to read file: {err}");
-                });
-                let _ = stream.try_write(buffer.as_mut()).map_err(|err| {
-                    error!("Failed to write file: {err}");
-                });
+     * `server`: The server.
+     *
+     * # Returns
+     * The accepted connection.
+     *
+     */
+    async fn wait_for_accept(server: &Server, tcp_listener_data: &TcpListenerData) -> Option<Connection> {
+        if !tcp_listener_data.accept {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            return None;
+        }
+        if let Some(delay_accept_ms) = tcp_listener_data.delay_accept_ms {
+            tokio::time::sleep(Duration::from_millis(delay_accept_ms)).await;
+        }
+        let connection = match server {
+            Server::Tcp(tcp_listener) => tcp_listener.accept().await.map(|(stream, _)| Connection::Tcp(stream)),
+            #[cfg(unix)]
+            Server::Unix(unix_listener) => unix_listener.accept().await.map(|(stream, _)| Connection::Unix(stream)),
+        };
+        match connection {
+            Ok(connection) => {
+                log::debug!("Accepted connection");
+                Some(connection)
             }
             Err(err) => {
-                error!("Failed to open file: {err}");
+                error!("Failed to accept connection: {err}");
+                None
             }
         }
     }
 
     /**
-     * Wait for the accept.
+     * Handle the accepted connection. The connection is held without reading if configured, otherwise the TLS handshake
+     * is done if configured before the stream is handled.
      *
      * # Arguments
-     * `server`: The server.
+     * `stream`: The accepted stream.
+     * `tls_acceptor`: The TLS acceptor.
+     * `conversation`: The compiled conversation.
      *
      * # Returns
-     * The accepted stream.
+     * Ok if the connection was handled.
      *
+     * # Errors
+     * An error if the TLS handshake failed or the stream could not be handled.
      */
-    async fn wait_for_accept(tcp_listener: &tokio::net::TcpListener, tcp_listener_data: &TcpListenerData) -> Option<tokio::net::TcpStream> {
-        if !tcp_listener_data.accept {
-            tokio::time::sleep(Duration::from_secs(1)).await;
-            return None;
+    async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(stream: S, tls_acceptor: Option<TlsAcceptor>, conversation: &Conversation) -> Result<(), ApplicationError> {
+        match tls_acceptor {
+            Some(tls_acceptor) => match tls_acceptor.accept(stream).await {
+                Ok(stream) => Self::handle_tcp_stream(stream, conversation).await,
+                Err(err) => Err(ApplicationError::ServerStartUpError(format!("TLS handshake failed: {err}"))),
+            },
+            None => Self::handle_tcp_stream(stream, conversation).await,
         }
-        let (stream, _) = match tcp_listener.accept().await {
-            Ok(stream) => stream,
-            Err(err) => {
-                error!("Failed to accept connection: {err}");
-                return None;
-            }
-        };
-        log::debug!("Accepted connection");
-        Some(stream)
     }
 
     /**
-     * Handle the TCP stream.
+     * Handle the TCP stream. The stream is either a plain tcp stream or a TLS stream.
+     * The stream is proxied if a proxy is configured, otherwise the conversation script is used if configured.
+     * The stream is shut down when the conversation ends, unless it is reset on close.
      *
      * # Arguments
      * `stream`: The TCP stream.
-     * `tcp_listener_data`: The TCP listener data.
+     * `conversation`: The compiled conversation.
      *
      * # Returns
      * Ok if the stream was handled.
@@ -164,64 +313,161 @@ impl AppListener {
      * An error if the stream could not be handled.
      *
      */
-    async fn handle_tcp_stream(mut stream: tokio::net::TcpStream, tcp_listener_data: TcpListenerData) -> Result<(), ApplicationError> {
-        let mut written = true;
+    async fn handle_tcp_stream<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, conversation: &Conversation) -> Result<(), ApplicationError> {
+        let result = match (&conversation.proxy, &conversation.script) {
+            (Some(proxy), _) => proxy.run(&mut stream).await,
+            (None, Some(script)) => script.run(&mut stream).await,
+            (None, None) => Self::converse(&mut stream, conversation).await,
+        };
+        if !conversation.tcp_listener_data.reset_on_close {
+            let _ = stream.shutdown().await;
+        }
+        result
+    }
 
+    /**
+     * Read messages from the stream and write the responses until the connection is closed.
+     * The response of the first rule matching the message is used. Otherwise the data or file is used.
+     *
+     * # Arguments
+     * `stream`: The stream.
+     * `conversation`: The compiled conversation.
+     *
+     * # Returns
+     * Ok if the client closed the connection or the connection should be closed.
+     *
+     * # Errors
+     * An error if the stream could not be read or written.
+     */
+    async fn converse<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, conversation: &Conversation) -> Result<(), ApplicationError> {
+        let tcp_listener_data = &conversation.tcp_listener_data;
+        let mut framer = conversation.framer.clone();
         loop {
-            tokio::time::sleep(Duration::from_micros(10)).await;
-
-            let ready = stream.ready(Interest::READABLE | Interest::WRITABLE).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to get ready: {err}")))?;
-            log::debug!("Ready: {ready:?}");
-            if ready.is_read_closed() || ready.is_write_closed() {
+            if tcp_listener_data.close_connection == CloseConnectionWhen::BeforeRead {
                 return Ok(());
             }
 
-            if tcp_listener_data.close_connection == CloseConnectionWhen::BeforeRead {
+            let Some(message) = framer.read_message(stream).await? else {
+                return Ok(());
+            };
+            log_received(&message);
+
+            if tcp_listener_data.close_connection == CloseConnectionWhen::AfterRead {
                 return Ok(());
             }
 
-            if ready.is_readable() {
-                loop {
-                    let mut buffer = vec![];
-                    let _ = stream.try_read_buf(&mut buffer);
-                    if !buffer.is_empty() {
-                        info!("Received: {:?}", String::from_utf8_lossy(&buffer));
-                    }
-                    if buffer.is_empty() {
-                        written = false;
-                        break;
-                    }
-                }
+            if let Some(delay_write_ms) = tcp_listener_data.delay_write_ms {
+                tokio::time::sleep(Duration::from_millis(delay_write_ms)).await;
+            }
+            let rule = find_rule(&conversation.rules, &message);
+            if let Some(response) = rule.map_or(conversation.response.as_ref(), |rule| rule.response.as_ref()) {
+                send(stream, response).await?;
+            }
+            if rule.is_some_and(|rule| rule.close) {
+                return Ok(());
             }
 
-            if tcp_listener_data.close_connection == CloseConnectionWhen::AfterRead {
+            if tcp_listener_data.close_connection == CloseConnectionWhen::AfterResponse {
                 return Ok(());
             }
+        }
+    }
+}
 
-            if ready.is_writable() && !written {
-                if let Some(delay_write_ms) = tcp_listener_data.delay_write_ms {
-                    tokio::time::sleep(Duration::from_millis(delay_write_ms)).await;
-                }
-                if let Some(data) = tcp_listener_data.data.as_ref() {
-                    Self::output_string_data(&mut stream, data);
-                } else if let Some(file) = tcp_listener_data.file.as_ref() {
-                    Self::output_file_data(&mut stream, file);
-                }
+impl Connection {
+    /**
+     * Close the connection with a reset. Unix sockets have no reset and are closed normally.
+     */
+    fn set_reset_on_close(&self) {
+        match self {
+            Connection::Tcp(stream) => set_reset_on_close(stream),
+            #[cfg(unix)]
+            Connection::Unix(_) => {}
+        }
+    }
 
-                if tcp_listener_data.close_connection == CloseConnectionWhen::AfterResponse {
-                    return Ok(());
-                }
-                written = true;
+    /**
+     * Wait until the peer closes the connection without reading the data it sent.
+     * The readiness is polled while unread data is pending, because the connection stays readable.
+     */
+    async fn wait_for_close(&self) {
+        loop {
+            let ready = match self {
+                Connection::Tcp(stream) => stream.ready(Interest::READABLE).await,
+                #[cfg(unix)]
+                Connection::Unix(stream) => stream.ready(Interest::READABLE).await,
+            };
+            match ready {
+                Ok(ready) if !ready.is_read_closed() && !ready.is_error() => tokio::time::sleep(Duration::from_millis(NEVER_READ_POLL_INTERVAL_MS)).await,
+                _ => return,
             }
         }
     }
 }
 
+/**
+ * Counts an open connection until it is dropped.
+ */
+struct OpenConnection {
+    // The number of open connections of the listener.
+    open_connections: Arc<AtomicUsize>,
+}
+
+impl OpenConnection {
+    /**
+     * Count a new open connection.
+     *
+     * # Arguments
+     * `open_connections`: The number of open connections of the listener.
+     *
+     * # Returns
+     * The open connection.
+     */
+    fn new(open_connections: &Arc<AtomicUsize>) -> Self {
+        open_connections.fetch_add(1, Ordering::SeqCst);
+        OpenConnection { open_connections: open_connections.clone() }
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.open_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/**
+ * Set SO_LINGER to 0 so that closing the connection sends a reset (RST) instead of a graceful close.
+ *
+ * # Arguments
+ * `stream`: The stream.
+ */
+fn set_reset_on_close(stream: &tokio::net::TcpStream) {
+    let _ = SockRef::from(stream).set_linger(Some(Duration::ZERO)).map_err(|err| error!("Failed to set linger: {err}"));
+}
+
+/**
+ * Create the TLS acceptor. The certificates are loaded as for an https server.
+ *
+ * # Arguments
+ * `tls`: The TLS configuration.
+ *
+ * # Returns
+ * The TLS acceptor.
+ *
+ * # Errors
+ * An error if the certificates could not be loaded or a TLS version is not supported.
+ */
+pub(super) fn get_tls_acceptor(tls: &TcpTlsConfiguration) -> Result<TlsAcceptor, ApplicationError> {
+    let https_config = HttpsConfiguration::new(tls.server_certificate.clone(), tls.private_key.clone(), 0, tls.client_certificate.clone(), tls.supported_tls_versions.clone());
+    Ok(TlsAcceptor::from(Arc::new(ssl_builder(&https_config)?)))
+}
+
 impl StartableServer for AppListener {
     fn start_server(&mut self) -> Result<Vec<JoinHandle<()>>, ApplicationError> {
         let tcp_listener_data = self.tcp_listener.clone();
+        let params = self.params.clone();
         let handle = tokio::spawn(async move {
-            let listener = AppListener { tcp_listener: tcp_listener_data };
+            let listener = AppListener { tcp_listener: tcp_listener_data, params };
             let _ = listener.start_listener().await.map_err(|err| {
                 error!("Failed to start listener: {err}");
             });
@@ -229,3 +475,86 @@ impl StartableServer for AppListener {
         Ok(vec![handle])
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /**
+     * Create a listener answering each message with the data.
+     */
+    fn get_listener(data: &str, port: u16) -> AppListener {
+        let tcp_listener = TcpListenerData::new(None, Some(data.to_string()), None, port, true, CloseConnectionWhen::Never).unwrap();
+        AppListener::new(&tcp_listener, Vec::new())
+    }
+
+    /**
+     * Get a port assigned by the operating system, so the tests do not depend on fixed ports being free.
+     */
+    fn get_free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+
+    /**
+     * Verify that a response larger than the stream buffer is written completely.
+     */
+    #[tokio::test]
+    async fn test_large_response() {
+        let data = "0123456789".repeat(10_000);
+        let conversation = get_listener(&data, 0).compile_conversation().unwrap();
+        let (server, mut client) = tokio::io::duplex(64);
+        let handle = tokio::spawn(async move { AppListener::handle_tcp_stream(server, &conversation).await });
+        client.write_all(b"PING").await.unwrap();
+        let mut received = vec![0; data.len()];
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, data.as_bytes());
+        drop(client);
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    /**
+     * Verify that connections are handled concurrently and wait for the connection limit.
+     */
+    #[tokio::test]
+    async fn test_connection_limit() {
+        let port = get_free_port();
+        let mut listener = get_listener("OK", port);
+        listener.tcp_listener.connection_limit = Some(2);
+        listener.start_listener().await.unwrap();
+        let mut first = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut second = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut third = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut buffer = [0; 2];
+        for stream in [&mut first, &mut second, &mut third] {
+            stream.write_all(b"PING").await.unwrap();
+        }
+        second.read_exact(&mut buffer).await.unwrap();
+        first.read_exact(&mut buffer).await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(200), third.read_exact(&mut buffer)).await.is_err());
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(5), third.read_exact(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer, b"OK");
+    }
+
+    /**
+     * Verify that the listener accepts connections on the unix socket instead of the port.
+     */
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket() {
+        let path = std::env::temp_dir().join(format!("apinae-tcp-{}.sock", std::process::id()));
+        let port = get_free_port();
+        let mut listener = get_listener("OK", port);
+        listener.tcp_listener.unix_socket = Some(apinae_lib::config::UnixSocketConfiguration::new(path.to_string_lossy().to_string(), None));
+        listener.start_listener().await.unwrap();
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream.write_all(b"PING").await.unwrap();
+        let mut buffer = [0; 2];
+        tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer, b"OK");
+        assert!(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use apinae_lib::{config::UdpListenerData, error::ApplicationError};
+use log::{error, info};
+use tokio::{net::UdpSocket, task::JoinHandle};
+
+use super::{
+    common::StartableServer,
+    fault::check_probability,
+    payload::{decode_data, log_received, Payload},
+    rules::{compile_udp_rules, find_rule, Rule},
+};
+
+/**
+ * The largest datagram received.
+ */
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+/**
+ * The `AppUdpListener` struct is used to configure and start the udp listener.
+ */
+pub struct AppUdpListener {
+    // Listener configuration.
+    udp_listener: UdpListenerData,
+    // The parameters replaced in the data.
+    params: Vec<(String, String)>,
+}
+
+/**
+ * The listener configuration with the rules and data compiled. It is shared by the datagrams.
+ */
+struct Responder {
+    // The rules.
+    rules: Vec<Rule>,
+    // The response used if no rule matches.
+    response: Option<Payload>,
+    // Time to wait before responding.
+    delay_write_ms: Option<u64>,
+    // Probability that a datagram is dropped.
+    drop_probability: Option<f64>,
+}
+
+impl AppUdpListener {
+    /**
+     * Create a new `AppUdpListener`.
+     *
+     * # Arguments
+     * `udp_listener`: The UDP listener configuration.
+     * `params`: The parameters replaced in the data.
+     *
+     * # Returns
+     * The created `AppUdpListener`.
+     */
+    pub fn new(udp_listener: &UdpListenerData, params: Vec<(String, String)>) -> Self {
+        AppUdpListener { udp_listener: udp_listener.clone(), params }
+    }
+
+    /**
+     * Start the listener. Each datagram is answered in its own task so delayed responses do not hold back other datagrams.
+     *
+     * # Returns
+     * Ok if the listener was started.
+     *
+     * # Errors
+     * An error if the socket could not be bound or the rules or data are invalid.
+     */
+    pub async fn start_listener(&self) -> Result<(), ApplicationError> {
+        let responder = Arc::new(self.compile_responder()?);
+        let socket = Arc::new(
+            UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], self.udp_listener.port))).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to create udp listener: {err}")))?,
+        );
+        info!("Listening on udp: {}", self.udp_listener.port);
+
+        tokio::spawn(async move {
+            let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+            loop {
+                let (read, peer) = match socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(err) => {
+                        error!("Failed to receive datagram: {err}");
+                        continue;
+                    }
+                };
+                let datagram = buffer[..read].to_vec();
+                let socket = socket.clone();
+                let responder = responder.clone();
+                tokio::spawn(async move {
+                    let _ = Self::respond(&socket, peer, &datagram, &responder).await.map_err(|err| {
+                        error!("Error responding to datagram from {peer}: {err}");
+                    });
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /**
+     * Compile the rules and data.
+     *
+     * # Returns
+     * The compiled responder.
+     *
+     * # Errors
+     * An error if the rules or data are invalid, or the drop probability is outside 0.0 to 1.0.
+     */
+    fn compile_responder(&self) -> Result<Responder, ApplicationError> {
+        check_probability(self.udp_listener.drop_probability, "Drop probability")?;
+        let response = match &self.udp_listener.data {
+            Some(data) => Some(Payload::Bytes(decode_data(data, &self.udp_listener.data_encoding, &self.params)?)),
+            None => self.udp_listener.file.clone().map(Payload::File),
+        };
+        Ok(Responder {
+            rules: compile_udp_rules(self.udp_listener.rules.as_deref().unwrap_or_default(), &self.params)?,
+            response,
+            delay_write_ms: self.udp_listener.delay_write_ms,
+            drop_probability: self.udp_listener.drop_probability,
+        })
+    }
+
+    /**
+     * Log the datagram and send the response to the peer. The response of the first rule matching the datagram is used.
+     * Otherwise the data or file is used. Nothing is sent if the datagram is dropped.
+     *
+     * # Arguments
+     * `socket`: The socket.
+     * `peer`: The address the datagram was received from.
+     * `datagram`: The datagram.
+     * `responder`: The compiled responder.
+     *
+     * # Returns
+     * Ok if the datagram was handled.
+     *
+     * # Errors
+     * An error if the file could not be read or the response could not be sent.
+     */
+    async fn respond(socket: &UdpSocket, peer: SocketAddr, datagram: &[u8], responder: &Responder) -> Result<(), ApplicationError> {
+        info!("Datagram from {peer}");
+        log_received(datagram);
+        if responder.drop_probability.is_some_and(|drop_probability| rand::random::<f64>() < drop_probability) {
+            info!("Dropping datagram from {peer}");
+            return Ok(());
+        }
+        let rule = find_rule(&responder.rules, datagram);
+        let Some(response) = rule.map_or(responder.response.as_ref(), |rule| rule.response.as_ref()) else {
+            return Ok(());
+        };
+        if let Some(delay_write_ms) = responder.delay_write_ms {
+            tokio::time::sleep(Duration::from_millis(delay_write_ms)).await;
+        }
+        let data = response.load().await?;
+        info!("Sending: {:?}", String::from_utf8_lossy(&data));
+        socket.send_to(&data, peer).await.map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to send datagram: {err}")))?;
+        Ok(())
+    }
+}
+
+impl StartableServer for AppUdpListener {
+    fn start_server(&mut self) -> Result<Vec<JoinHandle<()>>, ApplicationError> {
+        let udp_listener_data = self.udp_listener.clone();
+        let params = self.params.clone();
+        let handle = tokio::spawn(async move {
+            let listener = AppUdpListener { udp_listener: udp_listener_data, params };
+            let _ = listener.start_listener().await.map_err(|err| {
+                error!("Failed to start udp listener: {err}");
+            });
+        });
+        Ok(vec![handle])
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use apinae_lib::config::{TcpResponseConfiguration, UdpRuleConfiguration};
+
+    use super::*;
+
+    /**
+     * Send the datagram and wait for a response.
+     */
+    async fn request(port: u16, datagram: &[u8]) -> Option<Vec<u8>> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.send_to(datagram, ("127.0.0.1", port)).await.unwrap();
+        let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+        let read = tokio::time::timeout(Duration::from_millis(300), socket.recv(&mut buffer)).await.ok()?.unwrap();
+        Some(buffer[..read].to_vec())
+    }
+
+    /**
+     * Verify that datagrams are answered with the matching rule or the data.
+     */
+    #[tokio::test]
+    async fn test_respond() {
+        let mut udp_listener = UdpListenerData::new(None, Some("${name}".to_string()), Some(10), 18_044).unwrap();
+        udp_listener.rules = Some(vec![
+            UdpRuleConfiguration::new(Some("^stats".to_string()), None, None),
+            UdpRuleConfiguration::new(None, Some("01 02".to_string()), Some(TcpResponseConfiguration::new(None, Some("03".to_string()), None))),
+        ]);
+        AppUdpListener::new(&udp_listener, vec![("name".to_string(), "pong".to_string())]).start_listener().await.unwrap();
+        assert_eq!(request(18_044, b"ping").await, Some(b"pong".to_vec()));
+        assert_eq!(request(18_044, b"\x00\x01\x02").await, Some(vec![0x03]));
+        assert_eq!(request(18_044, b"stats.count:1|c").await, None);
+    }
+
+    /**
+     * Verify that dropped datagrams are not answered.
+     */
+    #[tokio::test]
+    async fn test_drop() {
+        let mut udp_listener = UdpListenerData::new(None, Some("pong".to_string()), None, 18_045).unwrap();
+        udp_listener.drop_probability = Some(1.0);
+        AppUdpListener::new(&udp_listener, Vec::new()).start_listener().await.unwrap();
+        assert_eq!(request(18_045, b"ping").await, None);
+        udp_listener.drop_probability = Some(1.5);
+        assert!(AppUdpListener::new(&udp_listener, Vec::new()).start_listener().await.is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::os::unix::{
+    fs::{FileTypeExt, PermissionsExt},
+    net::{UnixListener, UnixStream},
+};
+
+use actix_http::Protocol;
+use apinae_lib::{config::UnixSocketConfiguration, error::ApplicationError};
+use tokio::io::AsyncReadExt;
+
+use super::prefixed::PrefixedStream;
+
+/**
+ * The start of the HTTP/2 connection preface sent by a client with prior knowledge.
+ */
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2";
+
+/**
+ * Bind the unix socket. A socket file nobody listens on is removed first. The permissions are set after binding.
+ *
+ * # Arguments
+ * `unix_socket`: The unix socket configuration.
+ *
+ * # Returns
+ * The non-blocking listener.
+ *
+ * # Errors
+ * An error if the mode is invalid, the path is in use or is not a socket, or the socket could not be bound.
+ */
+pub fn bind_unix_socket(unix_socket: &UnixSocketConfiguration) -> Result<UnixListener, ApplicationError> {
+    let path = &unix_socket.path;
+    let mode = unix_socket
+        .mode
+        .as_ref()
+        .map(|mode| u32::from_str_radix(mode, 8).map_err(|err| ApplicationError::ConfigurationError(format!("Invalid mode {mode} for unix socket {path}: {err}"))))
+        .transpose()?;
+    remove_stale_socket(path)?;
+    let map_err = |err: std::io::Error| ApplicationError::ServerStartUpError(format!("Failed to bind unix socket {path}: {err}"));
+    let listener = UnixListener::bind(path).map_err(map_err)?;
+    listener.set_nonblocking(true).map_err(map_err)?;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).map_err(map_err)?;
+    }
+    log::info!("Listening on unix socket: {path}");
+    Ok(listener)
+}
+
+/**
+ * Remove the socket file if nobody listens on it.
+ *
+ * # Arguments
+ * `path`: The path of the socket file.
+ *
+ * # Errors
+ * An error if the path is in use, is not a socket or could not be removed.
+ */
+fn remove_stale_socket(path: &str) -> Result<(), ApplicationError> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(ApplicationError::ServerStartUpError(format!("{path} exists and is not a unix socket")));
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(ApplicationError::ServerStartUpError(format!("Unix socket {path} is in use")));
+    }
+    log::info!("Removing stale unix socket: {path}");
+    std::fs::remove_file(path).map_err(|err| ApplicationError::ServerStartUpError(format!("Failed to remove stale unix socket {path}: {err}")))
+}
+
+/**
+ * Detect the protocol of a connection on the unix socket from the connection preface.
+ * The bytes read are returned first when reading from the returned stream.
+ *
+ * # Arguments
+ * `stream`: The accepted connection.
+ *
+ * # Returns
+ * The stream and HTTP/2 if the client sent the HTTP/2 preface, otherwise HTTP/1.
+ *
+ * # Errors
+ * An error if the connection could not be read.
+ */
+pub async fn detect_protocol(mut stream: actix_web::rt::net::UnixStream) -> std::io::Result<(PrefixedStream<actix_web::rt::net::UnixStream>, Protocol)> {
+    let mut preface = Vec::with_capacity(H2_PREFACE.len());
+    while preface.len() < H2_PREFACE.len() && H2_PREFACE.starts_with(&preface) {
+        let mut buffer = [0u8; H2_PREFACE.len()];
+        let read = stream.read(&mut buffer[..H2_PREFACE.len() - preface.len()]).await?;
+        if read == 0 {
+            break;
+        }
+        preface.extend_from_slice(&buffer[..read]);
+    }
+    let protocol = if preface == H2_PREFACE { Protocol::Http2 } else { Protocol::Http1 };
+    Ok((PrefixedStream::new(preface, stream), protocol))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /**
+     * Verify that a stale socket is replaced, a socket in use is kept and the mode is set.
+     */
+    #[test]
+    fn test_bind_unix_socket() {
+        let path = std::env::temp_dir().join(format!("apinae-unix-{}.sock", std::process::id()));
+        let unix_socket = UnixSocketConfiguration::new(path.to_string_lossy().to_string(), Some("600".to_string()));
+        drop(bind_unix_socket(&unix_socket).unwrap());
+        let listener = bind_unix_socket(&unix_socket).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(bind_unix_socket(&unix_socket).is_err());
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&path, "").unwrap();
+        assert!(bind_unix_socket(&unix_socket).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(bind_unix_socket(&UnixSocketConfiguration::new(path.to_string_lossy().to_string(), Some("9".to_string()))).is_err());
+    }
+
+    /**
+     * Verify that the protocol is detected from the preface and the bytes read are kept.
+     */
+    #[tokio::test]
+    async fn test_detect_protocol() {
+        use tokio::io::AsyncWriteExt;
+        for (request, expected) in [(&b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n"[..], Protocol::Http2), (&b"GET / HTTP/1.1\r\n\r\n"[..], Protocol::Http1), (&b"PR"[..], Protocol::Http1)] {
+            let (mut client, server) = actix_web::rt::net::UnixStream::pair().unwrap();
+            client.write_all(request).await.unwrap();
+            client.shutdown().await.unwrap();
+            let (mut stream, protocol) = detect_protocol(server).await.unwrap();
+            assert_eq!(protocol, expected);
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await.unwrap();
+            assert_eq!(received, request);
+        }
+    }
+}
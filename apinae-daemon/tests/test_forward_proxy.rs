@@ -0,0 +1,41 @@
+use tokio::process::Command;
+
+mod common;
+
+/**
+ * Initalizes a forward proxy intercepting example.com and a target server with http and https.
+ * Requests through the proxy with curl and verifies that matching endpoints are mocked and the rest is passed through.
+ */
+#[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+async fn test_forward_proxy() {
+    // Start the server. Allow zombie process as it's a daemon running.
+    #![allow(clippy::zombie_processes)]
+    let mut server_command = common::start_server("./tests/resources/test_forward_proxy.json", "1").await.expect("Failed to start server");
+    // Run curl through the proxy and collect the bodies and status codes.
+    let intercepted = curl(&["--cacert", "../target/test-proxy-ca/ca_cert.pem", "https://example.com/test"]).await;
+    let mocked = curl(&["http://localhost:8202/mock"]).await;
+    let passed_through = curl(&["http://localhost:8202/passthrough"]).await;
+    let tunneled = curl(&["--insecure", "https://localhost:8203/passthrough"]).await;
+    let not_implemented = curl(&["http://localhost:8202/missing"]).await;
+    // Stop the server.
+    server_command.kill().expect("Failed to kill process");
+    // Verify the output.
+    assert_eq!(intercepted, (Some(0), "Intercepted 200".to_string()));
+    assert_eq!(mocked, (Some(0), "Mocked 200".to_string()));
+    assert_eq!(passed_through, (Some(0), "Target 200".to_string()));
+    assert_eq!(tunneled, (Some(0), "Target 200".to_string()));
+    assert_eq!(not_implemented, (Some(0), "Not implemented 501".to_string()));
+}
+
+/**
+ * Run curl through the proxy with the arguments and return the exit code and the body followed by the status code.
+ */
+async fn curl(args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new("curl")
+        .args(["--silent", "--max-time", "3", "--noproxy", "", "--proxy", "http://localhost:8201", "--write-out", " %{http_code}"])
+        .args(args)
+        .output()
+        .await
+        .expect("Failed to execute curl command");
+    (output.status.code(), String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,47 @@
+use tokio::process::Command;
+
+mod common;
+
+/**
+ * Initalizes servers with h2c, HTTP/1 and h2c on a port and a unix socket, https with h2 and https without h2.
+ * Requests the servers with curl and verifies the negotiated protocol and the HTTP/2 faults.
+ */
+#[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+async fn test_http2_server() {
+    // Start the server. Allow zombie process as it's a daemon running.
+    #![allow(clippy::zombie_processes)]
+    let mut server_command = common::start_server("./tests/resources/test_http2.json", "1").await.expect("Failed to start server");
+    // Run curl and collect the protocol versions, status codes and exit codes.
+    let h2c = curl(&["--http2-prior-knowledge", "http://localhost:8197/test"]).await;
+    let h2c_http1 = curl(&["http://localhost:8197/test"]).await;
+    let both_http1 = curl(&["http://localhost:8198/test"]).await;
+    let both_h2c = curl(&["--http2-prior-knowledge", "http://localhost:8198/test"]).await;
+    let unix_http1 = curl(&["--unix-socket", "/tmp/apinae-test-http2.sock", "http://localhost/test"]).await;
+    let unix_h2c = curl(&["--unix-socket", "/tmp/apinae-test-http2.sock", "--http2-prior-knowledge", "http://localhost/test"]).await;
+    let https_h2 = curl(&["--http2", "--insecure", "https://localhost:8199/test"]).await;
+    let https_http1 = curl(&["--http2", "--insecure", "https://localhost:8200/test"]).await;
+    let rst_stream = curl(&["--http2-prior-knowledge", "http://localhost:8197/rst"]).await;
+    let go_away = curl(&["--http2-prior-knowledge", "http://localhost:8197/goaway"]).await;
+    // Stop the server.
+    server_command.kill().expect("Failed to kill process");
+    // Verify the output. 92 is a HTTP/2 stream error and 16 is a HTTP/2 framing error.
+    assert_eq!(h2c, (Some(0), "OK 2 200".to_string()));
+    assert_eq!(h2c_http1, (Some(0), " 1.1 505".to_string()));
+    assert_eq!(both_http1, (Some(0), "OK 1.1 200".to_string()));
+    assert_eq!(both_h2c, (Some(0), "OK 2 200".to_string()));
+    assert_eq!(unix_http1, (Some(0), "OK 1.1 200".to_string()));
+    assert_eq!(unix_h2c, (Some(0), "OK 2 200".to_string()));
+    assert_eq!(https_h2, (Some(0), "OK 2 200".to_string()));
+    assert_eq!(https_http1, (Some(0), "OK 1.1 200".to_string()));
+    assert_eq!(rst_stream.0, Some(92));
+    assert_eq!(go_away.0, Some(16));
+}
+
+/**
+ * Run curl with the arguments and return the exit code and the body followed by the protocol version and status code.
+ */
+async fn curl(args: &[&str]) -> (Option<i32>, String) {
+    let output =
+        Command::new("curl").arg("--silent").arg("--max-time").arg("3").arg("--write-out").arg(" %{http_version} %{http_code}").args(args).output().await.expect("Failed to execute curl command");
+    (output.status.code(), String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,44 @@
+use tokio::process::Command;
+
+mod common;
+
+/**
+ * Initalizes the server for http with faults injected on the endpoints.
+ * Requests the server with curl and verifies the curl exit codes.
+ */
+#[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+async fn test_http_fault() {
+    // Start the server. Allow zombie process as it's a daemon running.
+    #![allow(clippy::zombie_processes)]
+    let mut server_command = common::start_server("./tests/resources/test_http_fault.json", "1").await.expect("Failed to start server");
+    // Run curl and collect the exit codes. 52 is empty reply, 56 is failure receiving data and 18 is partial file.
+    let empty = curl_exit_code("http://localhost:8080/empty").await;
+    let reset = curl_exit_code("http://localhost:8080/reset").await;
+    let malformed = curl_exit_code("http://localhost:8080/malformed").await;
+    let headers = curl_exit_code("http://localhost:8080/headers").await;
+    let never = curl_exit_code("http://localhost:8080/never").await;
+    let random = curl_output_length("http://localhost:8080/random").await;
+    // Stop the server.
+    server_command.kill().expect("Failed to kill process");
+    // Verify the exit codes.
+    assert_eq!(empty, Some(52));
+    assert_eq!(reset, Some(56));
+    assert_ne!(malformed, Some(0));
+    assert_eq!(headers, Some(18));
+    assert_eq!(never, Some(0));
+    assert_eq!(random, 64);
+}
+
+/**
+ * Run curl against the url and return the exit code.
+ */
+async fn curl_exit_code(url: &str) -> Option<i32> {
+    Command::new("curl").arg("--silent").arg("--max-time").arg("3").arg(url).output().await.ok().and_then(|output| output.status.code())
+}
+
+/**
+ * Run curl against the url allowing a response without a status line and return the number of bytes received.
+ */
+async fn curl_output_length(url: &str) -> usize {
+    Command::new("curl").arg("--silent").arg("--http0.9").arg("--max-time").arg("3").arg(url).output().await.map_or(0, |output| output.stdout.len())
+}
@@ -1,5 +1,7 @@
 use std::process::{Child, Command};
 
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
 mod common;
 
 /**
@@ -13,10 +15,19 @@ async fn test_tcp_listener() {
 
     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
     // Assert the server response.
-    assert_command(&mut server_command, "8180", "Test").await;
-    assert_command(&mut server_command, "8181", "Testing This File").await;
-    assert_command(&mut server_command, "8182", "").await;
-    assert_command(&mut server_command, "8183", "").await;
+    assert_command(&mut server_command, "http://localhost:8180", "Test").await;
+    assert_command(&mut server_command, "http://localhost:8181", "Testing This File").await;
+    assert_command(&mut server_command, "http://localhost:8182", "").await;
+    assert_command(&mut server_command, "http://localhost:8183", "").await;
+    assert_command(&mut server_command, "https://localhost:8185", "Test").await;
+    assert_command(&mut server_command, "http://localhost:8186", "Scripted").await;
+    assert_command(&mut server_command, "http://localhost:8187/rule", "Rule").await;
+    assert_command(&mut server_command, "http://localhost:8187/other", "Unknown").await;
+    assert_command(&mut server_command, "http://localhost:8188", "Base64").await;
+    assert_command(&mut server_command, "http://localhost:8189", "Te").await;
+    Command::new("curl").arg("-X").arg("POST").arg("--max-time").arg("3").arg("http://localhost:8190/toxics/limit/disable").output().expect("Failed to disable toxic");
+    assert_command(&mut server_command, "http://localhost:8189", "Test").await;
+    assert_refused(&mut server_command, "127.0.0.1:8191").await;
 
     // Stop the server.
     server_command.kill().expect("Failed to kill process");
@@ -25,9 +36,8 @@ async fn test_tcp_listener() {
 /**
  * Asserts the server response.
  */
-async fn assert_command(server_command: &mut Child, port: &str, expected: &str) {
-    let connect = format!("http://localhost:{port}");
-    let nc_command = match Command::new("curl").arg("--http0.9").arg("-X").arg("GET").arg("--max-time").arg("3").arg(connect).output() {
+async fn assert_command(server_command: &mut Child, connect: &str, expected: &str) {
+    let nc_command = match Command::new("curl").arg("--http0.9").arg("--insecure").arg("-X").arg("GET").arg("--max-time").arg("3").arg(connect).output() {
         Ok(nc_command) => nc_command,
         Err(error) => {
             server_command.kill().expect("Failed to kill server process");
@@ -42,3 +52,25 @@ async fn assert_command(server_command: &mut Child, port: &str, expected: &str)
         panic!("Expected: {expected}, Got: {output_string}");
     }
 }
+
+/**
+ * Asserts that a connection is reset while another connection is held open, and that a connection is held again after it is closed.
+ */
+async fn assert_refused(server_command: &mut Child, address: &str) {
+    let mut held = tokio::net::TcpStream::connect(address).await.expect("Failed to connect");
+    held.write_all(b"Unread").await.expect("Failed to write");
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    let mut refused = tokio::net::TcpStream::connect(address).await.expect("Failed to connect");
+    let mut buffer = [0; 1];
+    if refused.read(&mut buffer).await.is_ok() {
+        server_command.kill().expect("Failed to kill server process");
+        panic!("Expected connection reset");
+    }
+    drop(held);
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let mut accepted = tokio::net::TcpStream::connect(address).await.expect("Failed to connect");
+    if tokio::time::timeout(tokio::time::Duration::from_millis(500), accepted.read(&mut buffer)).await.is_ok() {
+        server_command.kill().expect("Failed to kill server process");
+        panic!("Expected connection to be held");
+    }
+}
@@ -0,0 +1,38 @@
+use std::process::Command;
+
+mod common;
+
+/**
+ * Imports an OpenAPI document with the import command and starts the generated setup.
+ * Requests the server with curl and verifies the example, the synthesized response and the empty response.
+ */
+#[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+async fn test_openapi_import() {
+    // Import the document to a new configuration file.
+    #![allow(clippy::zombie_processes)]
+    let output = "../target/test_openapi_import.json";
+    let _ = std::fs::remove_file(output);
+    let status =
+        Command::new("../target/debug/apinae-daemon").args(["import", "--openapi", "./tests/resources/test_openapi.yaml", "--output", output, "--port", "8204"]).status().expect("Failed to import");
+    assert!(status.success());
+    let config = apinae_lib::config::AppConfiguration::load(output).expect("Failed to load configuration");
+    // Start the server with the imported setup.
+    let mut server_command = common::start_server(output, &config.setups[0].id).await.expect("Failed to start server");
+    let pets = curl(&["http://localhost:8204/v1/pets?limit=1"]);
+    let pet = curl(&["http://localhost:8204/v1/pets/3"]);
+    let deleted = curl(&["--request", "DELETE", "http://localhost:8204/v1/pets/3"]);
+    // Stop the server.
+    server_command.kill().expect("Failed to kill process");
+    // Verify the output.
+    assert_eq!(pets, "[\n  {\n    \"id\": 1,\n    \"name\": \"Rex\"\n  }\n] 200");
+    assert_eq!(pet, "{\n  \"id\": 0,\n  \"name\": \"string\"\n} 200");
+    assert_eq!(deleted, " 204");
+}
+
+/**
+ * Run curl with the arguments and return the body followed by the status code.
+ */
+fn curl(args: &[&str]) -> String {
+    let output = Command::new("curl").args(["--silent", "--max-time", "3", "--write-out", " %{http_code}"]).args(args).output().expect("Failed to execute curl command");
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
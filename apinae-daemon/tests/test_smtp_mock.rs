@@ -0,0 +1,47 @@
+use std::process::{Child, Command};
+
+mod common;
+
+/**
+ * Initalizes the smtp servers.
+ * Sends mail with curl and verifies that it is accepted, rejected or captured.
+ */
+#[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+async fn test_smtp_server() {
+    let _ = std::fs::remove_dir_all("../target/test-smtp-capture");
+    // Start the server.
+    let mut server_command = common::start_server("./tests/resources/test_smtp.json", "1").await.expect("Failed to start server");
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    // Assert the mail is accepted or rejected.
+    assert_mail(&mut server_command, &["smtp://localhost:8195", "--mail-rcpt", "receiver@test"], true);
+    assert_mail(&mut server_command, &["smtp://localhost:8195", "--mail-rcpt", "blocked@test"], false);
+    assert_mail(&mut server_command, &["smtp://localhost:8196", "--mail-rcpt", "receiver@test"], false);
+    assert_mail(&mut server_command, &["smtp://localhost:8196", "--mail-rcpt", "receiver@test", "--ssl-reqd", "--user", "user:secret"], true);
+
+    // Assert the mail is captured.
+    let captured = std::fs::read_dir("../target/test-smtp-capture")
+        .expect("Failed to read capture directory")
+        .map(|entry| std::fs::read_to_string(entry.expect("Failed to read entry").path()).expect("Failed to read mail"))
+        .collect::<Vec<String>>();
+    server_command.kill().expect("Failed to kill process");
+    assert_eq!(captured.len(), 1);
+    assert!(captured[0].contains("X-Envelope-To: receiver@test") && captured[0].contains("Testing mail"));
+}
+
+/**
+ * Sends the mail and asserts that it is accepted.
+ */
+fn assert_mail(server_command: &mut Child, args: &[&str], accepted: bool) {
+    let curl_command = match Command::new("curl").args(["--insecure", "--max-time", "3", "--mail-from", "sender@test", "--upload-file", "./tests/resources/test_mail.txt"]).args(args).output() {
+        Ok(curl_command) => curl_command,
+        Err(error) => {
+            server_command.kill().expect("Failed to kill server process");
+            panic!("Failed to execute curl command: {error}");
+        }
+    };
+    if curl_command.status.success() != accepted {
+        server_command.kill().expect("Failed to kill server process");
+        panic!("Expected accepted {accepted} for {args:?}, Got: {}", String::from_utf8_lossy(&curl_command.stderr));
+    }
+}
@@ -331,6 +331,15 @@ pub struct SetupConfiguration {
     pub servers: Vec<ServerConfiguration>,
     // TCP listeners
     pub listeners: Vec<TcpListenerData>,
+    // UDP listeners
+    #[serde(default)]
+    pub udp_listeners: Vec<UdpListenerData>,
+    // DNS servers
+    #[serde(default)]
+    pub dns_servers: Vec<DnsServerConfiguration>,
+    // SMTP servers
+    #[serde(default)]
+    pub smtp_servers: Vec<SmtpServerConfiguration>,
     // The parameters to pass to the setup.
     pub params: Option<HashSet<String>>,
     // Predefined sets of parameters.
@@ -339,7 +348,7 @@ pub struct SetupConfiguration {
 
 impl SetupConfiguration {
     /**
-     * Create a new test configuration.
+     * Create a new test configuration. There are no UDP listeners, DNS servers or SMTP servers.
      *
      * `name` The name of the setup.
      * `description` The description of the setup.
@@ -353,7 +362,7 @@ impl SetupConfiguration {
      */
     pub fn new(name: String, description: String, servers: Vec<ServerConfiguration>, listeners: Vec<TcpListenerData>, params: Option<HashSet<String>>, predefined_params: Option<Vec<PredefinedSet>>) -> Result<Self, ApplicationError> {
         let id = get_identifier()?;
-        Ok(SetupConfiguration { id, name, description, servers, listeners, params, predefined_params })
+        Ok(SetupConfiguration { id, name, description, servers, listeners, udp_listeners: Vec::new(), dns_servers: Vec::new(), smtp_servers: Vec::new(), params, predefined_params })
     }
 
     /**
@@ -556,8 +565,10 @@ impl PredefinedSet {
 #[serde(rename_all = "camelCase")]
 pub struct HttpsConfiguration {
     // The path to the certificate.
+    #[serde(default)]
     pub server_certificate: String,
     // The path to the private key.
+    #[serde(default)]
     pub private_key: String,
     // The https port
     pub https_port: u16,
@@ -566,6 +577,13 @@ pub struct HttpsConfiguration {
     // The supported versions. Only 1.2 and 1.3 are supported.
     #[serde(default = "default_server_supported_tls_versions")]
     pub supported_tls_versions: Vec<TlsVersion>,
+    // Certificates selected by the SNI hostname. The server certificate is used if no hostname matches.
+    pub sni_certificates: Option<Vec<SniCertificateConfiguration>>,
+    // Generate a local CA, server and client certificates instead of using the configured files.
+    pub auto_generate: Option<CertificateGenerationConfiguration>,
+    // Offer h2 with ALPN. Only HTTP/1.1 is negotiated if false.
+    #[serde(default = "default_as_true")]
+    pub http2: bool,
 }
 
 impl HttpsConfiguration {
@@ -576,14 +594,172 @@ impl HttpsConfiguration {
      * `private_key` The path to the private key.
      * `https_port` The https port.
      *
-     * The https configuration.
+     * The https configuration. No SNI certificates are used, no certificates are generated and h2 is offered.
      */
     #[must_use]
     pub fn new(server_certificate: String, private_key: String, https_port: u16, client_certificate: Option<String>, supported_tls_versions: Vec<TlsVersion>) -> Self {
-        HttpsConfiguration { server_certificate, private_key, https_port, client_certificate, supported_tls_versions }
+        HttpsConfiguration { server_certificate, private_key, https_port, client_certificate, supported_tls_versions, sni_certificates: None, auto_generate: None, http2: true }
+    }
+}
+
+/**
+ * Generation of a local CA and certificates signed by it for an https server.
+ * The files are only generated if they do not exist, so clients can keep trusting the CA between restarts.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateGenerationConfiguration {
+    // The directory the files are written to. Relative to the configuration file. Defaults to the directory of the configuration file.
+    pub directory: Option<String>,
+    // The subject alternative names of the server certificate. Defaults to localhost and 127.0.0.1.
+    #[serde(default)]
+    pub subject_alt_names: Vec<String>,
+    // Require clients to present a certificate signed by the generated CA.
+    #[serde(default)]
+    pub client_authentication: bool,
+}
+
+impl CertificateGenerationConfiguration {
+    /**
+     * Create a new certificate generation configuration.
+     *
+     * `directory` The directory the files are written to.
+     * `subject_alt_names` The subject alternative names of the server certificate.
+     * `client_authentication` Require clients to present a certificate signed by the generated CA.
+     *
+     * The certificate generation configuration.
+     */
+    #[must_use]
+    pub fn new(directory: Option<String>, subject_alt_names: Vec<String>, client_authentication: bool) -> Self {
+        CertificateGenerationConfiguration { directory, subject_alt_names, client_authentication }
+    }
+}
+
+/**
+ * A certificate used by an https server when the client requests the hostname with SNI.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SniCertificateConfiguration {
+    // The hostname. A leading `*.` matches any single label.
+    pub hostname: String,
+    // The path to the certificate.
+    pub server_certificate: String,
+    // The path to the private key.
+    pub private_key: String,
+}
+
+impl SniCertificateConfiguration {
+    /**
+     * Create a new SNI certificate configuration.
+     *
+     * `hostname` The hostname.
+     * `server_certificate` The path to the certificate.
+     * `private_key` The path to the private key.
+     *
+     * The SNI certificate configuration.
+     */
+    #[must_use]
+    pub fn new(hostname: String, server_certificate: String, private_key: String) -> Self {
+        SniCertificateConfiguration { hostname, server_certificate, private_key }
+    }
+}
+
+/**
+ * A unix domain socket. A stale socket file left by a stopped process is removed before binding.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UnixSocketConfiguration {
+    // The path of the socket file.
+    pub path: String,
+    // The permissions of the socket file in octal, like 660. The umask is used if not set.
+    pub mode: Option<String>,
+}
+
+impl UnixSocketConfiguration {
+    /**
+     * Create a new unix socket configuration.
+     *
+     * `path` The path of the socket file.
+     * `mode` The permissions of the socket file in octal.
+     *
+     * The unix socket configuration.
+     */
+    #[must_use]
+    pub fn new(path: String, mode: Option<String>) -> Self {
+        UnixSocketConfiguration { path, mode }
+    }
+}
+
+/**
+ * A forward proxy. Clients send absolute-form requests and CONNECT tunnels to the port. Requests matching an endpoint
+ * are handled by the endpoint and the others are passed through to the target.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardProxyConfiguration {
+    // The port the proxy listens on.
+    pub port: u16,
+    // TLS interception of the CONNECT tunnels. The tunnels are passed through unchanged if not set.
+    pub intercept: Option<InterceptConfiguration>,
+}
+
+impl ForwardProxyConfiguration {
+    /**
+     * Create a new forward proxy configuration.
+     *
+     * `port` The port the proxy listens on.
+     * `intercept` TLS interception of the CONNECT tunnels.
+     *
+     * The forward proxy configuration.
+     */
+    #[must_use]
+    pub fn new(port: u16, intercept: Option<InterceptConfiguration>) -> Self {
+        ForwardProxyConfiguration { port, intercept }
+    }
+}
+
+/**
+ * TLS interception of CONNECT tunnels. The certificates of the hosts are signed by a generated CA the clients must trust.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InterceptConfiguration {
+    // The directory of the generated CA. Relative to the configuration file. Defaults to the directory of the configuration file.
+    pub directory: Option<String>,
+    // Regular expressions for the intercepted hosts. All hosts are intercepted if not set.
+    pub hosts: Option<Vec<String>>,
+}
+
+impl InterceptConfiguration {
+    /**
+     * Create a new interception configuration.
+     *
+     * `directory` The directory of the generated CA.
+     * `hosts` Regular expressions for the intercepted hosts.
+     *
+     * The interception configuration.
+     */
+    #[must_use]
+    pub fn new(directory: Option<String>, hosts: Option<Vec<String>>) -> Self {
+        InterceptConfiguration { directory, hosts }
     }
 }
 
+/**
+ * The protocols served without TLS.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum HttpProtocol {
+    // HTTP/1.x only.
+    Http1,
+    // HTTP/2 with prior knowledge only. HTTP/1.x requests are answered with 505 HTTP Version Not Supported.
+    H2c,
+    // HTTP/1.x and HTTP/2 with prior knowledge. The protocol is detected from the connection preface.
+    Http1AndH2c,
+}
+
 /**
  * Configuration for a server.
  */
@@ -600,11 +776,20 @@ pub struct ServerConfiguration {
     pub endpoints: Vec<EndpointConfiguration>,
     // The https configuration.
     pub https_config: Option<HttpsConfiguration>,
+    // Rate limit for all requests to the server.
+    pub rate_limit: Option<RateLimitConfiguration>,
+    // Unix socket the server is served on with http in addition to the ports.
+    pub unix_socket: Option<UnixSocketConfiguration>,
+    // The protocols served on the http port.
+    #[serde(default = "default_http_protocol")]
+    pub http_protocol: HttpProtocol,
+    // Forward proxy handling the requests with the endpoints.
+    pub forward_proxy: Option<ForwardProxyConfiguration>,
 }
 
 impl ServerConfiguration {
     /**
-     * Create a new server configuration.
+     * Create a new server configuration. No rate limit, unix socket or forward proxy is used and only HTTP/1 is served on the http port.
      *
      * `name` The name of the server.
      * `port` The port to run the server on.
@@ -615,7 +800,7 @@ impl ServerConfiguration {
      */
     pub fn new(name: String, http_port: Option<u16>, endpoints: Vec<EndpointConfiguration>, https_config: Option<HttpsConfiguration>) -> Result<Self, ApplicationError> {
         let id = get_identifier()?;
-        Ok(ServerConfiguration { id, name, http_port, endpoints, https_config })
+        Ok(ServerConfiguration { id, name, http_port, endpoints, https_config, rate_limit: None, unix_socket: None, http_protocol: HttpProtocol::Http1, forward_proxy: None })
     }
 
     /**
@@ -657,17 +842,23 @@ pub struct EndpointConfiguration {
     pub id: String,
     // Path expression for the apinae API. This is a regular expression.
     pub path_expression: Option<String>,
+    // Host expression. This is a regular expression matched against the host without the port. For requests through the forward proxy it is the target host.
+    pub host_expression: Option<String>,
     // Body expression for the apinae API. This is a regular expression.
     pub body_expression: Option<String>,
     // The HTTP method.
     pub method: Option<String>,
     // Defines how the endpoint is to be handled.
     pub endpoint_type: Option<EndpointType>,
+    // Rate limit for requests to the endpoint.
+    pub rate_limit: Option<RateLimitConfiguration>,
+    // Matches the verified client certificate of https requests.
+    pub client_identity: Option<ClientIdentityConfiguration>,
 }
 
 impl EndpointConfiguration {
     /**
-     * Create a new endpoint configuration.
+     * Create a new endpoint configuration. No host expression, rate limit or client identity is used.
      *
      * `path_expression` Endpoint for the apinae API. This is a regular expression.
      * `body_expression` Body expression for the apinae API. This is a regular expression.
@@ -679,13 +870,116 @@ impl EndpointConfiguration {
      */
     pub fn new(path_expression: Option<String>, method: Option<String>, body_expression: Option<String>, endpoint_type: Option<EndpointType>) -> Result<Self, ApplicationError> {
         let id = get_identifier()?;
-        Ok(EndpointConfiguration { id, path_expression, body_expression, method, endpoint_type })
+        Ok(EndpointConfiguration { id, path_expression, host_expression: None, body_expression, method, endpoint_type, rate_limit: None, client_identity: None })
+    }
+}
+
+/**
+ * Matches the client certificate verified during the TLS handshake. All specified expressions must match.
+ * Requests without a client certificate never match.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientIdentityConfiguration {
+    // Regular expression for the common name of the subject.
+    pub subject_cn: Option<String>,
+    // Regular expression for a subject alternative name. Any DNS name, email, URI or IP address can match.
+    pub san: Option<String>,
+    // Regular expression for the issuer distinguished name. Example `CN=Test CA, O=Example`.
+    pub issuer: Option<String>,
+    // The SHA-256 fingerprint as hex. Case and colons are ignored.
+    pub fingerprint: Option<String>,
+}
+
+impl ClientIdentityConfiguration {
+    /**
+     * Create a new client identity configuration.
+     *
+     * `subject_cn` Regular expression for the common name of the subject.
+     * `san` Regular expression for a subject alternative name.
+     * `issuer` Regular expression for the issuer distinguished name.
+     * `fingerprint` The SHA-256 fingerprint as hex.
+     *
+     * The client identity configuration.
+     */
+    #[must_use]
+    pub fn new(subject_cn: Option<String>, san: Option<String>, issuer: Option<String>, fingerprint: Option<String>) -> Self {
+        ClientIdentityConfiguration { subject_cn, san, issuer, fingerprint }
+    }
+}
+
+/**
+ * The rate limit algorithm.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum RateLimitAlgorithm {
+    // Bucket holding up to limit tokens, refilled with limit tokens per period.
+    TokenBucket,
+    // At most limit requests in each period.
+    FixedWindow,
+}
+
+/**
+ * What the rate limit is counted by.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum RateLimitKey {
+    // One limit shared by all clients.
+    Global,
+    // One limit per value of the configured header.
+    Header,
+    // One limit per client ip address.
+    Ip,
+}
+
+/**
+ * Configuration for rate limiting.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitConfiguration {
+    // The algorithm to use.
+    pub algorithm: RateLimitAlgorithm,
+    // Number of requests allowed per period.
+    pub limit: u64,
+    // The period in milliseconds.
+    pub period_ms: u64,
+    // What the limit is counted by. Default is global.
+    #[serde(default = "default_rate_limit_key")]
+    pub key: RateLimitKey,
+    // The header name used when counting by header.
+    pub key_header: Option<String>,
+    // The status code returned when the limit is exceeded. Default is 429.
+    pub status: Option<String>,
+    // The headers returned when the limit is exceeded.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    // The response returned when the limit is exceeded.
+    pub response: Option<String>,
+}
+
+impl RateLimitConfiguration {
+    /**
+     * Create a new rate limit configuration. The default 429 response is used.
+     *
+     * `algorithm` The algorithm to use.
+     * `limit` Number of requests allowed per period.
+     * `period_ms` The period in milliseconds.
+     * `key` What the limit is counted by.
+     * `key_header` The header name used when counting by header.
+     *
+     * The rate limit configuration.
+     */
+    #[must_use]
+    pub fn new(algorithm: RateLimitAlgorithm, limit: u64, period_ms: u64, key: RateLimitKey, key_header: Option<String>) -> Self {
+        RateLimitConfiguration { algorithm, limit, period_ms, key, key_header, status: None, headers: HashMap::new(), response: None }
     }
 }
 
 /**
  * The type of the endpoint. Determines what type of handling to use.
  */
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub enum EndpointType {
@@ -732,6 +1026,21 @@ impl From<&str> for CloseConnectionWhen {
     }
 }
 
+/**
+ * How inline data is encoded.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum DataEncoding {
+    // UTF-8 text.
+    Text,
+    // Text with escape sequences like \n, \r, \t, \0, \\ and \xHH.
+    Escaped,
+    // Bytes as hex. Whitespace is ignored.
+    Hex,
+    // Bytes as standard base64.
+    Base64,
+}
+
 /**
  * Configuration for a tcp connection.
  */
@@ -742,8 +1051,11 @@ pub struct TcpListenerData {
     pub id: String,
     // The file to read from.
     pub file: Option<String>,
-    // The data to return. If this is set, the file will be ignored.
+    // The data to return. If this is set, the file will be ignored. Parameters are replaced before it is decoded.
     pub data: Option<String>,
+    // How the data is encoded. Default is Text.
+    #[serde(default = "default_data_encoding")]
+    pub data_encoding: DataEncoding,
     // Time to wait before writing the response.
     pub delay_write_ms: Option<u64>,
     // The port to listen on.
@@ -754,11 +1066,38 @@ pub struct TcpListenerData {
     // When to close the connection. Default is AfterResponse.
     #[serde(default = "default_close_connection_when")]
     pub close_connection: CloseConnectionWhen,
+    // Accept TLS connections instead of plain tcp.
+    pub tls: Option<TcpTlsConfiguration>,
+    // Conversation script. If this is set, data, file, delay and close connection are ignored.
+    pub script: Option<TcpScriptConfiguration>,
+    // Responses selected by matching each message. Data or file is returned if no rule matches.
+    pub rules: Option<Vec<TcpRuleConfiguration>>,
+    // How the received bytes are split into messages. If not set, each read is a message.
+    pub framing: Option<TcpFramingConfiguration>,
+    // Forward the connections to an upstream. If this is set, the responses, script and rules are ignored.
+    pub proxy: Option<TcpProxyConfiguration>,
+    // Close the connections with a reset (RST) instead of a graceful close. Default is false.
+    #[serde(default)]
+    pub reset_on_close: bool,
+    // The listen backlog. Default is 1024.
+    pub backlog: Option<u32>,
+    // Time to wait before accepting each connection.
+    pub delay_accept_ms: Option<u64>,
+    // Maximum number of open connections. Connections above the limit are accepted and reset.
+    pub max_connections: Option<usize>,
+    // Maximum number of connections handled at the same time. New connections wait in the backlog until a connection closes.
+    pub connection_limit: Option<usize>,
+    // Accept the connections and never read from them. They stay open until the client closes them. Default is false.
+    #[serde(default)]
+    pub never_read: bool,
+    // Unix socket to listen on instead of the port.
+    pub unix_socket: Option<UnixSocketConfiguration>,
 }
 
 impl TcpListenerData {
     /**
-     * Create a new tcp configuration.
+     * Create a new tcp configuration. The data is text. TLS, scripts, rules, framing and proxy are not used.
+     * Connections are accepted on the port without delay or limit and closed gracefully.
      *
      * The tcp configuration.
      * `file` The file to read from.
@@ -773,7 +1112,7 @@ impl TcpListenerData {
      */
     pub fn new(file: Option<String>, data: Option<String>, delay_write_ms: Option<u64>, port: u16, accept: bool, close_connection: CloseConnectionWhen) -> Result<Self, ApplicationError> {
         let id = get_identifier()?;
-        Ok(TcpListenerData { id, file, data, delay_write_ms, port, accept, close_connection })
+        Ok(TcpListenerData { id, file, data, data_encoding: DataEncoding::Text, delay_write_ms, port, accept, close_connection, tls: None, script: None, rules: None, framing: None, proxy: None, reset_on_close: false, backlog: None, delay_accept_ms: None, max_connections: None, connection_limit: None, never_read: false, unix_socket: None })
     }
 
     /**
@@ -797,151 +1136,1219 @@ impl TcpListenerData {
 }
 
 /**
- * Configuration for a mock response.
+ * TLS for a tcp listener. The fields are used as in the https configuration.
  */
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct MockResponseConfiguration {
-    // The response to return when the mock is called.
-    pub response: Option<String>,
-    // The status code to return when the mock is called.
-    pub status: String,
-    // The headers to return when the mock is called.
-    pub headers: HashMap<String, String>,
-    // Time to wait in milliseconds before returning the response.
-    pub delay: u64,
+pub struct TcpTlsConfiguration {
+    // The path to the certificate.
+    pub server_certificate: String,
+    // The path to the private key.
+    pub private_key: String,
+    // The path to the CA certificate used to verify client certificates.
+    pub client_certificate: Option<String>,
+    // The supported versions. Only 1.2 and 1.3 are supported.
+    #[serde(default = "default_server_supported_tls_versions")]
+    pub supported_tls_versions: Vec<TlsVersion>,
 }
 
-impl MockResponseConfiguration {
+impl TcpTlsConfiguration {
     /**
-     * Create a new mock response configuration.
+     * Create a new tcp TLS configuration.
      *
-     * `response` The response to return when the mock is called.
-     * `status` The status code to return when the mock is called.
-     * `headers` The headers to return when the mock is called.
-     * `delay` Time to wait before returning the response.
+     * `server_certificate` The path to the certificate.
+     * `private_key` The path to the private key.
+     * `client_certificate` The path to the CA certificate used to verify client certificates.
+     * `supported_tls_versions` The supported versions.
      *
-     * The mock response configuration.
+     * The tcp TLS configuration.
      */
     #[must_use]
-    pub fn new(response: Option<String>, status: String, headers: HashMap<String, String>, delay: u64) -> Self {
-        MockResponseConfiguration { response, status, headers, delay }
+    pub fn new(server_certificate: String, private_key: String, client_certificate: Option<String>, supported_tls_versions: Vec<TlsVersion>) -> Self {
+        TcpTlsConfiguration { server_certificate, private_key, client_certificate, supported_tls_versions }
     }
 }
 
 /**
- * The supported TLS versions.
+ * A conversation script for a tcp listener. The steps are run in order for each connection.
  */
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-pub enum TlsVersion {
-    TLSv1_0,
-    TLSv1_1,
-    TLSv1_2,
-    TLSv1_3,
+#[serde(rename_all = "camelCase")]
+pub struct TcpScriptConfiguration {
+    // The steps of the conversation.
+    pub steps: Vec<TcpScriptStep>,
+    // Used when an expect step gets unexpected input. If not set the connection is closed.
+    pub fallback: Option<TcpScriptFallback>,
 }
 
-impl From<TlsVersion> for String {
+impl TcpScriptConfiguration {
     /**
-     * Convert a `TlsVersion` to a string.
+     * Create a new tcp script configuration.
+     *
+     * `steps` The steps of the conversation.
+     * `fallback` Used when an expect step gets unexpected input.
+     *
+     * The tcp script configuration.
      */
-    fn from(version: TlsVersion) -> Self {
-        match version {
-            TlsVersion::TLSv1_0 => "TLSv1.0".to_string(),
-            TlsVersion::TLSv1_1 => "TLSv1.1".to_string(),
-            TlsVersion::TLSv1_2 => "TLSv1.2".to_string(),
-            TlsVersion::TLSv1_3 => "TLSv1.3".to_string(),
-        }
+    #[must_use]
+    pub fn new(steps: Vec<TcpScriptStep>, fallback: Option<TcpScriptFallback>) -> Self {
+        TcpScriptConfiguration { steps, fallback }
     }
 }
 
-impl From<String> for TlsVersion {
-    /**
-     * Convert a string to a `TlsVersion`. Used when converting to the file model.
-     */
-    fn from(version: String) -> Self {
-        let version = version.as_str();
-        match version {
-            "TLSv1.0" => TlsVersion::TLSv1_0,
-            "TLSv1.1" => TlsVersion::TLSv1_1,
-            "TLSv1.2" => TlsVersion::TLSv1_2,
-            _ => TlsVersion::TLSv1_3,
-        }
-    }
+/**
+ * The type of a tcp script step.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum TcpScriptStepType {
+    // Read until the input contains the data or hex bytes, or matches the regex.
+    Expect,
+    // Send the data, hex bytes or file.
+    Send,
+    // Wait delayMs milliseconds.
+    Wait,
+    // Close the connection.
+    Close,
+    // Continue from the target step.
+    Loop,
 }
 
 /**
- * Configuration for a route.
+ * A step in a tcp conversation script. The fields used depend on the step type.
  */
-#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct RouteConfiguration {
-    // The URL of the endpoint.
-    pub url: String,
-    // The proxy url. Example: `<http://localhost:8080>`
-    pub proxy_url: Option<String>,
-    // The log file where the requests and responses are stored.
-    //TODO: Unimplemented
-    pub log: Option<String>,
-    // HTTP/1 only
-    #[serde(default)]
-    pub http1_only: bool,
-    // Accept invalid certificates
-    #[serde(default)]
-    pub accept_invalid_certs: bool,
-    // Accept invalid hostnames
-    #[serde(default)]
-    pub accept_invalid_hostnames: bool,
-    // Minimum TLS version
-    pub min_tls_version: Option<TlsVersion>,
-    // Maximum TLS version
-    pub max_tls_version: Option<TlsVersion>,
-    // Read timeout
-    pub read_timeout: Option<u64>,
-    // Connect timeout
-    pub connect_timeout: Option<u64>,
-    // Delay before request in milliseconds
-    pub delay_before: Option<u64>,
-    // Delay after request in milliseconds
-    pub delay_after: Option<u64>,    
+pub struct TcpScriptStep {
+    // The type of step.
+    pub step_type: TcpScriptStepType,
+    // Text to expect or send.
+    pub data: Option<String>,
+    // Bytes as hex to expect or send. Whitespace is ignored.
+    pub hex: Option<String>,
+    // Regular expression the input must match. Used by expect.
+    pub regex: Option<String>,
+    // The file to send. Used by send.
+    pub file: Option<String>,
+    // Time to wait. Used by wait.
+    pub delay_ms: Option<u64>,
+    // Time to wait for the expected input. If not set, each read must match.
+    pub timeout_ms: Option<u64>,
+    // Index of the step to continue from. Used by loop. Default is 0.
+    pub target: Option<usize>,
 }
 
-impl RouteConfiguration {
+impl TcpScriptStep {
     /**
-     * Create a new route configuration.
+     * Create a new step. The fields used by the step type must be set afterwards.
      *
-     * `url` The URL of the endpoint. Example `<http://localhost:8080>`
-     * `proxy_url` Proxy to use.
-     * `log` The log file where the requests and responses are stored.
-     * `http1_only` HTTP/1 only
-     * `accept_invalid_certs` Accept invalid certificates
-     * `accept_invalid_hostnames` Accept invalid hostnames
-     * `min_tls_version` Minimum TLS version
-     * `max_tls_version` Maximum TLS version
-     * `read_timeout` Read timeout
-     * `connect_timeout` Connect timeout
-     * `delay_before` Delay before request in milliseconds
-     * `delay_after` Delay after request in milliseconds
+     * `step_type` The type of step.
      *
+     * The tcp script step.
      */
-    #[allow(clippy::too_many_arguments)]
-    #[allow(clippy::fn_params_excessive_bools)]
     #[must_use]
-    pub fn new(
-        url: String,
-        proxy_url: Option<String>,
-        log: Option<String>,
-        http1_only: bool,
-        accept_invalid_certs: bool,
-        accept_invalid_hostnames: bool,
-        min_tls_version: Option<TlsVersion>,
-        max_tls_version: Option<TlsVersion>,
-        read_timeout: Option<u64>,
-        connect_timeout: Option<u64>,
-        delay_before: Option<u64>,
-        delay_after: Option<u64>,
-    ) -> Self {
-        RouteConfiguration { url, proxy_url, log, http1_only, accept_invalid_certs, accept_invalid_hostnames, min_tls_version, max_tls_version, read_timeout, connect_timeout, delay_before, delay_after }
+    pub fn new(step_type: TcpScriptStepType) -> Self {
+        TcpScriptStep { step_type, data: None, hex: None, regex: None, file: None, delay_ms: None, timeout_ms: None, target: None }
+    }
+}
+
+/**
+ * The response to unexpected input in a tcp conversation script.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpScriptFallback {
+    // Text to send.
+    pub data: Option<String>,
+    // Bytes as hex to send.
+    pub hex: Option<String>,
+    // The file to send.
+    pub file: Option<String>,
+    // Close the connection after the response. Otherwise the unexpected input is discarded and the step is retried. Default is true.
+    #[serde(default = "default_as_true")]
+    pub close: bool,
+}
+
+impl TcpScriptFallback {
+    /**
+     * Create a new fallback.
+     *
+     * `data` Text to send.
+     * `hex` Bytes as hex to send.
+     * `file` The file to send.
+     * `close` Close the connection after the response.
+     *
+     * The tcp script fallback.
+     */
+    #[must_use]
+    pub fn new(data: Option<String>, hex: Option<String>, file: Option<String>, close: bool) -> Self {
+        TcpScriptFallback { data, hex, file, close }
+    }
+}
+
+/**
+ * A rule for a tcp listener. The response of the first rule matching a message is returned.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpRuleConfiguration {
+    // Regular expression the message must match.
+    pub regex: Option<String>,
+    // Bytes as hex the message must contain. Whitespace is ignored.
+    pub hex: Option<String>,
+    // The response. Nothing is sent if not set.
+    pub response: Option<TcpResponseConfiguration>,
+    // Close the connection after the response. Default is false.
+    #[serde(default)]
+    pub close: bool,
+}
+
+impl TcpRuleConfiguration {
+    /**
+     * Create a new tcp rule. A rule without regex and hex matches every message.
+     *
+     * `regex` Regular expression the message must match.
+     * `hex` Bytes as hex the message must contain.
+     * `response` The response.
+     * `close` Close the connection after the response.
+     *
+     * The tcp rule.
+     */
+    #[must_use]
+    pub fn new(regex: Option<String>, hex: Option<String>, response: Option<TcpResponseConfiguration>, close: bool) -> Self {
+        TcpRuleConfiguration { regex, hex, response, close }
+    }
+}
+
+/**
+ * A tcp response. Data is used before hex and hex before file.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpResponseConfiguration {
+    // Text to send.
+    pub data: Option<String>,
+    // Bytes as hex to send. Whitespace is ignored.
+    pub hex: Option<String>,
+    // The file to send.
+    pub file: Option<String>,
+}
+
+impl TcpResponseConfiguration {
+    /**
+     * Create a new tcp response.
+     *
+     * `data` Text to send.
+     * `hex` Bytes as hex to send.
+     * `file` The file to send.
+     *
+     * The tcp response.
+     */
+    #[must_use]
+    pub fn new(data: Option<String>, hex: Option<String>, file: Option<String>) -> Self {
+        TcpResponseConfiguration { data, hex, file }
+    }
+}
+
+/**
+ * How the received bytes are split into messages.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum TcpFramingType {
+    // Messages end with a newline. The newline and a preceding carriage return are removed.
+    Newline,
+    // Messages have a fixed length.
+    FixedLength,
+    // Messages start with an unsigned length header. The header is removed.
+    LengthPrefixed,
+    // Messages end with a delimiter byte. The delimiter is removed.
+    Delimiter,
+}
+
+/**
+ * Framing for a tcp listener. The fields used depend on the framing type.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpFramingConfiguration {
+    // The framing type.
+    pub framing_type: TcpFramingType,
+    // The message length. Used by fixed length.
+    pub length: Option<usize>,
+    // The header length in bytes, between 1 and 8. Used by length prefixed. Default is 2.
+    pub header_length: Option<usize>,
+    // The header is little endian. Used by length prefixed. Default is false.
+    #[serde(default)]
+    pub little_endian: bool,
+    // The delimiter byte. Used by delimiter.
+    pub delimiter: Option<u8>,
+    // The maximum message length in bytes. The connection is closed if a message is longer. Used by newline, delimiter and length prefixed. Default is 1 MiB.
+    pub max_length: Option<usize>,
+}
+
+impl TcpFramingConfiguration {
+    /**
+     * Create a new framing. The fields used by the framing type must be set afterwards.
+     *
+     * `framing_type` The framing type.
+     *
+     * The tcp framing.
+     */
+    #[must_use]
+    pub fn new(framing_type: TcpFramingType) -> Self {
+        TcpFramingConfiguration { framing_type, length: None, header_length: None, little_endian: false, delimiter: None, max_length: None }
+    }
+}
+
+/**
+ * Configuration for a tcp proxy. The toxics are applied in order to the data in each direction.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpProxyConfiguration {
+    // The upstream address. Example: localhost:5432.
+    pub upstream: String,
+    // Toxics for the data from the client to the upstream.
+    #[serde(default)]
+    pub upstream_toxics: Vec<ToxicConfiguration>,
+    // Toxics for the data from the upstream to the client.
+    #[serde(default)]
+    pub downstream_toxics: Vec<ToxicConfiguration>,
+    // Port of the http api used to enable and disable toxics. The api is not started if not set.
+    pub control_port: Option<u16>,
+}
+
+impl TcpProxyConfiguration {
+    /**
+     * Create a new tcp proxy configuration.
+     *
+     * `upstream` The upstream address.
+     * `upstream_toxics` Toxics for the data from the client to the upstream.
+     * `downstream_toxics` Toxics for the data from the upstream to the client.
+     * `control_port` Port of the http api used to enable and disable toxics.
+     *
+     * The tcp proxy configuration.
+     */
+    #[must_use]
+    pub fn new(upstream: String, upstream_toxics: Vec<ToxicConfiguration>, downstream_toxics: Vec<ToxicConfiguration>, control_port: Option<u16>) -> Self {
+        TcpProxyConfiguration { upstream, upstream_toxics, downstream_toxics, control_port }
+    }
+}
+
+/**
+ * The type of network fault injected by a tcp proxy.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum ToxicType {
+    // Delay each chunk by latencyMs plus or minus jitterMs.
+    Latency,
+    // Limit the rate to bytesPerSecond.
+    Bandwidth,
+    // Write the data in fragments of sliceSize bytes with sliceDelayMs between them.
+    Slicer,
+    // Close the connection after bytes bytes.
+    LimitData,
+    // Discard the data.
+    Blackhole,
+    // Shut down the write side after bytes bytes. The other direction stays open.
+    HalfClose,
+}
+
+/**
+ * A network fault for a tcp proxy. The fields used depend on the toxic type.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ToxicConfiguration {
+    // Unique name used to enable and disable the toxic.
+    pub name: String,
+    // The type of toxic.
+    pub toxic_type: ToxicType,
+    // The toxic is enabled when the proxy starts. Default is true.
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    // Added delay. Used by latency.
+    pub latency_ms: Option<u64>,
+    // Random variation of the delay. Used by latency.
+    pub jitter_ms: Option<u64>,
+    // The rate limit. Used by bandwidth.
+    pub bytes_per_second: Option<u64>,
+    // The fragment size. Used by slicer.
+    pub slice_size: Option<usize>,
+    // Time between the fragments. Used by slicer.
+    pub slice_delay_ms: Option<u64>,
+    // Number of bytes forwarded before the toxic acts. Used by limit data and half close. Default is 0.
+    pub bytes: Option<u64>,
+}
+
+impl ToxicConfiguration {
+    /**
+     * Create a new enabled toxic. The fields used by the toxic type must be set afterwards.
+     *
+     * `name` Unique name used to enable and disable the toxic.
+     * `toxic_type` The type of toxic.
+     *
+     * The toxic configuration.
+     */
+    #[must_use]
+    pub fn new(name: String, toxic_type: ToxicType) -> Self {
+        ToxicConfiguration { name, toxic_type, enabled: true, latency_ms: None, jitter_ms: None, bytes_per_second: None, slice_size: None, slice_delay_ms: None, bytes: None }
+    }
+}
+
+/**
+ * Configuration for a udp listener. Each datagram is answered with the response of the first matching rule, otherwise with the data or file.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpListenerData {
+    // The ID of the listener. This is a UUID automatically generated.
+    pub id: String,
+    // The port to listen on.
+    pub port: u16,
+    // The file to respond with.
+    pub file: Option<String>,
+    // The data to respond with. If this is set, the file will be ignored.
+    pub data: Option<String>,
+    // How the data is encoded. Default is text.
+    #[serde(default = "default_data_encoding")]
+    pub data_encoding: DataEncoding,
+    // The rules. The first rule matching a datagram is used.
+    pub rules: Option<Vec<UdpRuleConfiguration>>,
+    // Time to wait before responding.
+    pub delay_write_ms: Option<u64>,
+    // Probability between 0.0 and 1.0 that a received datagram is dropped without a response. If not set no datagrams are dropped.
+    pub drop_probability: Option<f64>,
+}
+
+impl UdpListenerData {
+    /**
+     * Create a new udp configuration. The data is text. No rules are used and no datagrams are dropped.
+     *
+     * `file` The file to respond with.
+     * `data` The data to respond with. If this is set, the file will be ignored.
+     * `delay_write_ms` Time to wait before responding.
+     * `port` The port to listen on.
+     *
+     * # Errors
+     * An error if the identifier could not be generated.
+     */
+    pub fn new(file: Option<String>, data: Option<String>, delay_write_ms: Option<u64>, port: u16) -> Result<Self, ApplicationError> {
+        let id = get_identifier()?;
+        Ok(UdpListenerData { id, port, file, data, data_encoding: DataEncoding::Text, rules: None, delay_write_ms, drop_probability: None })
+    }
+}
+
+/**
+ * A rule for a udp listener. The response of the first rule matching a datagram is returned.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpRuleConfiguration {
+    // Regular expression the datagram must match.
+    pub regex: Option<String>,
+    // Bytes as hex the datagram must contain. Whitespace is ignored.
+    pub hex: Option<String>,
+    // The response. Nothing is sent if not set.
+    pub response: Option<TcpResponseConfiguration>,
+}
+
+impl UdpRuleConfiguration {
+    /**
+     * Create a new udp rule. A rule without regex and hex matches every datagram.
+     *
+     * `regex` Regular expression the datagram must match.
+     * `hex` Bytes as hex the datagram must contain.
+     * `response` The response.
+     *
+     * The udp rule.
+     */
+    #[must_use]
+    pub fn new(regex: Option<String>, hex: Option<String>, response: Option<TcpResponseConfiguration>) -> Self {
+        UdpRuleConfiguration { regex, hex, response }
+    }
+}
+
+/**
+ * Configuration for a dns server. Queries are answered over udp and tcp from the records.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsServerConfiguration {
+    // The ID of the dns server. This is a UUID automatically generated.
+    pub id: String,
+    // The port to listen on for udp and tcp.
+    pub port: u16,
+    // The records.
+    #[serde(default)]
+    pub records: Vec<DnsRecordConfiguration>,
+    // Time to wait before answering.
+    pub delay_ms: Option<u64>,
+    // Failures returned instead of the records. The first matching fault is used.
+    pub faults: Option<Vec<DnsFaultConfiguration>>,
+    // Address of the dns server unknown names are forwarded to, like 127.0.0.53:53. Unknown names are NXDOMAIN if not set.
+    pub forward: Option<String>,
+}
+
+impl DnsServerConfiguration {
+    /**
+     * Create a new dns server configuration without delay, faults or forwarding.
+     *
+     * `port` The port to listen on.
+     * `records` The records.
+     *
+     * # Errors
+     * An error if the identifier could not be generated.
+     */
+    pub fn new(port: u16, records: Vec<DnsRecordConfiguration>) -> Result<Self, ApplicationError> {
+        let id = get_identifier()?;
+        Ok(DnsServerConfiguration { id, port, records, delay_ms: None, faults: None, forward: None })
+    }
+}
+
+/**
+ * The type of a dns record.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum DnsRecordType {
+    // IPv4 address.
+    A,
+    // IPv6 address.
+    AAAA,
+    // Alias for another name.
+    CNAME,
+    // Text.
+    TXT,
+    // Service location with priority, weight, port and target.
+    SRV,
+}
+
+/**
+ * A dns record. The value is the address for A and AAAA, the text for TXT and the target name for CNAME and SRV.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsRecordConfiguration {
+    // The name. Names are matched without case.
+    pub name: String,
+    // The record type.
+    pub record_type: DnsRecordType,
+    // The value.
+    pub value: String,
+    // Time to live in seconds. Default is 60.
+    #[serde(default = "default_dns_ttl")]
+    pub ttl: u32,
+    // Priority of an SRV record. Default is 0.
+    pub priority: Option<u16>,
+    // Weight of an SRV record. Default is 0.
+    pub weight: Option<u16>,
+    // Port of an SRV record. Required for SRV.
+    pub port: Option<u16>,
+}
+
+impl DnsRecordConfiguration {
+    /**
+     * Create a new dns record with the default time to live. The SRV fields must be set afterwards.
+     *
+     * `name` The name.
+     * `record_type` The record type.
+     * `value` The value.
+     *
+     * The dns record.
+     */
+    #[must_use]
+    pub fn new(name: String, record_type: DnsRecordType, value: String) -> Self {
+        DnsRecordConfiguration { name, record_type, value, ttl: default_dns_ttl(), priority: None, weight: None, port: None }
+    }
+}
+
+/**
+ * The failure returned by a dns fault.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum DnsFaultType {
+    // The name does not exist.
+    NxDomain,
+    // The server failed.
+    ServFail,
+}
+
+/**
+ * A failure injected by a dns server.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsFaultConfiguration {
+    // Regular expression the queried name must match. Every name matches if not set.
+    pub name: Option<String>,
+    // The failure.
+    pub fault_type: DnsFaultType,
+    // Probability between 0.0 and 1.0 that the fault is injected. If not set the fault is always injected.
+    pub probability: Option<f64>,
+}
+
+impl DnsFaultConfiguration {
+    /**
+     * Create a new dns fault.
+     *
+     * `name` Regular expression the queried name must match.
+     * `fault_type` The failure.
+     * `probability` Probability that the fault is injected.
+     *
+     * The dns fault.
+     */
+    #[must_use]
+    pub fn new(name: Option<String>, fault_type: DnsFaultType, probability: Option<f64>) -> Self {
+        DnsFaultConfiguration { name, fault_type, probability }
+    }
+}
+
+/**
+ * Configuration for a smtp server. Accepted messages are captured.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SmtpServerConfiguration {
+    // The ID of the smtp server. This is a UUID automatically generated.
+    pub id: String,
+    // The port to listen on.
+    pub port: u16,
+    // The domain in the greeting. Default is localhost.
+    pub domain: Option<String>,
+    // STARTTLS is offered if set.
+    pub tls: Option<TcpTlsConfiguration>,
+    // Users accepted by AUTH PLAIN. Any credentials are accepted if not set.
+    pub users: Option<Vec<SmtpUserConfiguration>>,
+    // Require AUTH before MAIL. Default is false.
+    #[serde(default)]
+    pub require_auth: bool,
+    // Regular expressions for rejected recipients.
+    pub reject_recipients: Option<Vec<String>>,
+    // Replies injected instead of the normal replies. The first matching reply is used.
+    pub replies: Option<Vec<SmtpReplyConfiguration>>,
+    // Directory the captured messages are written to. Messages are only logged if not set.
+    pub capture_directory: Option<String>,
+    // Maximum size of a message in bytes. Larger messages are rejected with 552. Default is 10 MiB.
+    pub max_message_size: Option<usize>,
+}
+
+impl SmtpServerConfiguration {
+    /**
+     * Create a new smtp server configuration. STARTTLS is not offered, any credentials are accepted and every recipient is accepted.
+     *
+     * `port` The port to listen on.
+     * `capture_directory` Directory the captured messages are written to.
+     *
+     * # Errors
+     * An error if the identifier could not be generated.
+     */
+    pub fn new(port: u16, capture_directory: Option<String>) -> Result<Self, ApplicationError> {
+        let id = get_identifier()?;
+        Ok(SmtpServerConfiguration { id, port, domain: None, tls: None, users: None, require_auth: false, reject_recipients: None, replies: None, capture_directory, max_message_size: None })
+    }
+}
+
+/**
+ * A user accepted by a smtp server.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SmtpUserConfiguration {
+    // The user name.
+    pub username: String,
+    // The password.
+    pub password: String,
+}
+
+impl SmtpUserConfiguration {
+    /**
+     * Create a new smtp user.
+     *
+     * `username` The user name.
+     * `password` The password.
+     *
+     * The smtp user.
+     */
+    #[must_use]
+    pub fn new(username: String, password: String) -> Self {
+        SmtpUserConfiguration { username, password }
+    }
+}
+
+/**
+ * The stage of a smtp conversation a reply is injected at.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum SmtpStage {
+    // The greeting.
+    Connect,
+    // EHLO and HELO.
+    Hello,
+    // AUTH.
+    Auth,
+    // MAIL.
+    Mail,
+    // RCPT.
+    Rcpt,
+    // DATA.
+    Data,
+    // The end of the message. The message is not captured.
+    Message,
+}
+
+/**
+ * A reply injected by a smtp server. The connection is closed after a 421 reply.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SmtpReplyConfiguration {
+    // The stage the reply is injected at.
+    pub stage: SmtpStage,
+    // The reply code, like 451 or 550.
+    pub code: u16,
+    // The reply text. Default is "Injected reply".
+    pub text: Option<String>,
+    // Probability between 0.0 and 1.0 that the reply is injected. If not set the reply is always injected.
+    pub probability: Option<f64>,
+}
+
+impl SmtpReplyConfiguration {
+    /**
+     * Create a new injected smtp reply.
+     *
+     * `stage` The stage the reply is injected at.
+     * `code` The reply code.
+     * `text` The reply text.
+     * `probability` Probability that the reply is injected.
+     *
+     * The injected reply.
+     */
+    #[must_use]
+    pub fn new(stage: SmtpStage, code: u16, text: Option<String>, probability: Option<f64>) -> Self {
+        SmtpReplyConfiguration { stage, code, text, probability }
+    }
+}
+
+/**
+ * Configuration for a mock response.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MockResponseConfiguration {
+    // The response to return when the mock is called.
+    pub response: Option<String>,
+    // The status code to return when the mock is called.
+    pub status: String,
+    // The headers to return when the mock is called.
+    pub headers: HashMap<String, String>,
+    // Time to wait in milliseconds before returning the response.
+    pub delay: u64,
+    // Fault to inject instead of the response.
+    pub fault: Option<FaultConfiguration>,
+    // Random latency added to the delay.
+    pub latency: Option<LatencyConfiguration>,
+    // Error rate for returning an alternate response.
+    pub error_rate: Option<ErrorRateConfiguration>,
+}
+
+impl MockResponseConfiguration {
+    /**
+     * Create a new mock response configuration. No fault, latency or error rate is used.
+     *
+     * `response` The response to return when the mock is called.
+     * `status` The status code to return when the mock is called.
+     * `headers` The headers to return when the mock is called.
+     * `delay` Time to wait before returning the response.
+     *
+     * The mock response configuration.
+     */
+    #[must_use]
+    pub fn new(response: Option<String>, status: String, headers: HashMap<String, String>, delay: u64) -> Self {
+        MockResponseConfiguration { response, status, headers, delay, fault: None, latency: None, error_rate: None }
+    }
+}
+
+/**
+ * The distribution used to calculate random latency.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum LatencyDistribution {
+    // Uniform between min and max.
+    Uniform,
+    // Normal distribution with mean and standard deviation.
+    Normal,
+    // Log-normal distribution with mean and standard deviation.
+    LogNormal,
+    // Interpolated between the configured percentiles.
+    Percentiles,
+}
+
+/**
+ * A latency percentile. Example: percentile 99 with delay 800 means 99% of the delays are at most 800ms.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPercentile {
+    // The percentile between 0 and 100.
+    pub percentile: f64,
+    // The delay in milliseconds.
+    pub delay: u64,
+}
+
+/**
+ * Configuration for random latency.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyConfiguration {
+    // The distribution to use.
+    pub distribution: LatencyDistribution,
+    // Minimum delay in milliseconds. Required for uniform. Lower bound for the other distributions.
+    pub min: Option<u64>,
+    // Maximum delay in milliseconds. Required for uniform. Upper bound for the other distributions.
+    pub max: Option<u64>,
+    // Mean delay in milliseconds. Required for normal and log-normal.
+    pub mean: Option<f64>,
+    // Standard deviation in milliseconds. Required for normal and log-normal.
+    pub std_dev: Option<f64>,
+    // The percentiles. Required for percentiles.
+    pub percentiles: Option<Vec<LatencyPercentile>>,
+    // Seed for the random generator. If not set the delays are not reproducible.
+    pub seed: Option<u64>,
+}
+
+impl LatencyConfiguration {
+    /**
+     * Create a new latency configuration.
+     *
+     * `distribution` The distribution to use.
+     * `min` Minimum delay in milliseconds.
+     * `max` Maximum delay in milliseconds.
+     * `mean` Mean delay in milliseconds.
+     * `std_dev` Standard deviation in milliseconds.
+     * `percentiles` The percentiles.
+     * `seed` Seed for the random generator.
+     *
+     * The latency configuration.
+     */
+    #[must_use]
+    pub fn new(distribution: LatencyDistribution, min: Option<u64>, max: Option<u64>, mean: Option<f64>, std_dev: Option<f64>, percentiles: Option<Vec<LatencyPercentile>>, seed: Option<u64>) -> Self {
+        LatencyConfiguration { distribution, min, max, mean, std_dev, percentiles, seed }
+    }
+}
+
+/**
+ * Configuration for returning an alternate response a percentage of the time.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorRateConfiguration {
+    // Percentage between 0 and 100 of requests that get the alternate response.
+    pub percentage: f64,
+    // The alternate status code.
+    pub status: String,
+    // The alternate headers.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    // The alternate response.
+    pub response: Option<String>,
+    // Seed for the random generator. If not set the errors are not reproducible.
+    pub seed: Option<u64>,
+}
+
+impl ErrorRateConfiguration {
+    /**
+     * Create a new error rate configuration.
+     *
+     * `percentage` Percentage of requests that get the alternate response.
+     * `status` The alternate status code.
+     * `headers` The alternate headers.
+     * `response` The alternate response.
+     * `seed` Seed for the random generator.
+     *
+     * The error rate configuration.
+     */
+    #[must_use]
+    pub fn new(percentage: f64, status: String, headers: HashMap<String, String>, response: Option<String>, seed: Option<u64>) -> Self {
+        ErrorRateConfiguration { percentage, status, headers, response, seed }
+    }
+}
+
+/**
+ * The type of fault to inject on a http endpoint.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum FaultType {
+    // Reset the connection (TCP RST) without sending a response.
+    ConnectionReset,
+    // Send the status line and headers, then close the connection.
+    CloseAfterHeaders,
+    // Send a response that is not valid HTTP, then close the connection.
+    MalformedResponse,
+    // Close the connection without sending anything.
+    EmptyReply,
+    // Send random bytes, then close the connection.
+    RandomData,
+    // Send the status line and headers, then reset the HTTP/2 stream (RST_STREAM). HTTP/1 connections are closed.
+    RstStream,
+    // Send GOAWAY on a h2c connection, then close it. Other connections are closed without a response.
+    GoAway,
+}
+
+/**
+ * Configuration for fault injection on a http endpoint.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FaultConfiguration {
+    // The fault to inject.
+    pub fault_type: FaultType,
+    // Probability between 0.0 and 1.0 that the fault is injected. If not set the fault is always injected.
+    pub probability: Option<f64>,
+    // Number of bytes to send for the `RandomData` fault. Default is 1024.
+    pub random_data_length: Option<usize>,
+}
+
+impl FaultConfiguration {
+    /**
+     * Create a new fault configuration.
+     *
+     * `fault_type` The fault to inject.
+     * `probability` Probability that the fault is injected.
+     * `random_data_length` Number of bytes to send for the `RandomData` fault.
+     *
+     * The fault configuration.
+     */
+    #[must_use]
+    pub fn new(fault_type: FaultType, probability: Option<f64>, random_data_length: Option<usize>) -> Self {
+        FaultConfiguration { fault_type, probability, random_data_length }
+    }
+}
+
+/**
+ * The supported TLS versions.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum TlsVersion {
+    TLSv1_0,
+    TLSv1_1,
+    TLSv1_2,
+    TLSv1_3,
+}
+
+impl From<TlsVersion> for String {
+    /**
+     * Convert a `TlsVersion` to a string.
+     */
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::TLSv1_0 => "TLSv1.0".to_string(),
+            TlsVersion::TLSv1_1 => "TLSv1.1".to_string(),
+            TlsVersion::TLSv1_2 => "TLSv1.2".to_string(),
+            TlsVersion::TLSv1_3 => "TLSv1.3".to_string(),
+        }
+    }
+}
+
+impl From<String> for TlsVersion {
+    /**
+     * Convert a string to a `TlsVersion`. Used when converting to the file model.
+     */
+    fn from(version: String) -> Self {
+        let version = version.as_str();
+        match version {
+            "TLSv1.0" => TlsVersion::TLSv1_0,
+            "TLSv1.1" => TlsVersion::TLSv1_1,
+            "TLSv1.2" => TlsVersion::TLSv1_2,
+            _ => TlsVersion::TLSv1_3,
+        }
+    }
+}
+
+/**
+ * Configuration for a route.
+ */
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteConfiguration {
+    // The URL of the endpoint. Not used if upstreams are configured.
+    #[serde(default)]
+    pub url: String,
+    // The proxy url. Example: `<http://localhost:8080>`
+    pub proxy_url: Option<String>,
+    // The log file where the requests and responses are stored.
+    //TODO: Unimplemented
+    pub log: Option<String>,
+    // HTTP/1 only
+    #[serde(default)]
+    pub http1_only: bool,
+    // Accept invalid certificates
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    // Accept invalid hostnames
+    #[serde(default)]
+    pub accept_invalid_hostnames: bool,
+    // Minimum TLS version
+    pub min_tls_version: Option<TlsVersion>,
+    // Maximum TLS version
+    pub max_tls_version: Option<TlsVersion>,
+    // Read timeout
+    pub read_timeout: Option<u64>,
+    // Connect timeout
+    pub connect_timeout: Option<u64>,
+    // Delay before request in milliseconds
+    pub delay_before: Option<u64>,
+    // Delay after request in milliseconds
+    pub delay_after: Option<u64>,
+    // Fault to inject instead of the routed response.
+    pub fault: Option<FaultConfiguration>,
+    // Random latency added before the request.
+    pub latency: Option<LatencyConfiguration>,
+    // Error rate for returning an alternate response instead of routing the request.
+    pub error_rate: Option<ErrorRateConfiguration>,
+    // Rules rewriting the request path, applied in order.
+    pub path_rewrites: Option<Vec<PathRewriteConfiguration>>,
+    // Changes to the headers sent to the url.
+    pub request_headers: Option<HeaderRulesConfiguration>,
+    // Changes to the headers returned to the client.
+    pub response_headers: Option<HeaderRulesConfiguration>,
+    // Upstreams to balance the requests between. Replaces the url if set.
+    pub upstreams: Option<Vec<UpstreamConfiguration>>,
+    // How requests are balanced between the upstreams.
+    pub load_balancing: Option<LoadBalancingConfiguration>,
+    // Client certificate pem file presented to the upstream.
+    pub client_certificate: Option<String>,
+    // Private key pem file in PKCS#8 format for the client certificate.
+    pub client_key: Option<String>,
+    // CA certificate pem file trusted in addition to the system certificates.
+    pub ca_certificate: Option<String>,
+    // Time in milliseconds idle connections are kept in the pool.
+    pub pool_idle_timeout: Option<u64>,
+    // Maximum number of idle connections per host kept in the pool.
+    pub pool_max_idle_per_host: Option<usize>,
+    // Use HTTP/2 without negotiation.
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+}
+
+impl RouteConfiguration {
+    /**
+     * Create a new route configuration.
+     *
+     * `url` The URL of the endpoint. Example `<http://localhost:8080>`
+     * `proxy_url` Proxy to use.
+     * `log` The log file where the requests and responses are stored.
+     * `http1_only` HTTP/1 only
+     * `accept_invalid_certs` Accept invalid certificates
+     * `accept_invalid_hostnames` Accept invalid hostnames
+     * `min_tls_version` Minimum TLS version
+     * `max_tls_version` Maximum TLS version
+     * `read_timeout` Read timeout
+     * `connect_timeout` Connect timeout
+     * `delay_before` Delay before request in milliseconds
+     * `delay_after` Delay after request in milliseconds
+     *
+     * No fault, latency, error rate, path rewrite, header rules, upstreams or client certificate are used.
+     * The default pool settings are used.
+     */
+    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::fn_params_excessive_bools)]
+    #[must_use]
+    pub fn new(
+        url: String,
+        proxy_url: Option<String>,
+        log: Option<String>,
+        http1_only: bool,
+        accept_invalid_certs: bool,
+        accept_invalid_hostnames: bool,
+        min_tls_version: Option<TlsVersion>,
+        max_tls_version: Option<TlsVersion>,
+        read_timeout: Option<u64>,
+        connect_timeout: Option<u64>,
+        delay_before: Option<u64>,
+        delay_after: Option<u64>,
+    ) -> Self {
+        RouteConfiguration {
+            url,
+            proxy_url,
+            log,
+            http1_only,
+            accept_invalid_certs,
+            accept_invalid_hostnames,
+            min_tls_version,
+            max_tls_version,
+            read_timeout,
+            connect_timeout,
+            delay_before,
+            delay_after,
+            fault: None,
+            latency: None,
+            error_rate: None,
+            path_rewrites: None,
+            request_headers: None,
+            response_headers: None,
+            upstreams: None,
+            load_balancing: None,
+            client_certificate: None,
+            client_key: None,
+            ca_certificate: None,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            http2_prior_knowledge: false,
+        }
+    }
+}
+
+/**
+ * An upstream a route can send requests to.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamConfiguration {
+    // The URL of the upstream. Example `<http://localhost:8080>`
+    pub url: String,
+    // Weight used by the weighted strategy. Default is 1.
+    pub weight: Option<u32>,
+}
+
+impl UpstreamConfiguration {
+    /**
+     * Create a new upstream configuration.
+     *
+     * `url` The URL of the upstream.
+     * `weight` Weight used by the weighted strategy.
+     *
+     * The upstream configuration.
+     */
+    #[must_use]
+    pub fn new(url: String, weight: Option<u32>) -> Self {
+        UpstreamConfiguration { url, weight }
+    }
+}
+
+/**
+ * Strategy for choosing the upstream of a request.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub enum LoadBalancingStrategy {
+    // Each upstream in turn.
+    #[default]
+    RoundRobin,
+    // A random upstream.
+    Random,
+    // A random upstream with probability proportional to the weight.
+    Weighted,
+    // The first healthy upstream in the list.
+    Failover,
+}
+
+/**
+ * Configuration for balancing requests between upstreams. An upstream that fails with a connection error
+ * or a timeout is skipped until the cool-down has passed.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadBalancingConfiguration {
+    // The strategy.
+    #[serde(default)]
+    pub strategy: LoadBalancingStrategy,
+    // Time in milliseconds a failed upstream is skipped. Default is 10000.
+    pub cooldown_ms: Option<u64>,
+    // Number of other upstreams tried if the connection fails. Default is 0.
+    pub retries: Option<u32>,
+}
+
+impl LoadBalancingConfiguration {
+    /**
+     * Create a new load balancing configuration.
+     *
+     * `strategy` The strategy.
+     * `cooldown_ms` Time in milliseconds a failed upstream is skipped.
+     * `retries` Number of other upstreams tried if the connection fails.
+     *
+     * The load balancing configuration.
+     */
+    #[must_use]
+    pub fn new(strategy: LoadBalancingStrategy, cooldown_ms: Option<u64>, retries: Option<u32>) -> Self {
+        LoadBalancingConfiguration { strategy, cooldown_ms, retries }
+    }
+}
+
+/**
+ * The type of path rewrite on a route.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum PathRewriteType {
+    // Remove the pattern from the start of the path.
+    StripPrefix,
+    // Replace the pattern at the start of the path with the replacement.
+    ReplacePrefix,
+    // Replace all matches of the regular expression pattern with the replacement. Capture groups are referenced with $1 or ${name}.
+    Regex,
+}
+
+/**
+ * Configuration for rewriting the request path before it is routed.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PathRewriteConfiguration {
+    // The type of rewrite.
+    pub rewrite_type: PathRewriteType,
+    // The prefix or regular expression.
+    pub pattern: String,
+    // The replacement. Parameters are substituted.
+    pub replacement: Option<String>,
+}
+
+impl PathRewriteConfiguration {
+    /**
+     * Create a new path rewrite configuration.
+     *
+     * `rewrite_type` The type of rewrite.
+     * `pattern` The prefix or regular expression.
+     * `replacement` The replacement.
+     *
+     * The path rewrite configuration.
+     */
+    #[must_use]
+    pub fn new(rewrite_type: PathRewriteType, pattern: String, replacement: Option<String>) -> Self {
+        PathRewriteConfiguration { rewrite_type, pattern, replacement }
+    }
+}
+
+/**
+ * Configuration for changing headers on a route. Headers are removed first, then added and finally set.
+ * Header names are case insensitive and parameters are substituted in the values.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderRulesConfiguration {
+    // Headers to remove.
+    #[serde(default)]
+    pub remove: Vec<String>,
+    // Headers to add in addition to existing headers with the same name.
+    #[serde(default)]
+    pub add: HashMap<String, String>,
+    // Headers to set, replacing existing headers with the same name.
+    #[serde(default)]
+    pub set: HashMap<String, String>,
+}
+
+impl HeaderRulesConfiguration {
+    /**
+     * Create a new header rules configuration.
+     *
+     * `remove` Headers to remove.
+     * `add` Headers to add.
+     * `set` Headers to set.
+     *
+     * The header rules configuration.
+     */
+    #[must_use]
+    pub fn new(remove: Vec<String>, add: HashMap<String, String>, set: HashMap<String, String>) -> Self {
+        HeaderRulesConfiguration { remove, add, set }
     }
 }
 
@@ -972,6 +2379,20 @@ fn default_as_true() -> bool {
     true
 }
 
+/**
+ * Default http protocol.
+ */
+fn default_http_protocol() -> HttpProtocol {
+    HttpProtocol::Http1
+}
+
+/**
+ * Default rate limit key.
+ */
+fn default_rate_limit_key() -> RateLimitKey {
+    RateLimitKey::Global
+}
+
 /**
  * Default close connection when.
  */
@@ -979,6 +2400,20 @@ fn default_close_connection_when() -> CloseConnectionWhen {
     CloseConnectionWhen::AfterResponse
 }
 
+/**
+ * Default data encoding.
+ */
+fn default_data_encoding() -> DataEncoding {
+    DataEncoding::Text
+}
+
+/**
+ * Default dns time to live in seconds.
+ */
+fn default_dns_ttl() -> u32 {
+    60
+}
+
 #[cfg(test)]
 mod test {
 
@@ -1,3 +1,5 @@
+pub mod cert;
 pub mod config;
 pub mod error;
+pub mod openapi;
 pub mod settings;
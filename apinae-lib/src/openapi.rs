@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{EndpointConfiguration, EndpointType, MockResponseConfiguration, ServerConfiguration, SetupConfiguration},
+    error::ApplicationError,
+};
+
+/**
+ * The http port of the generated server unless another port is specified.
+ */
+pub const DEFAULT_HTTP_PORT: u16 = 8080;
+/**
+ * The operations of a path item in the order they are imported.
+ */
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+/**
+ * The media type preferred for the mock responses.
+ */
+const JSON_MEDIA_TYPE: &str = "application/json";
+/**
+ * The status code used for the default response.
+ */
+const DEFAULT_STATUS_CODE: &str = "200";
+/**
+ * The maximum depth of references and schemas followed when a response is synthesized. Deeper schemas become null.
+ */
+const MAX_DEPTH: usize = 16;
+
+/**
+ * Import an OpenAPI 3.x file. The file is read as JSON if the extension is json, otherwise as YAML.
+ *
+ * `path` The path of the OpenAPI document.
+ * `http_port` The http port of the generated server.
+ *
+ * The setup with a server mocking the operations.
+ *
+ * # Errors
+ * An error if the file could not be read.
+ * An error if the document is not a valid OpenAPI 3.x document.
+ */
+pub fn import_file(path: &str, http_port: u16) -> Result<SetupConfiguration, ApplicationError> {
+    let content = std::fs::read_to_string(path).map_err(|err| ApplicationError::FileError(format!("Failed to read {path}: {err}")))?;
+    let document = if path.to_lowercase().ends_with(".json") {
+        serde_json::from_str(&content).map_err(|err| ApplicationError::FileError(format!("Failed to parse {path} as JSON: {err}")))?
+    } else {
+        serde_norway::from_str(&content).map_err(|err| ApplicationError::FileError(format!("Failed to parse {path} as YAML: {err}")))?
+    };
+    import(&document, http_port)
+}
+
+/**
+ * Generate a setup from an OpenAPI 3.x document. The server has one mocked endpoint per operation.
+ * The path templates become path expressions prefixed with the path of the first server url.
+ * Paths without templates are matched first.
+ *
+ * `document` The OpenAPI document.
+ * `http_port` The http port of the generated server.
+ *
+ * The setup with a server mocking the operations.
+ *
+ * # Errors
+ * An error if the document is not a valid OpenAPI 3.x document.
+ */
+pub fn import(document: &Value, http_port: u16) -> Result<SetupConfiguration, ApplicationError> {
+    let version = document.get("openapi").and_then(Value::as_str).unwrap_or_default();
+    if !version.starts_with("3.") {
+        return Err(ApplicationError::ConfigurationError(format!("Unsupported OpenAPI version: {version}. Only 3.x is supported")));
+    }
+    let info = document.get("info");
+    let title = info.and_then(|info| info.get("title")).and_then(Value::as_str).unwrap_or("OpenAPI").to_string();
+    let description = info.and_then(|info| info.get("description")).and_then(Value::as_str).map_or_else(|| format!("Imported from OpenAPI {version}"), ToString::to_string);
+    let base_path = get_base_path(document);
+    let mut operations = Vec::new();
+    for (path, path_item) in document.get("paths").and_then(Value::as_object).into_iter().flatten() {
+        let path_item = resolve(document, path_item, 0);
+        for method in METHODS {
+            if let Some(operation) = path_item.get(method) {
+                operations.push((format!("{base_path}{path}"), method, resolve(document, operation, 0)));
+            }
+        }
+    }
+    operations.sort_by_key(|(path, _, _)| path.matches('{').count());
+    let mut endpoints = Vec::new();
+    for (path, method, operation) in operations {
+        let mock = get_mock_response(document, operation);
+        endpoints.push(EndpointConfiguration::new(Some(get_path_expression(&path)), Some(method.to_uppercase()), None, Some(EndpointType::Mock { configuration: mock }))?);
+    }
+    let server = ServerConfiguration::new(title.clone(), Some(http_port), endpoints, None)?;
+    SetupConfiguration::new(title, description, vec![server], Vec::new(), None, None)
+}
+
+/**
+ * Get the path of the first server url without a trailing slash. Server urls are absolute or relative to the document.
+ *
+ * `document` The OpenAPI document.
+ *
+ * The base path of the operations. Empty if there are no servers.
+ */
+fn get_base_path(document: &Value) -> String {
+    let url = document.get("servers").and_then(|servers| servers.get(0)).and_then(|server| server.get("url")).and_then(Value::as_str).unwrap_or_default();
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |index| &rest[index..]),
+        None => url,
+    };
+    path.trim_end_matches('/').to_string()
+}
+
+/**
+ * Convert a path template to a regular expression. Path parameters like `{id}` match a single segment. A query string is allowed after the path.
+ *
+ * `path` The path template.
+ *
+ * The path expression.
+ */
+fn get_path_expression(path: &str) -> String {
+    let mut expression = String::from("^");
+    let mut rest = path;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        escape(&rest[..start], &mut expression);
+        expression.push_str("[^/?]+");
+        rest = &rest[start + end + 1..];
+    }
+    escape(rest, &mut expression);
+    expression.push_str("(\\?.*)?$");
+    expression
+}
+
+/**
+ * Escape the characters with a special meaning in regular expressions.
+ *
+ * `text` The literal text.
+ * `expression` The expression the escaped text is appended to.
+ */
+fn escape(text: &str, expression: &mut String) {
+    for character in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(character) {
+            expression.push('\\');
+        }
+        expression.push(character);
+    }
+}
+
+/**
+ * Build the mock response of an operation from the first successful response, the default response or the first response.
+ * The body is the example of the media type, the first of the examples or synthesized from the schema.
+ * JSON is preferred if the response has several media types.
+ *
+ * `document` The OpenAPI document.
+ * `operation` The operation.
+ *
+ * The mock response.
+ */
+fn get_mock_response(document: &Value, operation: &Value) -> MockResponseConfiguration {
+    let responses = operation.get("responses").and_then(Value::as_object).cloned().unwrap_or_default();
+    let mut codes = responses.keys().filter(|code| code.starts_with('2')).collect::<Vec<_>>();
+    codes.sort();
+    let code = codes.first().copied().or_else(|| responses.keys().find(|code| *code == "default")).or_else(|| responses.keys().next());
+    let Some(code) = code else {
+        return MockResponseConfiguration::new(None, DEFAULT_STATUS_CODE.to_string(), HashMap::new(), 0);
+    };
+    let status = if code.len() == 3 && code.chars().all(|character| character.is_ascii_digit()) { code.clone() } else { DEFAULT_STATUS_CODE.to_string() };
+    let response = resolve(document, &responses[code], 0);
+    let content = response.get("content").and_then(Value::as_object);
+    let media = content.and_then(|content| content.get_key_value(JSON_MEDIA_TYPE).or_else(|| content.iter().next()));
+    let Some((media_type, media)) = media else {
+        return MockResponseConfiguration::new(None, status, HashMap::new(), 0);
+    };
+    let body = match get_example(document, media) {
+        Value::String(body) => body,
+        body => serde_json::to_string_pretty(&body).unwrap_or_default(),
+    };
+    MockResponseConfiguration::new(Some(body), status, HashMap::from([("Content-Type".to_string(), media_type.clone())]), 0)
+}
+
+/**
+ * Get the example of a media type.
+ *
+ * `document` The OpenAPI document.
+ * `media` The media type object.
+ *
+ * The example, the value of the first of the examples or an example synthesized from the schema.
+ */
+fn get_example(document: &Value, media: &Value) -> Value {
+    if let Some(example) = media.get("example") {
+        return example.clone();
+    }
+    let first = media.get("examples").and_then(Value::as_object).and_then(|examples| examples.values().next());
+    if let Some(value) = first.and_then(|example| resolve(document, example, 0).get("value")) {
+        return value.clone();
+    }
+    media.get("schema").map_or(Value::Null, |schema| synthesize(document, schema, 0))
+}
+
+/**
+ * Synthesize an example from a schema. Examples, defaults and enums of the schema are used when present.
+ * Objects get all properties, arrays a single item and the first of oneOf and anyOf is used.
+ *
+ * `document` The OpenAPI document.
+ * `schema` The schema.
+ * `depth` The depth of the schema.
+ *
+ * The example.
+ */
+fn synthesize(document: &Value, schema: &Value, depth: usize) -> Value {
+    if depth > MAX_DEPTH {
+        return Value::Null;
+    }
+    let schema = resolve(document, schema, depth);
+    if let Some(example) = schema.get("example").or_else(|| schema.get("default")).or_else(|| schema.get("const")) {
+        return example.clone();
+    }
+    if let Some(value) = schema.get("enum").and_then(|values| values.get(0)) {
+        return value.clone();
+    }
+    if let Some(schemas) = schema.get("allOf").and_then(Value::as_array) {
+        let mut object = Map::new();
+        for schema in schemas {
+            if let Value::Object(properties) = synthesize(document, schema, depth + 1) {
+                object.extend(properties);
+            }
+        }
+        return Value::Object(object);
+    }
+    if let Some(schema) = schema.get("oneOf").or_else(|| schema.get("anyOf")).and_then(|schemas| schemas.get(0)) {
+        return synthesize(document, schema, depth + 1);
+    }
+    let schema_type = match schema.get("type") {
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).find(|schema_type| *schema_type != "null"),
+        Some(schema_type) => schema_type.as_str(),
+        None => None,
+    };
+    match schema_type {
+        Some("array") => Value::Array(schema.get("items").map(|items| synthesize(document, items, depth + 1)).into_iter().collect()),
+        Some("string") => Value::String(get_string_example(schema.get("format").and_then(Value::as_str)).to_string()),
+        Some("integer") => Value::from(0),
+        Some("number") => Value::from(0.0),
+        Some("boolean") => Value::Bool(true),
+        Some("object") | None => match schema.get("properties").and_then(Value::as_object) {
+            Some(properties) => Value::Object(properties.iter().map(|(name, property)| (name.clone(), synthesize(document, property, depth + 1))).collect()),
+            None if schema_type.is_some() => Value::Object(Map::new()),
+            None => Value::Null,
+        },
+        Some(_) => Value::Null,
+    }
+}
+
+/**
+ * Get an example string for the format.
+ *
+ * `format` The format of the string.
+ *
+ * The example.
+ */
+fn get_string_example(format: Option<&str>) -> &'static str {
+    match format {
+        Some("date") => "2024-01-01",
+        Some("date-time") => "2024-01-01T00:00:00Z",
+        Some("time") => "00:00:00",
+        Some("uuid") => "00000000-0000-0000-0000-000000000000",
+        Some("email") => "user@example.com",
+        Some("uri" | "url") => "https://example.com",
+        Some("hostname") => "example.com",
+        Some("ipv4") => "127.0.0.1",
+        Some("ipv6") => "::1",
+        _ => "string",
+    }
+}
+
+/**
+ * Follow the local references like `#/components/schemas/Pet` of an object. Other references resolve to null.
+ *
+ * `document` The OpenAPI document.
+ * `value` The object that may be a reference.
+ * `depth` The number of references followed.
+ *
+ * The referenced object or the object itself.
+ */
+fn resolve<'a>(document: &'a Value, value: &'a Value, depth: usize) -> &'a Value {
+    match value.get("$ref").and_then(Value::as_str) {
+        Some(_) if depth > MAX_DEPTH => &Value::Null,
+        Some(reference) => reference.strip_prefix('#').and_then(|pointer| document.pointer(pointer)).map_or(&Value::Null, |value| resolve(document, value, depth + 1)),
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /**
+     * Get the mock response of an endpoint.
+     */
+    fn get_mock(endpoint: &EndpointConfiguration) -> &MockResponseConfiguration {
+        match &endpoint.endpoint_type {
+            Some(EndpointType::Mock { configuration }) => configuration,
+            _ => panic!("Not a mock"),
+        }
+    }
+
+    /**
+     * Test converting path templates to path expressions.
+     */
+    #[test]
+    fn test_get_path_expression() {
+        assert_eq!(get_path_expression("/v1/pets"), "^/v1/pets(\\?.*)?$");
+        assert_eq!(get_path_expression("/pets/{petId}/toys/{toyId}"), "^/pets/[^/?]+/toys/[^/?]+(\\?.*)?$");
+        assert_eq!(get_path_expression("/files/{name}.json"), "^/files/[^/?]+\\.json(\\?.*)?$");
+        let expression = regex::Regex::new(&get_path_expression("/pets/{petId}")).unwrap();
+        assert!(expression.is_match("/pets/3"));
+        assert!(expression.is_match("/pets/3?fields=name"));
+        assert!(!expression.is_match("/pets/3/toys"));
+        assert!(!expression.is_match("/pets?id=3"));
+    }
+
+    /**
+     * Test getting the base path from the server url.
+     */
+    #[test]
+    fn test_get_base_path() {
+        assert_eq!(get_base_path(&serde_json::json!({"servers": [{"url": "https://api.example.com/v1/"}]})), "/v1");
+        assert_eq!(get_base_path(&serde_json::json!({"servers": [{"url": "https://api.example.com"}]})), "");
+        assert_eq!(get_base_path(&serde_json::json!({"servers": [{"url": "/api"}]})), "/api");
+        assert_eq!(get_base_path(&serde_json::json!({})), "");
+    }
+
+    /**
+     * Test synthesizing examples from schemas with references, formats and composition.
+     */
+    #[test]
+    fn test_synthesize() {
+        let document = serde_json::json!({
+            "components": {"schemas": {
+                "Pet": {"type": "object", "properties": {"id": {"type": "integer"}, "name": {"type": "string", "example": "Rex"}, "born": {"type": "string", "format": "date"}}},
+                "Node": {"type": "object", "properties": {"child": {"$ref": "#/components/schemas/Node"}}}
+            }}
+        });
+        let pets = synthesize(&document, &serde_json::json!({"type": "array", "items": {"$ref": "#/components/schemas/Pet"}}), 0);
+        assert_eq!(pets, serde_json::json!([{"id": 0, "name": "Rex", "born": "2024-01-01"}]));
+        let all_of = synthesize(&document, &serde_json::json!({"allOf": [{"$ref": "#/components/schemas/Pet"}, {"properties": {"tag": {"enum": ["dog", "cat"]}}}]}), 0);
+        assert_eq!(all_of["tag"], "dog");
+        assert_eq!(synthesize(&document, &serde_json::json!({"type": ["string", "null"], "format": "uuid"}), 0), "00000000-0000-0000-0000-000000000000");
+        assert_eq!(synthesize(&document, &serde_json::json!({"$ref": "#/components/schemas/Missing"}), 0), Value::Null);
+        assert!(synthesize(&document, &serde_json::json!({"$ref": "#/components/schemas/Node"}), 0).is_object());
+    }
+
+    /**
+     * Test importing a YAML document with examples, schemas and several responses.
+     */
+    #[test]
+    fn test_import() {
+        let document: Value = serde_norway::from_str(
+            r#"
+openapi: 3.0.3
+info:
+  title: Petstore
+servers:
+  - url: https://petstore.example.com/v1
+paths:
+  /pets/{petId}:
+    get:
+      responses:
+        '404':
+          description: Not found
+        '200':
+          description: A pet
+          content:
+            text/plain:
+              schema:
+                type: string
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Pet'
+    delete:
+      responses:
+        '204':
+          description: Deleted
+  /pets/mine:
+    post:
+      responses:
+        default:
+          description: Pets
+          content:
+            application/json:
+              examples:
+                first:
+                  $ref: '#/components/examples/Pets'
+components:
+  schemas:
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+  examples:
+    Pets:
+      value:
+        - name: Rex
+"#,
+        )
+        .unwrap();
+        let setup = import(&document, 8080).unwrap();
+        assert_eq!(setup.name, "Petstore");
+        assert_eq!(setup.description, "Imported from OpenAPI 3.0.3");
+        let server = &setup.servers[0];
+        assert_eq!(server.http_port, Some(8080));
+        assert_eq!(server.endpoints.len(), 3);
+        let mine = &server.endpoints[0];
+        assert_eq!(mine.path_expression.as_deref(), Some("^/v1/pets/mine(\\?.*)?$"));
+        assert_eq!(mine.method.as_deref(), Some("POST"));
+        assert_eq!(get_mock(mine).status, "200");
+        assert_eq!(serde_json::from_str::<Value>(get_mock(mine).response.as_ref().unwrap()).unwrap(), serde_json::json!([{"name": "Rex"}]));
+        let get = &server.endpoints[1];
+        assert_eq!(get.path_expression.as_deref(), Some("^/v1/pets/[^/?]+(\\?.*)?$"));
+        assert_eq!(get.method.as_deref(), Some("GET"));
+        assert_eq!(get_mock(get).status, "200");
+        assert_eq!(get_mock(get).headers.get("Content-Type").map(String::as_str), Some(JSON_MEDIA_TYPE));
+        assert_eq!(serde_json::from_str::<Value>(get_mock(get).response.as_ref().unwrap()).unwrap(), serde_json::json!({"name": "string"}));
+        let delete = &server.endpoints[2];
+        assert_eq!(delete.method.as_deref(), Some("DELETE"));
+        assert_eq!(get_mock(delete).status, "204");
+        assert_eq!(get_mock(delete).response, None);
+        assert!(import(&serde_json::json!({"swagger": "2.0"}), 8080).is_err());
+    }
+}
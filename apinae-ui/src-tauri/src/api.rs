@@ -8,7 +8,9 @@ use crate::{
     state::ProcessData,
 };
 use apinae_lib::{
+    cert::{self, GeneratedCertificates},
     config::{AppConfiguration, CloseConnectionWhen, EndpointConfiguration, EndpointType, HttpsConfiguration, MockResponseConfiguration, ServerConfiguration, TcpListenerData, SetupConfiguration},
+    openapi,
     settings::Settings,
 };
 use tauri::{AppHandle, State};
@@ -182,6 +184,27 @@ pub async fn add_setup(app_data: State<'_, AppData>) -> Result<(), String> {
     Ok(())
 }
 
+/**
+ * Imports an OpenAPI 3.x document in JSON or YAML as a setup with a mocked endpoint per operation.
+ *
+ * `app` The Tauri application handle.
+ * `app_data` The application data.
+ *
+ * # Errors
+ * If no file is selected.
+ * If the document could not be imported.
+ * If the configuration data could not be locked.
+ */
+#[tauri::command]
+pub async fn import_openapi(app: AppHandle, app_data: State<'_, AppData>) -> Result<(), String> {
+    let file_path = app.dialog().file().add_filter("OpenAPI", &["yaml", "yml", "json"]).blocking_pick_file().ok_or("No file selected")?;
+    let setup = openapi::import_file(&get_file_path(file_path)?, openapi::DEFAULT_HTTP_PORT).map_err(|err| err.to_string())?;
+    let mut data = get_configuration_data(&app_data)?;
+    data.setups.push(setup);
+    update_data(&app_data, Some(data))?;
+    Ok(())
+}
+
 /**
  * Updates a setup.
  *
@@ -596,6 +619,27 @@ pub async fn open_dialog(app: AppHandle, name: Option<String>, extension: Option
     dialog.blocking_pick_file().map(|file_path| get_file_path(file_path).unwrap_or_default())
 }
 
+/**
+ * Generates a local CA, a server certificate and a client certificate next to the current configuration file.
+ * Existing certificates are reused.
+ *
+ * `app_data` The application data.
+ * `names` The subject alternative names of the server certificate.
+ *
+ * Returns:
+ * The paths of the certificates.
+ *
+ * # Errors
+ * If there is no current configuration file.
+ * If the certificates could not be generated.
+ */
+#[tauri::command]
+pub async fn generate_certificates(app_data: State<'_, AppData>, names: Vec<String>) -> Result<GeneratedCertificates, String> {
+    let file_path = get_current_file_path(&app_data)?;
+    let directory = Path::new(&file_path).parent().unwrap_or(Path::new("."));
+    cert::generate_missing_certificates(directory, &names).map_err(|err| err.to_string())
+}
+
 /**
  * Starts a setup.
  *
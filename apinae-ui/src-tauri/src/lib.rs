@@ -8,6 +8,7 @@ use state::AppData;
 use crate::api::{
     add_endpoint, add_listener, add_server, add_setup, clean, confirm_dialog, delete_endpoint, delete_listener, delete_server, delete_setup, get_listeners, get_servers, get_setup, get_setups, load,
     load_settings, open_dialog, save, save_as, save_settings, start_setup, stop_setup, update_endpoint, update_listener, update_server, update_setup, get_predefined_sets, add_predefined_set, delete_predefined_set,
+    generate_certificates, import_openapi,
 };
 
 /**
@@ -54,6 +55,7 @@ pub fn run() {
             get_setup,
             update_setup,
             add_setup,
+            import_openapi,
             delete_setup,
             get_servers,
             update_server,
@@ -72,6 +74,7 @@ pub fn run() {
             update_endpoint,
             confirm_dialog,
             open_dialog,
+            generate_certificates,
             start_setup,
             stop_setup,
             save_settings,
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
-use apinae_lib::config::{EndpointConfiguration, EndpointType, HttpsConfiguration, MockResponseConfiguration, RouteConfiguration, ServerConfiguration, TcpListenerData, SetupConfiguration, TlsVersion};
+use apinae_lib::config::{CertificateGenerationConfiguration, EndpointConfiguration, EndpointType, ErrorRateConfiguration, FaultConfiguration, HeaderRulesConfiguration, HttpsConfiguration, LatencyConfiguration, LoadBalancingConfiguration, MockResponseConfiguration, PathRewriteConfiguration, RouteConfiguration, ServerConfiguration, SniCertificateConfiguration, TcpListenerData, SetupConfiguration, TlsVersion, UpstreamConfiguration};
 
 /**
  * This struct represents a setup row for both request and responses.
@@ -176,6 +176,15 @@ pub struct MockRow {
     pub headers: String,
     // The delay for writing responses.
     pub delay: u64,
+    // The fault to inject.
+    #[serde(default)]
+    pub fault: Option<FaultConfiguration>,
+    // The random latency.
+    #[serde(default)]
+    pub latency: Option<LatencyConfiguration>,
+    // The error rate.
+    #[serde(default)]
+    pub error_rate: Option<ErrorRateConfiguration>,
 }
 
 impl From<&MockResponseConfiguration> for MockRow {
@@ -191,6 +200,9 @@ impl From<&MockResponseConfiguration> for MockRow {
                 output
             }),
             delay: mock.delay,
+            fault: mock.fault.clone(),
+            latency: mock.latency.clone(),
+            error_rate: mock.error_rate.clone(),
         }
     }
 }
@@ -200,7 +212,7 @@ impl From<&MockRow> for MockResponseConfiguration {
      * Convert a mock row to a mock response configuration.
      */
     fn from(mock: &MockRow) -> Self {
-        MockResponseConfiguration::new(
+        let mut mock_response = MockResponseConfiguration::new(
             mock.response.clone(),
             mock.status.clone(),
             mock.headers
@@ -214,7 +226,11 @@ impl From<&MockRow> for MockResponseConfiguration {
                 })
                 .collect(),
             mock.delay,
-        )
+        );
+        mock_response.fault = mock.fault.clone();
+        mock_response.latency = mock.latency.clone();
+        mock_response.error_rate = mock.error_rate.clone();
+        mock_response
     }
 }
 
@@ -246,6 +262,48 @@ pub struct RouteRow {
     pub delay_before: Option<u64>,
     // The delay after the request.
     pub delay_after: Option<u64>,
+    // The fault to inject.
+    #[serde(default)]
+    pub fault: Option<FaultConfiguration>,
+    // The random latency.
+    #[serde(default)]
+    pub latency: Option<LatencyConfiguration>,
+    // The error rate.
+    #[serde(default)]
+    pub error_rate: Option<ErrorRateConfiguration>,
+    // The path rewrite rules.
+    #[serde(default)]
+    pub path_rewrites: Option<Vec<PathRewriteConfiguration>>,
+    // The request header rules.
+    #[serde(default)]
+    pub request_headers: Option<HeaderRulesConfiguration>,
+    // The response header rules.
+    #[serde(default)]
+    pub response_headers: Option<HeaderRulesConfiguration>,
+    // The upstreams.
+    #[serde(default)]
+    pub upstreams: Option<Vec<UpstreamConfiguration>>,
+    // The load balancing between the upstreams.
+    #[serde(default)]
+    pub load_balancing: Option<LoadBalancingConfiguration>,
+    // The client certificate.
+    #[serde(default)]
+    pub client_certificate: Option<String>,
+    // The client key.
+    #[serde(default)]
+    pub client_key: Option<String>,
+    // The ca certificate.
+    #[serde(default)]
+    pub ca_certificate: Option<String>,
+    // The pool idle timeout.
+    #[serde(default)]
+    pub pool_idle_timeout: Option<u64>,
+    // The maximum idle connections per host.
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    // The flag to use http2 without negotiation.
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
 }
 
 impl From<&RouteConfiguration> for RouteRow {
@@ -265,6 +323,20 @@ impl From<&RouteConfiguration> for RouteRow {
             connect_timeout: route.connect_timeout,
             delay_before: route.delay_before,
             delay_after: route.delay_after,
+            fault: route.fault.clone(),
+            latency: route.latency.clone(),
+            error_rate: route.error_rate.clone(),
+            path_rewrites: route.path_rewrites.clone(),
+            request_headers: route.request_headers.clone(),
+            response_headers: route.response_headers.clone(),
+            upstreams: route.upstreams.clone(),
+            load_balancing: route.load_balancing.clone(),
+            client_certificate: route.client_certificate.clone(),
+            client_key: route.client_key.clone(),
+            ca_certificate: route.ca_certificate.clone(),
+            pool_idle_timeout: route.pool_idle_timeout,
+            pool_max_idle_per_host: route.pool_max_idle_per_host,
+            http2_prior_knowledge: route.http2_prior_knowledge,
         }
     }
 }
@@ -274,7 +346,7 @@ impl From<&RouteRow> for RouteConfiguration {
      * Convert a route row to a route configuration.
      */
     fn from(route: &RouteRow) -> Self {
-        RouteConfiguration::new(
+        let mut route_configuration = RouteConfiguration::new(
             route.url.clone(),
             route.proxy_url.clone(),
             None,
@@ -287,7 +359,22 @@ impl From<&RouteRow> for RouteConfiguration {
             route.connect_timeout,
             route.delay_before,
             route.delay_after,
-        )
+        );
+        route_configuration.fault = route.fault.clone();
+        route_configuration.latency = route.latency.clone();
+        route_configuration.error_rate = route.error_rate.clone();
+        route_configuration.path_rewrites = route.path_rewrites.clone();
+        route_configuration.request_headers = route.request_headers.clone();
+        route_configuration.response_headers = route.response_headers.clone();
+        route_configuration.upstreams = route.upstreams.clone();
+        route_configuration.load_balancing = route.load_balancing.clone();
+        route_configuration.client_certificate = route.client_certificate.clone();
+        route_configuration.client_key = route.client_key.clone();
+        route_configuration.ca_certificate = route.ca_certificate.clone();
+        route_configuration.pool_idle_timeout = route.pool_idle_timeout;
+        route_configuration.pool_max_idle_per_host = route.pool_max_idle_per_host;
+        route_configuration.http2_prior_knowledge = route.http2_prior_knowledge;
+        route_configuration
     }
 }
 
@@ -307,6 +394,15 @@ pub struct HttpsRow {
     pub client_certificate: Option<String>,
     // The supported tls versions.
     pub supported_tls_versions: Vec<String>,
+    // The certificates selected by SNI hostname.
+    #[serde(default)]
+    pub sni_certificates: Option<Vec<SniCertificateConfiguration>>,
+    // Generation of the certificates when the server is started.
+    #[serde(default)]
+    pub auto_generate: Option<CertificateGenerationConfiguration>,
+    // Offer h2 with ALPN. True if not set.
+    #[serde(default)]
+    pub http2: Option<bool>,
 }
 
 impl From<&HttpsConfiguration> for HttpsRow {
@@ -320,6 +416,9 @@ impl From<&HttpsConfiguration> for HttpsRow {
             https_port: https_config.https_port,
             client_certificate: https_config.client_certificate.clone(),
             supported_tls_versions: https_config.clone().supported_tls_versions.into_iter().map(String::from).collect(),
+            sni_certificates: https_config.sni_certificates.clone(),
+            auto_generate: https_config.auto_generate.clone(),
+            http2: Some(https_config.http2),
         }
     }
 }
@@ -329,13 +428,17 @@ impl From<HttpsRow> for HttpsConfiguration {
      * Convert a https row to a https configuration.
      */
     fn from(https_row: HttpsRow) -> Self {
-        HttpsConfiguration::new(
+        let mut https_config = HttpsConfiguration::new(
             https_row.server_certificate,
             https_row.private_key,
             https_row.https_port,
             https_row.client_certificate,
             https_row.supported_tls_versions.into_iter().map(TlsVersion::from).collect(),
-        )
+        );
+        https_config.sni_certificates = https_row.sni_certificates;
+        https_config.auto_generate = https_row.auto_generate;
+        https_config.http2 = https_row.http2.unwrap_or(true);
+        https_config
     }
 }
 
@@ -472,7 +575,7 @@ mod test {
      */
     #[test]
     fn test_from_mockrow_to_mockresponseconfiguration() {
-        let mock_row = MockRow { response: Some("response".to_owned()), status: String::from("200"), headers: "header: value\nheader2:\n \n".to_owned(), delay: 0 };
+        let mock_row = MockRow { response: Some("response".to_owned()), status: String::from("200"), headers: "header: value\nheader2:\n \n".to_owned(), delay: 0, fault: None, latency: None, error_rate: None };
 
         let mock_config = MockResponseConfiguration::from(&mock_row);
 
@@ -489,7 +592,7 @@ mod test {
      */
     #[test]
     fn test_from_mockrow_to_mockresponseconfiguration_no_header() {
-        let mock_row = MockRow { response: None, status: String::from("200"), headers: String::new(), delay: 0 };
+        let mock_row = MockRow { response: None, status: String::from("200"), headers: String::new(), delay: 0, fault: None, latency: None, error_rate: None };
 
         let mock_config = MockResponseConfiguration::from(&mock_row);
 